@@ -5,6 +5,7 @@ pub fn os_shut_down() -> ! {
     unreachable!()
 }
 
+use core::sync::atomic::{AtomicUsize, Ordering};
 use riscv::register::satp::{self, Mode};
 
 pub fn change_pagetable(paddr: usize) {
@@ -22,6 +23,8 @@ pub fn change_pagetable(paddr: usize) {
     unsafe {
         satp::write(satp_val);
     }
+    // Every address space uses ASID 0, entries of the last one must go.
+    flush_tlb();
 }
 
 pub fn flush_tlb() {
@@ -30,20 +33,70 @@ pub fn flush_tlb() {
     }
 }
 
+/// Flush the TLB of every hart set in `hart_mask`, after page table entries
+/// they may have cached lost rights.
+pub fn flush_tlb_harts(hart_mask: usize) {
+    if hart_mask != 0 {
+        sbi_rt::remote_sfence_vma(hart_mask, 0, 0, usize::MAX);
+    }
+}
+
+/// Make instructions the kernel wrote to memory visible to instruction
+/// fetch, on this hart and the others.
+pub fn flush_icache() {
+    unsafe {
+        core::arch::asm!("fence.i");
+    }
+    sbi_rt::remote_fence_i(0, usize::MAX);
+}
 
+
+static CPU_NUM: AtomicUsize = AtomicUsize::new(1);
+
+/// Number of CPU slots, hart ids are in `0..get_cpu_num()`.
 pub fn get_cpu_num() -> usize {
-    // TODO: This should be initialized from the device tree at boot time.
-    const CPU_NUM: usize = 1;
-    CPU_NUM
+    CPU_NUM.load(Ordering::Relaxed)
+}
+
+/// Set from the device tree before the secondary harts are started.
+pub fn set_cpu_num(num: usize) {
+    CPU_NUM.store(num, Ordering::Relaxed);
 }
 
 pub fn get_cur_cpu_id() -> usize {
-    // riscv::register::mhartid::read()
-    0
+    // The boot code keeps the hart id in tp.
+    let hartid: usize;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) hartid);
+    }
+    hartid
 }
 
 pub fn wait_for_interrupt() {
     unsafe {
         riscv::asm::wfi();
     }
-}
\ No newline at end of file
+}
+pub fn enable_timer_interrupt() {
+    unsafe {
+        riscv::register::sie::set_stimer();
+    }
+}
+
+/// Send an inter-processor interrupt to every hart set in `hart_mask`.
+pub fn send_ipi(hart_mask: usize) {
+    sbi_rt::send_ipi(hart_mask, 0);
+}
+
+/// Sleep until an interrupt arrives, an IPI also wakes the hart.
+///
+/// Supervisor interrupts stay globally disabled, so a pending IPI only ends
+/// `wfi` and is cleared here instead of trapping.
+pub fn wait_for_ipi_or_interrupt() {
+    unsafe {
+        riscv::register::sie::set_ssoft();
+        riscv::asm::wfi();
+        riscv::register::sie::clear_ssoft();
+        riscv::register::sip::clear_ssoft();
+    }
+}
@@ -1,4 +1,4 @@
-use config::target::plat::VIRT_ADDR_START;
+use config::target::plat::{BOOT_STACK_SIZE, MAX_CPUS, VIRT_ADDR_START};
 use core::arch::global_asm;
 use core::ptr::{addr_of_mut};
 use riscv::register::satp;
@@ -60,7 +60,11 @@ unsafe extern "C" fn _start() -> ! {
     naked_asm!(
         "   mv      s0, a0
             mv      s1, a1
+            mv      tp, a0
             la      sp, bstack_top
+            li      t0, {boot_stack_size}
+            mul     t0, a0, t0
+            sub     sp, sp, t0
             li      t0, {virt_addr_start}
             not     t0, t0
             and     sp, sp, t0
@@ -81,11 +85,45 @@ unsafe extern "C" fn _start() -> ! {
         init_mmu = sym init_mmu,
         entry = sym rust_entry,
         virt_addr_start = const VIRT_ADDR_START,
+        boot_stack_size = const BOOT_STACK_SIZE,
+    )
+}
+
+/// Entry of the secondary harts started by [`start_secondary_harts`].
+///
+/// The boot page table is already built, so we only need a stack and the MMU.
+#[unsafe(naked)]
+unsafe extern "C" fn _secondary_start() -> ! {
+    naked_asm!(
+        "   mv      tp, a0
+            la      sp, bstack_top
+            li      t0, {boot_stack_size}
+            mul     t0, a0, t0
+            sub     sp, sp, t0
+            li      t0, {virt_addr_start}
+            not     t0, t0
+            and     sp, sp, t0
+
+            call    {init_mmu}
+
+            li      t0, {virt_addr_start}   // add virtual address
+            or      sp, sp, t0
+
+            la      a2, {entry}
+            or      a2, a2, t0
+            mv      a0, tp
+            jalr    a2                      // call rust_secondary_entry
+        ",
+        init_mmu = sym init_mmu,
+        entry = sym rust_secondary_entry,
+        virt_addr_start = const VIRT_ADDR_START,
+        boot_stack_size = const BOOT_STACK_SIZE,
     )
 }
 
 unsafe extern "C" {
     fn kernel_main(hartid: usize, dtb: usize);
+    fn kernel_secondary_main(hartid: usize);
 }
 
 global_asm!(
@@ -94,10 +132,11 @@ global_asm!(
         .global bstack
         .global bstack_top
         bstack:
-        .fill 0x80000
+        .fill {size}
         .size bstack, . - bstack
         bstack_top:
-    "
+    ",
+    size = const BOOT_STACK_SIZE * MAX_CPUS,
 );
 
 pub fn rust_entry(hartid: usize, dtb: usize) {
@@ -110,3 +149,26 @@ pub fn rust_entry(hartid: usize, dtb: usize) {
         kernel_main(hartid, dtb);
     }
 }
+
+pub fn rust_secondary_entry(hartid: usize) {
+    unsafe { sstatus::set_sum() };
+    unsafe {
+        kernel_secondary_main(hartid);
+    }
+}
+
+/// Start every hart in `hart_ids` except `boot_hart` through SBI HSM.
+pub fn start_secondary_harts(boot_hart: usize, hart_ids: &[usize]) {
+    // hart_start wants the physical address of the entry.
+    let entry = _secondary_start as usize & !VIRT_ADDR_START;
+    for &hartid in hart_ids.iter().filter(|&&id| id != boot_hart) {
+        if hartid >= MAX_CPUS {
+            println!("hart {} exceeds MAX_CPUS, keep it parked", hartid);
+            continue;
+        }
+        let ret = sbi_rt::hart_start(hartid, entry, 0);
+        if ret.error != 0 {
+            println!("failed to start hart {}: {:?}", hartid, ret);
+        }
+    }
+}
@@ -8,13 +8,47 @@ pub mod plat {
     pub const HEAP_SIZE: usize = 0x10_0000;
     pub const STACK_SIZE: usize = 0x10_0000;
     pub const FRAME_SIZE: usize = 512 * 1024 * 1024;
+
+    /// 支持的最大 hart 数量, hart id 需小于该值
+    pub const MAX_CPUS: usize = 8;
+
+    /// 每个 hart 的启动栈大小
+    pub const BOOT_STACK_SIZE: usize = 0x80000;
+
+    /// 用户任务时间片长度 (毫秒), 用完后在时钟中断中让出 CPU
+    pub const TIME_SLICE_MS: u64 = 20;
     
     /// 用户态动态链接用户程序的偏移
     pub const USER_DYN_ADDR: usize = 0x20000000;
 
+    /// 用户态位置无关程序 (ET_DYN) 的加载地址, 低于 mmap 区域
+    pub const USER_PIE_ADDR: usize = 0x10000;
+
     /// 用户态栈顶
     pub const USER_STACK_TOP: usize = 0x8000_0000;
 
     /// 用户栈初始大小
     pub const USER_STACK_INIT_SIZE: usize = 0x20000;
+
+    /// 每秒的时钟滴答数, times 等接口以此为单位 (AT_CLKTCK)
+    pub const CLK_TCK: usize = 100;
+
+    /// 用户程序可用的 ISA 扩展位图 (AT_HWCAP), 第 n 位对应字母 'a' + n, 即 rv64imafdc
+    pub const HWCAP: usize = isa_bits(b"imafdc");
+
+    /// 平台名称 (AT_PLATFORM)
+    pub const PLATFORM: &str = "riscv64";
+
+    const fn isa_bits(extensions: &[u8]) -> usize {
+        let mut bits = 0;
+        let mut i = 0;
+        while i < extensions.len() {
+            bits |= 1 << (extensions[i] - b'a');
+            i += 1;
+        }
+        bits
+    }
+
+    /// 信号处理函数返回时跳转的 rt_sigreturn 跳板页地址
+    pub const SIGRETURN_TRAMPOLINE: usize = 0x3f_ffff_f000;
 }
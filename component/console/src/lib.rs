@@ -79,3 +79,9 @@ pub fn init() {
 
     println!("Log level set to: {:?}", log_level);
 }
+
+/// Change the maximum level of the messages that get logged.
+pub fn set_log_level(log_level: LevelFilter) {
+    set_max_level(log_level);
+    println!("Log level set to: {:?}", log_level);
+}
@@ -8,6 +8,9 @@ pub use device_set::{DEVICE_SET, get_block_device, get_device, push_device};
 pub use driver_api::{BlockDriver, DeviceType, Driver};
 
 use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use spin::Mutex;
 use flat_device_tree::{Fdt, node::FdtNode};
 use log::info;
 // virtio_drivers::transport::DeviceType will be used via its full path
@@ -17,15 +20,37 @@ use virtio_drivers::transport::Transport;
 use virtio_drivers::transport::mmio::MmioTransport;
 use virtio_drivers::transport::mmio::VirtIOHeader;
 
+/// Hart ids of the `cpu@N` nodes found in the device tree.
+static HART_IDS: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+/// `bootargs` of the `/chosen` node, the kernel command line.
+static BOOTARGS: Mutex<String> = Mutex::new(String::new());
+
 pub fn init_dt(dtb: usize) {
     info!("device tree @ {:#x}", dtb);
     // Safe because the pointer is a valid pointer to unaliased memory.
     let fdt = unsafe { Fdt::from_ptr(dtb as *const u8).unwrap() };
+    if let Some(bootargs) = fdt
+        .find_node("/chosen")
+        .and_then(|node| node.property("bootargs"))
+        .and_then(|prop| prop.as_str())
+    {
+        info!("bootargs: {}", bootargs);
+        *BOOTARGS.lock() = bootargs.to_string();
+    }
     walk_dt(fdt);
 }
 
+/// The kernel command line, empty if the device tree has none.
+pub fn bootargs() -> String {
+    BOOTARGS.lock().clone()
+}
+
 pub fn walk_dt(fdt: Fdt) {
     for node in fdt.all_nodes() {
+        if node.name.starts_with("cpu@") {
+            cpu_probe(node);
+        }
         if let Some(compatible) = node.compatible() {
             if compatible.all().any(|s| s == "virtio,mmio") {
                 virtio_probe(node);
@@ -34,6 +59,21 @@ pub fn walk_dt(fdt: Fdt) {
     }
 }
 
+pub fn cpu_probe(node: FdtNode) {
+    if let Some(reg) = node.reg().next() {
+        let hartid = reg.starting_address as usize;
+        info!("found cpu, hart id: {}", hartid);
+        HART_IDS.lock().push(hartid);
+    }
+}
+
+/// Get the hart ids of all cpus, sorted.
+pub fn hart_ids() -> Vec<usize> {
+    let mut ids = HART_IDS.lock().clone();
+    ids.sort_unstable();
+    ids
+}
+
 pub fn virtio_probe(node: FdtNode) {
     if let Some(reg) = node.reg().next() {
         let paddr = reg.starting_address as usize;
@@ -0,0 +1,213 @@
+//! Binary formats `execve` knows.
+//!
+//! A format is told by the magic bytes at the start of the file or by the
+//! extension of its name. ELF programs are loaded by the kernel. `#!`
+//! scripts, and the formats registered with an interpreter like Linux
+//! `binfmt_misc` does, run their interpreter with the file as its argument.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use filesystem::file::{File, OpenFlags};
+use filesystem::path::Path;
+use filesystem::vfs::VfsError;
+use lazy_static::lazy_static;
+use log::debug;
+use spin::Mutex;
+
+/// Bytes of the file the formats are matched against, the `#!` line has to
+/// fit in them.
+pub const BINPRM_BUF_SIZE: usize = 256;
+
+/// How many interpreters deep a program may go.
+pub const MAX_INTERP_DEPTH: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinfmtMatch {
+    Magic { offset: usize, magic: Vec<u8> },
+    /// The extension of the file name, without the dot.
+    Extension(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BinfmtKind {
+    /// Loaded by the kernel.
+    Elf,
+    /// Run the interpreter of the `#!` line.
+    Shebang,
+    /// Run this interpreter.
+    Interpreter(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binfmt {
+    pub name: String,
+    pub matcher: BinfmtMatch,
+    pub kind: BinfmtKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinfmtError {
+    Vfs(VfsError),
+    /// No format matches, or the `#!` line is bad.
+    NoExec,
+    /// Too many interpreters deep.
+    Loop,
+}
+
+impl From<VfsError> for BinfmtError {
+    fn from(e: VfsError) -> Self {
+        Self::Vfs(e)
+    }
+}
+
+impl Binfmt {
+    pub fn magic(name: &str, offset: usize, magic: &[u8], kind: BinfmtKind) -> Self {
+        Self {
+            name: name.to_string(),
+            matcher: BinfmtMatch::Magic { offset, magic: magic.to_vec() },
+            kind,
+        }
+    }
+
+    pub fn extension(name: &str, extension: &str, kind: BinfmtKind) -> Self {
+        Self {
+            name: name.to_string(),
+            matcher: BinfmtMatch::Extension(extension.to_string()),
+            kind,
+        }
+    }
+
+    /// Whether the file `path` starting with `header` is of this format.
+    pub fn matches(&self, header: &[u8], path: &str) -> bool {
+        match &self.matcher {
+            BinfmtMatch::Magic { offset, magic } => {
+                header.get(*offset..*offset + magic.len()) == Some(magic.as_slice())
+            }
+            BinfmtMatch::Extension(extension) => {
+                let name = path.rsplit('/').next().unwrap_or(path);
+                name.rsplit_once('.').is_some_and(|(stem, ext)| !stem.is_empty() && ext == extension)
+            }
+        }
+    }
+}
+
+lazy_static! {
+    static ref BINFMTS: Mutex<Vec<Binfmt>> = Mutex::new(vec![
+        Binfmt::magic("elf", 0, b"\x7fELF", BinfmtKind::Elf),
+        Binfmt::magic("script", 0, b"#!", BinfmtKind::Shebang),
+    ]);
+}
+
+/// Add a format, tried after the ones before it. Fails if the name is taken.
+pub fn register_binfmt(binfmt: Binfmt) -> Result<(), Binfmt> {
+    let mut binfmts = BINFMTS.lock();
+    if binfmts.iter().any(|other| other.name == binfmt.name) {
+        return Err(binfmt);
+    }
+    binfmts.push(binfmt);
+    Ok(())
+}
+
+/// Remove the format `name`, giving it back.
+pub fn unregister_binfmt(name: &str) -> Option<Binfmt> {
+    let mut binfmts = BINFMTS.lock();
+    let index = binfmts.iter().position(|binfmt| binfmt.name == name)?;
+    Some(binfmts.remove(index))
+}
+
+fn find_binfmt(header: &[u8], path: &str) -> Option<BinfmtKind> {
+    BINFMTS
+        .lock()
+        .iter()
+        .find(|binfmt| binfmt.matches(header, path))
+        .map(|binfmt| binfmt.kind.clone())
+}
+
+/// The interpreter of a `#!` line and its optional argument, the rest of the
+/// line.
+pub fn parse_shebang(header: &[u8]) -> Option<(String, Option<String>)> {
+    let line = header.strip_prefix(b"#!")?;
+    let line = match line.iter().position(|&b| b == b'\n') {
+        Some(end) => &line[..end],
+        None => line,
+    };
+    let line = core::str::from_utf8(line).ok()?.trim_matches([' ', '\t', '\r']);
+    let (interp, arg) = match line.split_once([' ', '\t']) {
+        Some((interp, arg)) => (interp, Some(arg.trim_matches([' ', '\t']))),
+        None => (line, None),
+    };
+    if interp.is_empty() {
+        return None;
+    }
+    Some((interp.to_string(), arg.filter(|arg| !arg.is_empty()).map(String::from)))
+}
+
+/// Follow the interpreters of `path` down to the ELF program to load, and
+/// the argv it gets: the interpreter, its argument if any, the path of the
+/// file it runs, then `args` without `args[0]`.
+pub fn resolve_exec(mut path: Path, mut args: Vec<String>) -> Result<(Path, Vec<String>), BinfmtError> {
+    for _ in 0..=MAX_INTERP_DEPTH {
+        let name = path.to_string();
+        let file = File::open(&name, OpenFlags::O_RDONLY)?;
+        let mut header = [0u8; BINPRM_BUF_SIZE];
+        let len = file.read_at(0, &mut header)?;
+        let header = &header[..len];
+        let (interp, interp_arg) = match find_binfmt(header, &name).ok_or(BinfmtError::NoExec)? {
+            BinfmtKind::Elf => return Ok((path, args)),
+            BinfmtKind::Shebang => parse_shebang(header).ok_or(BinfmtError::NoExec)?,
+            BinfmtKind::Interpreter(interp) => (interp, None),
+        };
+        debug!("binfmt: {} runs with {} {:?}", name, interp, interp_arg);
+        let mut interp_args = vec![interp.clone()];
+        interp_args.extend(interp_arg);
+        interp_args.push(name);
+        interp_args.extend(args.into_iter().skip(1));
+        args = interp_args;
+        path = Path::new(interp);
+    }
+    Err(BinfmtError::Loop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shebang() {
+        assert_eq!(parse_shebang(b"#!/bin/sh\necho hi\n"), Some(("/bin/sh".to_string(), None)));
+        assert_eq!(
+            parse_shebang(b"#! /usr/bin/env  lua -i \nprint(1)"),
+            Some(("/usr/bin/env".to_string(), Some("lua -i".to_string())))
+        );
+        assert_eq!(parse_shebang(b"#!/busybox\tsh\r\n"), Some(("/busybox".to_string(), Some("sh".to_string()))));
+        assert_eq!(parse_shebang(b"#!/bin/sh"), Some(("/bin/sh".to_string(), None)));
+    }
+
+    #[test]
+    fn bad_shebang() {
+        assert_eq!(parse_shebang(b"#!\n"), None);
+        assert_eq!(parse_shebang(b"#!   \n/bin/sh"), None);
+        assert_eq!(parse_shebang(b"\x7fELF"), None);
+    }
+
+    #[test]
+    fn magic() {
+        let elf = Binfmt::magic("elf", 0, b"\x7fELF", BinfmtKind::Elf);
+        assert!(elf.matches(b"\x7fELF\x02\x01", "/a.out"));
+        assert!(!elf.matches(b"\x7fEL", "/a.out"));
+        assert!(!elf.matches(b"#!/bin/sh", "/a.out"));
+        let offset = Binfmt::magic("x", 2, b"MZ", BinfmtKind::Elf);
+        assert!(offset.matches(b"..MZ", "/x"));
+        assert!(!offset.matches(b"MZ", "/x"));
+    }
+
+    #[test]
+    fn extension() {
+        let lua = Binfmt::extension("lua", "lua", BinfmtKind::Interpreter("/lua".to_string()));
+        assert!(lua.matches(b"print(1)", "/test/sort.lua"));
+        assert!(!lua.matches(b"", "/test/sort.luac"));
+        assert!(!lua.matches(b"", "/test/.lua"));
+        assert!(!lua.matches(b"", "/lua.d/run"));
+    }
+}
@@ -2,26 +2,32 @@
 
 extern crate alloc;
 
+pub mod binfmt;
+pub mod tls;
+
 use alloc::format;
-use alloc::string::ToString;
-use config::riscv64_qemu::plat::USER_DYN_ADDR;
-use config::target::plat::{PAGE_SIZE, USER_STACK_INIT_SIZE, USER_STACK_TOP};
-use core::ops::Mul;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use config::riscv64_qemu::plat::{USER_DYN_ADDR, USER_PIE_ADDR};
+use config::target::plat::{PAGE_SIZE, USER_STACK_TOP};
 use filesystem::{
     file::{File, OpenFlags},
     path::Path,
+    vfs::VfsError,
 };
-use frame::alloc_continues;
-use log::{debug, error};
+use frame::{FrameTracer, alloc_continues, dealloc_continues};
+use log::debug;
 use log::info;
 use mem::{memregion::MemRegion, memset::MemSet};
 use mem::{memregion::MemRegionType, stack::StackRegion};
-use memory_addr::{MemoryAddr, PhysAddr, PhysAddrRange, VirtAddr, VirtAddrRange};
+use memory_addr::{PhysAddr, PhysAddrRange, VirtAddr, VirtAddrRange, align_down, align_up};
 use page_table_multiarch::MappingFlags;
+use tls::{TLS_VARIANT, Tls, TlsLayout, TlsTemplate};
 use xmas_elf::sections::SectionData;
 use xmas_elf::symbol_table::DynEntry64;
 use xmas_elf::symbol_table::Entry;
-use xmas_elf::{ElfFile, header};
+use xmas_elf::{ElfFile, header, program};
 
 pub trait ElfExt {
     fn relocate(&self, base: usize) -> Result<usize, &str>;
@@ -103,219 +109,476 @@ impl ElfExt for ElfFile<'_> {
 
 #[derive(Clone)]
 pub struct LoadElfReturn {
-    pub frame_addr: usize,
-    pub file_size: usize,
     pub ph_count: usize,
+    /// Address of the program headers in the program, `AT_PHDR` once
+    /// moved by `base`.
     pub ph_addr: usize,
     pub ph_entry_size: usize,
     pub entry_point: usize,
     pub memset: MemSet,
     pub stack_region: StackRegion,
     pub heap_bottom: usize,
+    /// How far above its addresses the program is mapped.
+    pub base: usize,
+    /// The program interpreter, if the program has one.
+    pub interp: Option<Interp>,
+    /// The TLS of the main thread, if the program has a `PT_TLS`.
+    pub tls: Option<Tls>,
+}
+
+/// The dynamic loader of a program, named by its `PT_INTERP`.
+#[derive(Debug, Clone, Copy)]
+pub struct Interp {
+    /// Where it is loaded, `AT_BASE`.
     pub base: usize,
-    pub sbss_start:usize,
-    pub sbss_size:usize,
-    pub bss_start:usize,
-    pub bss_size:usize,
+    /// Its entry point, where the program starts.
+    pub entry: usize,
+}
+
+impl LoadElfReturn {
+    /// Where the program starts running, in its interpreter if it has one.
+    pub fn start_pc(&self) -> usize {
+        self.interp.map_or(self.base + self.entry_point, |interp| interp.entry)
+    }
+
+    /// Free the frames of a program that was loaded but won't run.
+    pub fn release(&self) {
+        release_frames(&self.memset);
+        let stack = self.stack_region.paddr_range;
+        dealloc_continues(FrameTracer::new(stack.start), stack.size() / PAGE_SIZE);
+    }
 }
 
 impl core::fmt::Debug for LoadElfReturn {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         f.debug_struct("LoadElfReturn")
-            .field("frame_addr", &format_args!("0x{:x}", self.frame_addr))
-            .field("file_size", &self.file_size)
             .field("ph_addr", &format_args!("0x{:x}", self.ph_addr))
             .field("ph_count", &self.ph_count)
             .field("ph_entry_size", &self.ph_entry_size)
             .field("entry_point", &format_args!("0x{:x}", self.entry_point))
             .field("memset", &self.memset)
             .field("heap_start", &format_args!("0x{:x}", self.heap_bottom))
+            .field("interp", &self.interp)
+            .field("tls", &self.tls)
             .finish()
     }
 }
 
-// 把elf文件存储到frame内存中，返回elf文件的地址，ph地址，entry_point，memset
-pub fn load_elf_frame(path: Path) -> LoadElfReturn {
-    debug!("Loading ELF file from path: {:?}", path);
-    let file = File::open(&path.to_string(), OpenFlags::O_RDONLY).expect("Failed to open ELF file");
-    let file_size = file.get_file_size().expect("Failed to get file size");
-    debug!("ELF file size: {} bytes", file_size);
-
-    let frame_addr = alloc_continues(file_size.div_ceil(PAGE_SIZE))[0]
-        .paddr
-        .as_usize();
-
-    debug!("Allocated frame at address: 0x{:x}", frame_addr);
-
-    let buffer = unsafe { core::slice::from_raw_parts_mut(frame_addr as *mut u8, file_size) };
-    let read_size = file.read_at(0, buffer).expect("Failed to read ELF file");
-    assert_eq!(read_size, file_size);
-    debug!(
-        "Successfully loaded ELF file, size: {}, address: 0x{:x}",
-        file_size, frame_addr
-    );
-    let elf = ElfFile::new(buffer).expect("Failed to parse ELF file");
-    let ph_entry_size = elf.header.pt2.ph_entry_size() as usize;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    /// The program or its interpreter could not be opened or read.
+    Vfs(VfsError),
+    /// Not an ELF program we can run.
+    BadElf(&'static str),
+    NoMemory,
+}
 
-    // 获取要映射的内存区域
-    let mut memset = MemSet::new();
-    let mut elf_region_start_vaddr = 0xffffffffffffffffusize;
-    let ph_count = elf.header.pt2.ph_count();
-    for i in 0..ph_count {
-        let ph = elf.program_header(i).unwrap();
-        if ph.get_type() != Ok(xmas_elf::program::Type::Load) {
-            continue;
-        }
+impl From<VfsError> for LoadError {
+    fn from(e: VfsError) -> Self {
+        Self::Vfs(e)
+    }
+}
 
-        let va = VirtAddr::from(ph.virtual_addr() as usize);
-        if va.as_usize() < elf_region_start_vaddr {
-            elf_region_start_vaddr = va.as_usize();
-        }
-        let mem_size = ph.mem_size() as usize;
-        let mut flags = MappingFlags::USER;
-        if ph.flags().is_read() {
-            flags |= MappingFlags::READ;
-        }
-        if ph.flags().is_write() {
-            flags |= MappingFlags::WRITE;
+/// musl's dynamic loader is `libc.so` itself, which the test images ship at
+/// the root rather than as `/lib/ld-musl-*.so.1`.
+const MUSL_LOADER_FALLBACK: &str = "/libc.so";
+
+/// Whether `path` names musl's dynamic loader, `ld-musl-<arch>.so.1`.
+fn is_musl_loader(path: &str) -> bool {
+    path.rsplit('/').next().is_some_and(|name| name.starts_with("ld-musl-"))
+}
+
+/// Size of the ELF header of a 64-bit file.
+const EHDR_SIZE: usize = 64;
+
+/// How far into the file the program headers may end, they are read into
+/// kernel memory.
+const PHDRS_END_MAX: usize = 64 * 1024;
+
+/// Longest `PT_INTERP` path.
+const PATH_MAX: usize = 4096;
+
+/// The ELF header and the program headers of a file, at their offsets. The
+/// rest of the file is read where it is loaded.
+struct ElfHeaders {
+    buf: Vec<u8>,
+}
+
+impl ElfHeaders {
+    fn read(file: &File) -> Result<Self, LoadError> {
+        let mut buf = vec![0u8; EHDR_SIZE];
+        read_exact_at(file, 0, &mut buf, "short ELF header")?;
+        let elf = ElfFile::new(&buf).map_err(LoadError::BadElf)?;
+        let offset = elf.header.pt2.ph_offset() as usize;
+        let size = elf.header.pt2.ph_count() as usize * elf.header.pt2.ph_entry_size() as usize;
+        let end = offset
+            .checked_add(size)
+            .filter(|&end| end <= PHDRS_END_MAX)
+            .ok_or(LoadError::BadElf("program headers out of reach"))?;
+        if end > buf.len() {
+            buf.resize(end, 0);
         }
-        if ph.flags().is_execute() {
-            flags |= MappingFlags::EXECUTE;
+        read_exact_at(file, offset, &mut buf[offset..end], "program headers out of the file")?;
+        Ok(Self { buf })
+    }
+
+    fn parse(&self) -> Result<ElfFile<'_>, LoadError> {
+        let elf = ElfFile::new(&self.buf).map_err(LoadError::BadElf)?;
+        match elf.header.pt2.type_().as_type() {
+            header::Type::Executable | header::Type::SharedObject => Ok(elf),
+            _ => Err(LoadError::BadElf("not an executable")),
         }
+    }
+}
 
-        let start_va = va.align_down(PAGE_SIZE);
-        let end_va = VirtAddr::from(va.as_usize() + mem_size).align_up(PAGE_SIZE);
+/// Fill `buf` from `file` at `offset`, a short read is `BadElf(what)`.
+fn read_exact_at(file: &File, offset: usize, buf: &mut [u8], what: &'static str) -> Result<(), LoadError> {
+    if file.read_at(offset, buf)? != buf.len() {
+        return Err(LoadError::BadElf(what));
+    }
+    Ok(())
+}
 
-        let region_type = if ph.flags().is_execute() {
-            MemRegionType::Text
-        } else if ph.flags().is_write() {
-            MemRegionType::DATA
-        } else {
-            MemRegionType::RODATA
-        };
+/// Load the program `path` with its interpreter, and make it a stack of
+/// `stack_size` bytes, rounded up to pages.
+///
+/// Every segment gets its own frames: `p_filesz` bytes are read from the
+/// file and the rest up to `p_memsz` is zeroed.
+pub fn load_elf_frame(path: Path, stack_size: usize) -> Result<LoadElfReturn, LoadError> {
+    debug!("Loading ELF file from path: {:?}", path);
+    let file = File::open(&path.to_string(), OpenFlags::O_RDONLY)?;
+    let headers = ElfHeaders::read(&file)?;
+    let elf = headers.parse()?;
 
-        let paddr_start =
-            PhysAddr::from_usize((frame_addr as u64 + ph.offset()) as usize).align_down(PAGE_SIZE);
-        let paddr_end =
-            PhysAddr::from(paddr_start.as_usize() + (end_va.as_usize() - start_va.as_usize()));
-
-        let region = MemRegion::new_mapped(
-            start_va,
-            end_va,
-            paddr_start,
-            paddr_end,
-            flags | MappingFlags::USER,
-            format!("elf_segment_{}", i).to_string(),
-            region_type,
-        );
-        memset.push_region(region);
+    let mut memset = MemSet::new();
+    let result = load_program(&file, &elf, &mut memset, stack_size);
+    if result.is_err() {
+        release_frames(&memset);
     }
+    let (base, interp, tls, stack_region, heap_bottom) = result?;
+    info!("ELF info: base=0x{:x}, heap_bottom=0x{:x}", base, heap_bottom);
 
-    // 获取程序所有段之后的内存，4K 对齐后作为堆底,预先一页大小
-    let heap_bottom = elf
-        .program_iter()
-        .map(|x| (x.virtual_addr() + x.mem_size()) as usize)
-        .max()
-        .unwrap()
-        .div_ceil(PAGE_SIZE)
-        .mul(PAGE_SIZE);
-
-    let heap_start_addr: VirtAddr = heap_bottom.into();
-    let heap_end_addr = heap_start_addr.add(PAGE_SIZE);
-    let heap_size = PAGE_SIZE; // Default heap size
-    let heap_pages = heap_size.div_ceil(PAGE_SIZE);
-    let heap_paddr_start = alloc_continues(heap_pages)[0].paddr;
-    let heap_paddr_end = PhysAddr::from(heap_paddr_start.as_usize() + heap_size);
-
-    // let heap_region = MemRegion::new_mapped(
-    //     heap_start_addr,
-    //     heap_end_addr,
-    //     heap_paddr_start,
-    //     heap_paddr_end,
-    //     MappingFlags::USER | MappingFlags::READ | MappingFlags::WRITE,
-    //     "user_heap".to_string(),
-    //     MemRegionType::HEAP,
-    // );
-    // memset.push_region(heap_region);
-    // debug!(
-    //     "Added user heap: {:?} - {:?}",
-    //     heap_start_addr, heap_end_addr
-    // );
-
-    let map_base = memset.get_base();
-    let base = if elf.header.pt2.type_().as_type() == header::Type::Executable {
-        0
-    } else {
-        map_base
+    Ok(LoadElfReturn {
+        ph_addr: phdr_vaddr(&elf).unwrap_or(0),
+        ph_count: elf.header.pt2.ph_count() as usize,
+        ph_entry_size: elf.header.pt2.ph_entry_size() as usize,
+        entry_point: elf.header.pt2.entry_point() as usize,
+        memset,
+        stack_region,
+        heap_bottom,
+        base,
+        interp,
+        tls,
+    })
+}
+
+fn load_program(
+    file: &File,
+    elf: &ElfFile,
+    memset: &mut MemSet,
+    stack_size: usize,
+) -> Result<(usize, Option<Interp>, Option<Tls>, StackRegion, usize), LoadError> {
+    // A position independent program goes at `USER_PIE_ADDR`, any other
+    // where it asks to be.
+    let base = match elf.header.pt2.type_().as_type() {
+        header::Type::Executable => 0,
+        _ => USER_PIE_ADDR,
+    };
+    load_segments(file, elf, base, memset)?;
+    // The interpreter relocates the program, we only map it.
+    let interp = match interp_path(file, elf)? {
+        Some(path) => Some(load_interp(&path, memset)?),
+        None => None,
     };
 
-    info!("ELF info: map_base=0x{:x}, base=0x{:x}", map_base, base);
     let vaddr_end = VirtAddr::from(USER_STACK_TOP);
-    let vaddr_start = VirtAddr::from_usize(vaddr_end.as_usize() - USER_STACK_INIT_SIZE);
-    let frame_traces = alloc_continues(USER_STACK_INIT_SIZE.div_ceil(PAGE_SIZE));
-    let paddr_start = frame_traces[0].paddr;
-    let paddr_end = PhysAddr::from(paddr_start.as_usize() + USER_STACK_INIT_SIZE);
+    let stack_size = align_up(stack_size.max(1), PAGE_SIZE);
+    let vaddr_start = VirtAddr::from_usize(vaddr_end.as_usize() - stack_size);
+    // The TLS block goes right above the program, out of the way of the
+    // stack growing down, and the heap starts above it.
+    let mut heap_bottom = align_up(load_end(elf, base), PAGE_SIZE);
+    let tls = match tls_template(elf, base)? {
+        Some(template) => {
+            let (tls, end) = load_tls(file, elf, template, heap_bottom, vaddr_start.as_usize(), memset)?;
+            heap_bottom = end;
+            Some(tls)
+        }
+        None => None,
+    };
+    let paddr_start = alloc_continues(stack_size / PAGE_SIZE)
+        .first()
+        .ok_or(LoadError::NoMemory)?
+        .paddr;
+    let paddr_end = PhysAddr::from(paddr_start.as_usize() + stack_size);
     let stack_region = StackRegion::new(
         PhysAddrRange::new(paddr_start, paddr_end),
         VirtAddrRange::new(vaddr_start, vaddr_end),
     );
+    Ok((base, interp, tls, stack_region, heap_bottom))
+}
 
-    let mut bss_start = 0;
-    let mut bss_end = 0;
-    let mut sbss_start = 0;
-    let mut sbss_end = 0;
-    info!("elf_region_start_vaddr: 0x{:x}", elf_region_start_vaddr);
-    for section in elf.section_iter() {
-        if let Ok(name) = section.get_name(&elf) {
-            if name == ".bss" {
-                bss_start = section.address() as usize;
-                bss_end = bss_start + section.size() as usize;
-                debug!("Found .bss section: 0x{:x} - 0x{:x}", bss_start, bss_end);
-            } else if name == ".sbss" {
-                sbss_start = section.address() as usize;
-                sbss_end = sbss_start + section.size() as usize;
-                debug!("Found .sbss section: 0x{:x} - 0x{:x}", sbss_start, sbss_end);
-            }
+/// Free the frames of the regions a failed load allocated.
+pub fn release_frames(memset: &MemSet) {
+    for region in memset.regions.iter() {
+        if let Some(range) = region.paddr_range {
+            dealloc_continues(FrameTracer::new(range.start), range.size() / PAGE_SIZE);
         }
     }
-    let sbss_size = sbss_end - sbss_start;
-    let bss_size = bss_end - bss_start;
-
-    // // Clear .bss and .sbss sections by setting memory to zero
-    // if bss_end > bss_start {
-    //     let bss_start_paddr = frame_addr + bss_start - elf_region_start_vaddr;
-    //     let bss_size = bss_end - bss_start;
-    //     unsafe {
-    //         core::ptr::write_bytes(bss_start_paddr as *mut u8, 0, bss_size);
-    //     }
-    //     debug!("Zeroed .bss section: 0x{:x} - 0x{:x}", bss_start_paddr, bss_start_paddr + bss_size);
-    // }
-    
-    // if sbss_end > sbss_start {
-    //     let sbss_start_paddr = frame_addr + sbss_start - elf_region_start_vaddr;
-    //     let sbss_size = sbss_end - sbss_start;
-    //     unsafe {
-    //         core::ptr::write_bytes(sbss_start_paddr as *mut u8, 0, sbss_size);
-    //     }
-    //     debug!("Zeroed .sbss section: 0x{:x} - 0x{:x}", sbss_start_paddr, sbss_start_paddr + sbss_size);
-    // }
-    
-
-
-    LoadElfReturn {
-        frame_addr,
-        file_size,
-        ph_addr: map_base + elf.header.pt2.ph_offset() as usize,
-        ph_count: ph_count.into(),
-        ph_entry_size,
-        entry_point: elf.header.pt2.entry_point() as usize,
-        memset,
-        stack_region,
-        heap_bottom: heap_start_addr.as_usize(),
+}
+
+fn load_segments_iter<'a>(elf: &'a ElfFile<'a>) -> impl Iterator<Item = program::ProgramHeader<'a>> {
+    elf.program_iter()
+        .filter(|ph| ph.get_type() == Ok(program::Type::Load) && ph.mem_size() > 0)
+}
+
+/// End of the last segment of `elf` mapped `bias` above its addresses.
+fn load_end(elf: &ElfFile, bias: usize) -> usize {
+    load_segments_iter(elf)
+        .map(|ph| bias + (ph.virtual_addr() + ph.mem_size()) as usize)
+        .max()
+        .unwrap_or(bias)
+}
+
+/// Address of the program headers once loaded: `PT_PHDR`, or where the
+/// segment holding them puts them.
+fn phdr_vaddr(elf: &ElfFile) -> Option<usize> {
+    if let Some(ph) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(program::Type::Phdr))
+    {
+        return Some(ph.virtual_addr() as usize);
+    }
+    let ph_offset = elf.header.pt2.ph_offset();
+    load_segments_iter(elf)
+        .find(|ph| (ph.offset()..ph.offset() + ph.file_size()).contains(&ph_offset))
+        .map(|ph| (ph.virtual_addr() + ph_offset - ph.offset()) as usize)
+}
+
+fn segment_flags(flags: program::Flags) -> MappingFlags {
+    let mut mapping = MappingFlags::USER;
+    if flags.is_read() {
+        mapping |= MappingFlags::READ;
+    }
+    if flags.is_write() {
+        mapping |= MappingFlags::WRITE;
+    }
+    if flags.is_execute() {
+        mapping |= MappingFlags::EXECUTE;
+    }
+    mapping
+}
+
+fn region_type(flags: MappingFlags) -> MemRegionType {
+    if flags.contains(MappingFlags::EXECUTE) {
+        MemRegionType::Text
+    } else if flags.contains(MappingFlags::WRITE) {
+        MemRegionType::DATA
+    } else {
+        MemRegionType::RODATA
+    }
+}
+
+/// The path in the `PT_INTERP` of `elf`.
+fn interp_path(file: &File, elf: &ElfFile) -> Result<Option<String>, LoadError> {
+    let Some(ph) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(program::Type::Interp))
+    else {
+        return Ok(None);
+    };
+    let size = ph.file_size() as usize;
+    if size > PATH_MAX {
+        return Err(LoadError::BadElf("bad PT_INTERP"));
+    }
+    let mut bytes = vec![0u8; size];
+    read_exact_at(file, ph.offset() as usize, &mut bytes, "PT_INTERP out of the file")?;
+    let path = bytes.split(|&b| b == 0).next().unwrap_or_default();
+    match core::str::from_utf8(path) {
+        Ok(path) if !path.is_empty() => Ok(Some(String::from(path))),
+        _ => Err(LoadError::BadElf("bad PT_INTERP")),
+    }
+}
+
+/// Load the interpreter `path` at `USER_DYN_ADDR`.
+fn load_interp(path: &str, memset: &mut MemSet) -> Result<Interp, LoadError> {
+    debug!("Loading interpreter {}", path);
+    let file = match File::open(path, OpenFlags::O_RDONLY) {
+        Ok(file) => file,
+        Err(err) if is_musl_loader(path) => File::open(MUSL_LOADER_FALLBACK, OpenFlags::O_RDONLY).map_err(|_| err)?,
+        Err(err) => return Err(err.into()),
+    };
+    let headers = ElfHeaders::read(&file)?;
+    let elf = headers.parse()?;
+    if interp_path(&file, &elf)?.is_some() {
+        return Err(LoadError::BadElf("interpreter has an interpreter"));
+    }
+    let base = match elf.header.pt2.type_().as_type() {
+        header::Type::Executable => 0,
+        _ => USER_DYN_ADDR,
+    };
+    load_segments(&file, &elf, base, memset)?;
+    let interp = Interp {
         base,
-        sbss_start,
-        sbss_size,
-        bss_start,
-        bss_size,
+        entry: base + elf.header.pt2.entry_point() as usize,
+    };
+    info!("Interpreter {} loaded: {:x?}", path, interp);
+    Ok(interp)
+}
+
+/// Read the `PT_LOAD` segments of `elf` into new frames, mapped `bias`
+/// above their addresses, and zero them past the file contents.
+///
+/// Segments need not start on a page. Segments sharing a page get frames
+/// together, and the shared page is mapped once with the rights of both.
+fn load_segments(file: &File, elf: &ElfFile, bias: usize, memset: &mut MemSet) -> Result<(), LoadError> {
+    let segments: Vec<_> = load_segments_iter(elf).collect();
+    if segments.is_empty() {
+        return Err(LoadError::BadElf("no PT_LOAD"));
+    }
+    // The pages of each segment, and the groups of them sharing frames.
+    let mut spans: Vec<(usize, usize, MappingFlags)> = Vec::with_capacity(segments.len());
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for ph in segments.iter() {
+        if ph.file_size() > ph.mem_size() {
+            return Err(LoadError::BadElf("p_filesz above p_memsz"));
+        }
+        if ph.virtual_addr() % PAGE_SIZE as u64 != ph.offset() % PAGE_SIZE as u64 {
+            return Err(LoadError::BadElf("p_vaddr and p_offset differ in the page"));
+        }
+        let vaddr = bias + ph.virtual_addr() as usize;
+        let end = vaddr
+            .checked_add(ph.mem_size() as usize)
+            .filter(|&end| end <= USER_STACK_TOP)
+            .ok_or(LoadError::BadElf("segment out of the user space"))?;
+        let (start, end) = (align_down(vaddr, PAGE_SIZE), align_up(end, PAGE_SIZE));
+        match groups.last_mut() {
+            Some(last) if start < last.0 => return Err(LoadError::BadElf("PT_LOAD out of order")),
+            Some(last) if start < last.1 => last.1 = last.1.max(end),
+            _ => groups.push((start, end)),
+        }
+        spans.push((start, end, segment_flags(ph.flags())));
+    }
+    let page_flags = |vaddr: usize| {
+        spans
+            .iter()
+            .filter(|&&(start, end, _)| (start..end).contains(&vaddr))
+            .fold(MappingFlags::empty(), |flags, &(_, _, x)| flags | x)
+    };
+
+    let mut paddrs = Vec::with_capacity(groups.len());
+    let mut count = 0;
+    for &(start, end) in groups.iter() {
+        let paddr = alloc_continues((end - start) / PAGE_SIZE)
+            .first()
+            .ok_or(LoadError::NoMemory)?
+            .paddr
+            .as_usize();
+        unsafe {
+            core::ptr::write_bytes(paddr as *mut u8, 0, end - start);
+        }
+        paddrs.push(paddr);
+        // A region for each run of pages with the same rights.
+        let mut page = start;
+        while page < end {
+            let flags = page_flags(page);
+            let mut run_end = page + PAGE_SIZE;
+            while run_end < end && page_flags(run_end) == flags {
+                run_end += PAGE_SIZE;
+            }
+            memset.push_region(MemRegion::new_mapped(
+                VirtAddr::from_usize(page),
+                VirtAddr::from_usize(run_end),
+                PhysAddr::from_usize(paddr + page - start),
+                PhysAddr::from_usize(paddr + run_end - start),
+                flags,
+                format!("elf_segment_{:#x}_{}", bias, count),
+                region_type(flags),
+            ));
+            count += 1;
+            page = run_end;
+        }
+    }
+
+    for ph in segments.iter() {
+        let vaddr = bias + ph.virtual_addr() as usize;
+        let index = groups
+            .iter()
+            .position(|&(start, end)| (start..end).contains(&vaddr))
+            .unwrap();
+        let dst = paddrs[index] + vaddr - groups[index].0;
+        let data = unsafe { core::slice::from_raw_parts_mut(dst as *mut u8, ph.file_size() as usize) };
+        read_exact_at(file, ph.offset() as usize, data, "segment out of the file")?;
     }
+    Ok(())
+}
+
+/// The `PT_TLS` of `elf` mapped `bias` above its addresses.
+fn tls_template(elf: &ElfFile, bias: usize) -> Result<Option<TlsTemplate>, LoadError> {
+    let Some(ph) = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(program::Type::Tls))
+    else {
+        return Ok(None);
+    };
+    if ph.file_size() > ph.mem_size() {
+        return Err(LoadError::BadElf("PT_TLS p_filesz above p_memsz"));
+    }
+    let align = ph.align().max(1) as usize;
+    if !align.is_power_of_two() || align > PAGE_SIZE {
+        return Err(LoadError::BadElf("bad PT_TLS alignment"));
+    }
+    Ok(Some(TlsTemplate {
+        vaddr: bias + ph.virtual_addr() as usize,
+        file_size: ph.file_size() as usize,
+        mem_size: ph.mem_size() as usize,
+        align,
+    }))
+}
+
+/// Map a TLS block made from `template` in the pages from `start`, below
+/// `limit`. Gives where the thread pointer points and the end of the pages.
+fn load_tls(
+    file: &File,
+    elf: &ElfFile,
+    template: TlsTemplate,
+    start: usize,
+    limit: usize,
+    memset: &mut MemSet,
+) -> Result<(Tls, usize), LoadError> {
+    let ph = elf
+        .program_iter()
+        .find(|ph| ph.get_type() == Ok(program::Type::Tls))
+        .unwrap();
+    let mut tdata = vec![0u8; template.file_size];
+    read_exact_at(file, ph.offset() as usize, &mut tdata, "PT_TLS out of the file")?;
+
+    let layout = TlsLayout::new(&template, TLS_VARIANT);
+    let size = align_up(layout.size.max(1), PAGE_SIZE);
+    let end = start
+        .checked_add(size)
+        .filter(|&end| end <= limit)
+        .ok_or(LoadError::BadElf("PT_TLS too large"))?;
+    let vaddr = VirtAddr::from_usize(start);
+    let paddr = alloc_continues(size / PAGE_SIZE)
+        .first()
+        .ok_or(LoadError::NoMemory)?
+        .paddr;
+    // All of it, the frames come from the allocator as they were left.
+    let area = unsafe { core::slice::from_raw_parts_mut(paddr.as_usize() as *mut u8, size) };
+    layout.init(area, vaddr.as_usize(), &tdata);
+    memset.push_region(MemRegion::new_mapped(
+        vaddr,
+        VirtAddr::from_usize(end),
+        paddr,
+        PhysAddr::from_usize(paddr.as_usize() + size),
+        MappingFlags::USER | MappingFlags::READ | MappingFlags::WRITE,
+        String::from("tls"),
+        MemRegionType::DATA,
+    ));
+    let tls = Tls {
+        template,
+        tp: vaddr.as_usize() + layout.tp_offset,
+    };
+    debug!("TLS of the main thread: {:x?}", tls);
+    Ok((tls, end))
 }
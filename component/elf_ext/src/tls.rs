@@ -0,0 +1,143 @@
+//! Thread-local storage of the main thread, from the `PT_TLS` of a program.
+//!
+//! The loader lays out a TLS block initialised from the template and points
+//! the thread pointer at it, as each architecture's ABI places them. libc
+//! usually sets up its own TLS, programs without one use ours.
+
+use memory_addr::align_up;
+
+/// The `PT_TLS` of a program, at its loaded address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsTemplate {
+    /// Address of the initialised part, `.tdata`.
+    pub vaddr: usize,
+    /// Size of `.tdata`, the rest up to `mem_size` is `.tbss`.
+    pub file_size: usize,
+    pub mem_size: usize,
+    /// Always a power of two.
+    pub align: usize,
+}
+
+/// The TLS of the main thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tls {
+    pub template: TlsTemplate,
+    /// Initial thread pointer.
+    pub tp: usize,
+}
+
+/// Where the TLS block sits relative to the thread pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsVariant {
+    /// Variant I: the thread pointer is followed by a TCB of `tcb_size`
+    /// bytes, then the TLS block.
+    AboveTp { tcb_size: usize },
+    /// Variant II: the TLS block ends at the thread pointer, which points
+    /// at a TCB of `tcb_size` bytes starting with a pointer to itself.
+    BelowTp { tcb_size: usize },
+}
+
+#[cfg(any(target_arch = "riscv64", target_arch = "loongarch64"))]
+pub const TLS_VARIANT: TlsVariant = TlsVariant::AboveTp { tcb_size: 0 };
+#[cfg(target_arch = "aarch64")]
+pub const TLS_VARIANT: TlsVariant = TlsVariant::AboveTp { tcb_size: 16 };
+#[cfg(target_arch = "x86_64")]
+pub const TLS_VARIANT: TlsVariant = TlsVariant::BelowTp { tcb_size: 8 };
+
+/// A TLS block and its thread pointer in an area aligned to `align`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TlsLayout {
+    /// Size of the area.
+    pub size: usize,
+    pub align: usize,
+    /// Offset of the thread pointer in the area.
+    pub tp_offset: usize,
+    /// Offset of the TLS block in the area.
+    pub data_offset: usize,
+    /// Whether the thread pointer has to point to itself.
+    pub self_pointer: bool,
+}
+
+impl TlsLayout {
+    pub fn new(template: &TlsTemplate, variant: TlsVariant) -> Self {
+        let align = template.align.max(size_of::<usize>());
+        match variant {
+            TlsVariant::AboveTp { tcb_size } => {
+                let data_offset = align_up(tcb_size, align);
+                Self {
+                    size: data_offset + template.mem_size,
+                    align,
+                    tp_offset: 0,
+                    data_offset,
+                    self_pointer: false,
+                }
+            }
+            TlsVariant::BelowTp { tcb_size } => {
+                let tp_offset = align_up(template.mem_size, align);
+                Self {
+                    size: tp_offset + tcb_size.max(size_of::<usize>()),
+                    align,
+                    tp_offset,
+                    data_offset: tp_offset - template.mem_size,
+                    self_pointer: true,
+                }
+            }
+        }
+    }
+
+    /// Fill `area`, at least [`Self::size`] bytes at `vaddr`: the template
+    /// image `tdata`, then zeros, and the TCB self pointer if there is one.
+    pub fn init(&self, area: &mut [u8], vaddr: usize, tdata: &[u8]) {
+        area.fill(0);
+        area[self.data_offset..self.data_offset + tdata.len()].copy_from_slice(tdata);
+        if self.self_pointer {
+            let tp = vaddr + self.tp_offset;
+            area[self.tp_offset..self.tp_offset + size_of::<usize>()].copy_from_slice(&tp.to_ne_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn template(file_size: usize, mem_size: usize, align: usize) -> TlsTemplate {
+        TlsTemplate { vaddr: 0x1000, file_size, mem_size, align }
+    }
+
+    #[test]
+    fn above_tp() {
+        let riscv = TlsLayout::new(&template(0x17, 0x30, 8), TlsVariant::AboveTp { tcb_size: 0 });
+        assert_eq!((riscv.tp_offset, riscv.data_offset, riscv.size), (0, 0, 0x30));
+        let aarch64 = TlsLayout::new(&template(0x17, 0x30, 64), TlsVariant::AboveTp { tcb_size: 16 });
+        assert_eq!((aarch64.tp_offset, aarch64.data_offset, aarch64.size), (0, 64, 0x70));
+        assert!(!aarch64.self_pointer);
+    }
+
+    #[test]
+    fn below_tp() {
+        let layout = TlsLayout::new(&template(0x10, 0x1c, 16), TlsVariant::BelowTp { tcb_size: 8 });
+        assert_eq!(layout.tp_offset, 0x20);
+        assert_eq!(layout.data_offset, 0x20 - 0x1c);
+        assert_eq!(layout.size, 0x28);
+        assert_eq!(layout.tp_offset % layout.align, 0);
+    }
+
+    #[test]
+    fn init() {
+        let layout = TlsLayout::new(&template(4, 12, 4), TlsVariant::BelowTp { tcb_size: 8 });
+        let mut area = vec![0xff; layout.size];
+        layout.init(&mut area, 0x4000, &[1, 2, 3, 4]);
+        let data = &area[layout.data_offset..layout.data_offset + 12];
+        assert_eq!(data, [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let tp = 0x4000 + layout.tp_offset;
+        assert_eq!(area[layout.tp_offset..layout.tp_offset + 8], tp.to_ne_bytes());
+
+        let layout = TlsLayout::new(&template(2, 8, 8), TlsVariant::AboveTp { tcb_size: 16 });
+        let mut area = vec![0xff; layout.size];
+        layout.init(&mut area, 0x4000, &[7, 7]);
+        assert_eq!(area[..16], [0; 16]);
+        assert_eq!(area[16..], [7, 7, 0, 0, 0, 0, 0, 0]);
+    }
+}
@@ -108,6 +108,7 @@ impl Inode for DevFsDirInode {
         Ok(FileAttr {
             size: 0,
             file_type: FileType::Directory,
+            mode: 0o755,
             nlinks: 1,
             uid: 0,
             gid: 0,
@@ -73,6 +73,7 @@ impl Inode for NullDev {
         Ok(FileAttr {
             size: 0,
             file_type: FileType::CharDevice,
+            mode: 0o666,
             nlinks: 1,
             uid: 0,
             gid: 0,
@@ -86,6 +86,7 @@ impl Inode for UartDev {
         Ok(FileAttr {
             size: 0, // UART device size is typically 0
             file_type: self.file_type,
+            mode: 0o666,
             nlinks: 1,
             uid: 0,
             gid: 0,
@@ -76,6 +76,7 @@ impl Inode for ZeroDev {
         Ok(FileAttr {
             size: 0, // /dev/zero is infinite, but getattr usually shows 0 for devices
             file_type: FileType::CharDevice,
+            mode: 0o666,
             nlinks: 1,
             uid: 0,
             gid: 0,
@@ -1,11 +1,27 @@
 use super::file::{File, OpenFlags};
+use super::vfs::{VfsError, VfsResult};
 use alloc::collections::BTreeMap;
 use log::warn;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 
-#[derive(Debug, Default, Clone)]
+/// Default soft limit on open files, the `RLIMIT_NOFILE` of a new process.
+pub const DEFAULT_NOFILE: usize = 1024;
+
+#[derive(Debug, Clone)]
 pub struct FdTable {
     pub table: BTreeMap<usize, File>,
+    /// Fds are below this, `RLIMIT_NOFILE`.
+    pub limit: usize,
+}
+
+impl Default for FdTable {
+    fn default() -> Self {
+        Self {
+            table: BTreeMap::new(),
+            limit: DEFAULT_NOFILE,
+        }
+    }
 }
 
 impl FdTable {
@@ -33,7 +49,10 @@ impl FdTable {
                 OpenFlags::O_WRONLY,
             ),
         ); // stderr
-        Self { table }
+        Self {
+            table,
+            limit: DEFAULT_NOFILE,
+        }
     }
 
     pub fn get(&self, fd: usize) -> Option<&File> {
@@ -56,18 +75,37 @@ impl FdTable {
         }
     }
 
-    pub fn alloc(&mut self, file: File) -> usize {
-        let fd = (3..).find(|fd| self.table.get(fd).is_none()).unwrap();
+    /// Close every fd marked close-on-exec, done by `execve`.
+    pub fn close_on_exec(&mut self) {
+        let fds: Vec<usize> = self
+            .table
+            .iter()
+            .filter(|(_, file)| file.openflags.contains(OpenFlags::O_CLOEXEC))
+            .map(|(fd, _)| *fd)
+            .collect();
+        fds.into_iter().for_each(|fd| self.close(fd));
+    }
+
+    pub fn alloc(&mut self, file: File) -> VfsResult<usize> {
+        self.alloc_from(3, file)
+    }
+
+    /// Put `file` at the lowest free fd not below `start`.
+    pub fn alloc_from(&mut self, start: usize, file: File) -> VfsResult<usize> {
+        let fd = (start..self.limit)
+            .find(|fd| self.table.get(fd).is_none())
+            .ok_or(VfsError::TooManyOpenFiles)?;
         self.table.insert(fd, file);
-        fd
+        Ok(fd)
     }
 
     pub fn set(&mut self, fd: usize, file: File) {
         self.table.insert(fd, file);
     }
 
-    pub fn alloc_id(&mut self) -> usize {
-        let fd = (3..).find(|fd| self.table.get(fd).is_none()).unwrap();
-        fd
+    pub fn alloc_id(&mut self) -> VfsResult<usize> {
+        (3..self.limit)
+            .find(|fd| self.table.get(fd).is_none())
+            .ok_or(VfsError::TooManyOpenFiles)
     }
 }
@@ -1,5 +1,6 @@
 use crate::mount::get_mount_node;
 use crate::path::Path;
+use crate::perm::{Access, check_access, check_delete, current_cred};
 use crate::vfs::{DirEntry, FileType, Inode, VfsError, VfsResult};
 use alloc::{
     string::ToString,
@@ -48,6 +49,30 @@ impl OpenFlags {
     pub fn new_read_write() -> Self {
         Self::O_RDWR
     }
+
+    /// Access to the file the flags need.
+    pub fn access(&self) -> Access {
+        let mut access = Access::empty();
+        if self.is_readable() {
+            access |= Access::READ;
+        }
+        if self.is_writable() {
+            access |= Access::WRITE;
+        }
+        access
+    }
+}
+
+/// Mode of the files `open` creates when the caller gives none.
+pub const DEFAULT_FILE_MODE: u32 = 0o666;
+
+/// Make the caller the owner of a file it just created, with `mode` less
+/// its umask. File systems that don't keep owners or modes are fine with
+/// that.
+fn give_to_caller(inode: &Arc<dyn Inode>, mode: u32) {
+    let cred = current_cred();
+    let _ = inode.chown(cred.uid, cred.gid);
+    let _ = inode.chmod(mode & !cred.umask & 0o7777);
 }
 
 #[derive(Debug, Clone)]
@@ -72,6 +97,12 @@ impl File {
     }
 
     pub fn open_at(&self, path: &str, open_flags: OpenFlags) -> VfsResult<Self> {
+        self.open_at_mode(path, open_flags, DEFAULT_FILE_MODE)
+    }
+
+    /// Open `path` relative to this directory, creating it with `mode` for
+    /// `O_CREAT`.
+    pub fn open_at_mode(&self, path: &str, open_flags: OpenFlags, mode: u32) -> VfsResult<Self> {
         if path == "." {
             return Ok(Self {
                 inner: self.inner.clone(),
@@ -91,10 +122,12 @@ impl File {
 
         let mut current_inode = self.inner.clone();
         for component in dir_components {
+            check_access(&current_inode, Access::EXEC)?;
             current_inode = current_inode.lookup(component)?;
         }
 
         let dir_inode = current_inode;
+        check_access(&dir_inode, Access::EXEC)?;
 
         match dir_inode.lookup(file_name) {
             Ok(inode) => {
@@ -113,6 +146,11 @@ impl File {
                 if attr.file_type == FileType::Directory && !open_flags.contains(OpenFlags::O_DIRECTORY) && open_flags.is_writable() {
                     return Err(VfsError::IsDirectory);
                 }
+                // Directories are checked when they are listed or searched,
+                // `O_PATH` only needs the search.
+                if attr.file_type != FileType::Directory && !open_flags.contains(OpenFlags::O_PATH) {
+                    check_access(&inode, open_flags.access())?;
+                }
 
                 let file = Self {
                     inner: inode,
@@ -133,12 +171,14 @@ impl File {
             Err(VfsError::NotFound) => {
                 // File does not exist
                 if open_flags.contains(OpenFlags::O_CREAT) {
+                    check_access(&dir_inode, Access::WRITE | Access::EXEC)?;
                     if open_flags.contains(OpenFlags::O_DIRECTORY) {
                         dir_inode.mkdir_at(file_name)?;
                     } else {
                         dir_inode.create_file(file_name)?;
                     }
                     let inode = dir_inode.lookup(file_name)?;
+                    give_to_caller(&inode, mode);
                     Ok(Self {
                         inner: inode,
                         openflags: open_flags,
@@ -154,6 +194,11 @@ impl File {
     }
 
     pub fn open(path: &str, open_flags: OpenFlags) -> VfsResult<Self> {
+        Self::open_mode(path, open_flags, DEFAULT_FILE_MODE)
+    }
+
+    /// Open `path`, creating it with `mode` for `O_CREAT`.
+    pub fn open_mode(path: &str, open_flags: OpenFlags, mode: u32) -> VfsResult<Self> {
         let (resolved_mount_path, mount_node) = match get_mount_node(path.into()) {
             Some((p, node)) => (p, node),
             None => return Err(VfsError::NotFound),
@@ -204,10 +249,12 @@ impl File {
         let (file_name, dir_components) = components.split_last().unwrap();
         let mut current_inode = root_inode;
         for component in dir_components {
+            check_access(&current_inode, Access::EXEC)?;
             current_inode = current_inode.lookup(component)?;
         }
 
         let dir_inode = current_inode;
+        check_access(&dir_inode, Access::EXEC)?;
 
         match dir_inode.lookup(file_name) {
             Ok(inode) => {
@@ -226,6 +273,11 @@ impl File {
                 if attr.file_type == FileType::Directory && !open_flags.contains(OpenFlags::O_DIRECTORY) && open_flags.is_writable() {
                     return Err(VfsError::IsDirectory);
                 }
+                // Directories are checked when they are listed or searched,
+                // `O_PATH` only needs the search.
+                if attr.file_type != FileType::Directory && !open_flags.contains(OpenFlags::O_PATH) {
+                    check_access(&inode, open_flags.access())?;
+                }
 
                 let file = Self {
                     inner: inode,
@@ -246,12 +298,14 @@ impl File {
             Err(VfsError::NotFound) => {
                 // File does not exist
                 if open_flags.contains(OpenFlags::O_CREAT) {
+                    check_access(&dir_inode, Access::WRITE | Access::EXEC)?;
                     if open_flags.contains(OpenFlags::O_DIRECTORY) {
                         dir_inode.mkdir_at(file_name)?;
                     } else {
                         dir_inode.create_file(file_name)?;
                     }
                     let inode = dir_inode.lookup(file_name)?;
+                    give_to_caller(&inode, mode);
                     Ok(Self {
                         inner: inode,
                         openflags: open_flags,
@@ -314,17 +368,24 @@ impl File {
         self.inner.flush()
     }
 
-    pub fn mkdir_at(&self, path: &str) -> VfsResult<()> {
+    /// Make the directory `path` in this one with `mode`.
+    pub fn mkdir_at(&self, path: &str, mode: u32) -> VfsResult<()> {
         if !self.openflags.is_writable() {
             return Err(VfsError::PermissionDenied);
         }
-        self.inner.mkdir_at(path)
+        check_access(&self.inner, Access::WRITE | Access::EXEC)?;
+        self.inner.mkdir_at(path)?;
+        if let Ok(inode) = self.inner.lookup(path) {
+            give_to_caller(&inode, mode);
+        }
+        Ok(())
     }
 
     pub fn read_dir(&self) -> VfsResult<Vec<DirEntry>> {
         if !self.openflags.is_readable() {
             return Err(VfsError::PermissionDenied);
         }
+        check_access(&self.inner, Access::READ)?;
         self.inner.read_dir()
     }
 
@@ -345,7 +406,7 @@ impl File {
             FileType::Pipe => 0o010000,      // S_IFIFO
             FileType::Socket => 0o140000,    // S_IFSOCK
             FileType::Unknown => 0,
-        };
+        } | attr.mode;
         stat.st_nlink = attr.nlinks as u32;
         stat.st_uid = attr.uid as u32;
         stat.st_gid = attr.gid as u32;
@@ -361,10 +422,12 @@ impl File {
     }
 
     pub fn remove(&self, name: &str) -> VfsResult<()> {
+        check_delete(&self.inner, name)?;
         self.inner.rm_file(name)
     }
 
     pub fn rmdir(&self, name: &str) -> VfsResult<()> {
+        check_delete(&self.inner, name)?;
         self.inner.rm_dir(name)
     }
 
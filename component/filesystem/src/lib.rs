@@ -7,6 +7,7 @@ pub mod fd_table;
 pub mod file;
 pub mod mount;
 pub mod path;
+pub mod perm;
 pub mod plug;
 pub mod vfs;
 pub mod pipe;
@@ -23,14 +24,15 @@ lazy_static! {
     pub static ref ROOT_FS: Mutex<Option<Arc<Ext4FileSystemWrapper>>> = Mutex::new(None);
 }
 
-pub fn init_fs() {
+/// Mount the ext4 image on block device `root` at `/`, and devfs at `/dev`.
+pub fn init_fs(root: usize) {
     log::info!("Starting filesystem initialization");
-    mount_ext4();
+    mount_ext4(root);
     mount_devfs();
 }
 
-pub fn mount_ext4() {
-    match crate::plug::lwext4::Ext4FileSystemWrapper::new(0) {
+pub fn mount_ext4(blk_id: usize) {
+    match crate::plug::lwext4::Ext4FileSystemWrapper::new(blk_id) {
         Ok(ext4_fs) => {
             *ROOT_FS.lock() = Some(Arc::clone(&ext4_fs));
             let mount_path = Path::new("/".to_string());
@@ -1,3 +1,10 @@
+//! Mount tables.
+//!
+//! Each mount namespace has its own table. Paths resolve through the table
+//! of the caller's namespace, which the VFS gets from the kernel through
+//! `_vfs_current_mnt_ns`. The kernel itself and the processes it starts
+//! use `INIT_MNT_NS`.
+
 use crate::path::{self, Path};
 use crate::vfs::{FileSystem, Inode};
 use alloc::sync::Arc;
@@ -7,8 +14,10 @@ use log::trace;
 use spin::Mutex;
 
 lazy_static! {
-    pub static ref MOUNT_LIST: Mutex<Vec<(Path, MountNode)>> = Mutex::new(Vec::new());
+    /// The initial mount namespace, the root file system is mounted here.
+    pub static ref INIT_MNT_NS: Arc<MountNamespace> = Arc::new(MountNamespace::default());
 }
+
 #[derive(Clone)]
 pub struct MountNode {
     pub root_inner: Arc<dyn Inode>,
@@ -28,11 +37,92 @@ impl MountNode {
     }
 }
 
+/// A mount namespace: the mount points its processes see.
+#[derive(Default)]
+pub struct MountNamespace {
+    mounts: Mutex<Vec<(Path, MountNode)>>,
+}
+
+impl MountNamespace {
+    /// A new namespace starting out with a copy of this one's mounts.
+    /// Mounts in either one don't show in the other.
+    pub fn fork(&self) -> Arc<Self> {
+        Arc::new(MountNamespace {
+            mounts: Mutex::new(self.mounts.lock().clone()),
+        })
+    }
+
+    pub fn mount(&self, path: Path, node: MountNode) {
+        self.mounts.lock().push((path, node));
+    }
+
+    /// Remove the mount at `path`, the last one if several are stacked.
+    pub fn umount(&self, path: &Path) -> bool {
+        let mut mounts = self.mounts.lock();
+        match mounts.iter().rposition(|(p, _)| p == path) {
+            Some(index) => {
+                mounts.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The mount `path` is under: the one with the longest mount point
+    /// that is `path` or a parent of it.
+    pub fn lookup(&self, path: &Path) -> Option<(Path, MountNode)> {
+        let mount_list = self.mounts.lock();
+        let mut best_match_data: Option<(Path, MountNode)> = None;
+        let mut max_prefix_len: usize = 0;
+
+        let input_path_str = path.to_string();
+
+        for (mount_point, mount_node) in mount_list.iter() {
+            let mount_point_str = mount_point.to_string();
+
+            if input_path_str.starts_with(&mount_point_str) {
+                let current_prefix_len = mount_point_str.len();
+
+                let is_exact_match = input_path_str.len() == current_prefix_len;
+                let is_prefix_and_subdir = if !is_exact_match {
+                    input_path_str
+                        .as_bytes()
+                        .get(current_prefix_len)
+                        .map_or(false, |&c| c == b'/')
+                } else {
+                    false
+                };
+                let valid_match_as_prefix = if mount_point_str == "/" {
+                    true
+                } else {
+                    is_exact_match || is_prefix_and_subdir
+                };
+
+                // A later mount on the same point hides the earlier one.
+                if valid_match_as_prefix && current_prefix_len >= max_prefix_len {
+                    max_prefix_len = current_prefix_len;
+                    best_match_data = Some((mount_point.clone(), mount_node.clone()));
+                }
+            }
+        }
+        best_match_data
+    }
+}
+
+unsafe extern "Rust" {
+    fn _vfs_current_mnt_ns() -> Arc<MountNamespace>;
+}
+
+/// The mount namespace of the caller.
+pub fn current_mnt_ns() -> Arc<MountNamespace> {
+    unsafe { _vfs_current_mnt_ns() }
+}
+
 pub fn mount_fs(fs: Arc<dyn FileSystem>, path: Path) {
     trace!("Mounting filesystem at path: {:?}", path);
     if let Some(root) = fs.root_inode() {
         let mount_node = MountNode::new(Some(fs), root);
-        MOUNT_LIST.lock().push((path, mount_node));
+        current_mnt_ns().mount(path, mount_node);
         trace!("Filesystem mounted successfully");
     } else {
         trace!("Failed to get root inode for filesystem");
@@ -41,9 +131,7 @@ pub fn mount_fs(fs: Arc<dyn FileSystem>, path: Path) {
 
 pub fn umount_fs(path: Path) -> bool {
     trace!("Unmounting filesystem at path: {:?}", path);
-    let mut mount_list = MOUNT_LIST.lock();
-    if let Some(index) = mount_list.iter().position(|(p, _)| p == &path) {
-        mount_list.remove(index);
+    if current_mnt_ns().umount(&path) {
         trace!("Filesystem unmounted successfully");
         true
     } else {
@@ -53,45 +141,10 @@ pub fn umount_fs(path: Path) -> bool {
 }
 
 pub fn get_mount_node(path: Path) -> Option<(Path, MountNode)> {
-    let mount_list = MOUNT_LIST.lock();
-    let mut best_match_data: Option<(Path, MountNode)> = None;
-    let mut max_prefix_len: usize = 0;
-
-    let input_path_str = path.to_string();
-
-    for (mount_point, mount_node) in mount_list.iter() {
-        let mount_point_str = mount_point.to_string();
-
-        if input_path_str.starts_with(&mount_point_str) {
-            let current_prefix_len = mount_point_str.len();
-
-            let is_exact_match = input_path_str.len() == current_prefix_len;
-            let is_prefix_and_subdir = if !is_exact_match {
-                input_path_str
-                    .as_bytes()
-                    .get(current_prefix_len)
-                    .map_or(false, |&c| c == b'/')
-            } else {
-                false
-            };
-            let valid_match_as_prefix = if mount_point_str == "/" {
-                true
-            } else {
-                is_exact_match || is_prefix_and_subdir
-            };
-
-            if valid_match_as_prefix && current_prefix_len > max_prefix_len {
-                max_prefix_len = current_prefix_len;
-                best_match_data = Some((mount_point.clone(), mount_node.clone()));
-            }
-        }
-    }
-    best_match_data
+    current_mnt_ns().lookup(&path)
 }
 
-
-pub fn mount_inode(inode:Arc<dyn Inode>,path:Path)
-{
-    let mount_node = MountNode::new(None,inode);
-    MOUNT_LIST.lock().push((path, mount_node));
-}
\ No newline at end of file
+pub fn mount_inode(inode: Arc<dyn Inode>, path: Path) {
+    let mount_node = MountNode::new(None, inode);
+    current_mnt_ns().mount(path, mount_node);
+}
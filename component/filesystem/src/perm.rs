@@ -0,0 +1,179 @@
+//! POSIX permission checks.
+//!
+//! The VFS asks the kernel who the caller is through
+//! `_vfs_current_cred`, which the kernel defines.
+
+use crate::vfs::{FileAttr, FileType, Inode, VfsError, VfsResult};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use bitflags::bitflags;
+
+bitflags! {
+    /// What a caller wants to do with a file, as in the mode bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Access: u32 {
+        const READ = 0o4;
+        const WRITE = 0o2;
+        const EXEC = 0o1;
+    }
+}
+
+/// Set-user-ID, set-group-ID and sticky bits of a mode.
+pub const S_ISUID: u32 = 0o4000;
+pub const S_ISGID: u32 = 0o2000;
+pub const S_ISVTX: u32 = 0o1000;
+
+/// The ids a file access is checked with.
+#[derive(Debug, Clone, Default)]
+pub struct Cred {
+    pub uid: u32,
+    pub gid: u32,
+    /// Supplementary groups.
+    pub groups: Vec<u32>,
+    /// Mode bits taken away from the files the caller creates.
+    pub umask: u32,
+}
+
+impl Cred {
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+
+    pub fn in_group(&self, gid: u32) -> bool {
+        self.gid == gid || self.groups.contains(&gid)
+    }
+
+    /// Whether the mode bits of `attr` grant `access`. Root may read and
+    /// write anything, and execute what has an execute bit or is a
+    /// directory.
+    pub fn may(&self, attr: &FileAttr, access: Access) -> bool {
+        if self.is_root() {
+            return !access.contains(Access::EXEC)
+                || attr.file_type == FileType::Directory
+                || attr.mode & 0o111 != 0;
+        }
+        let granted = if self.uid == attr.uid {
+            attr.mode >> 6
+        } else if self.in_group(attr.gid) {
+            attr.mode >> 3
+        } else {
+            attr.mode
+        };
+        Access::from_bits_truncate(granted & 0o7).contains(access)
+    }
+
+    /// Whether the caller may remove or rename `child` in directory `dir`,
+    /// which is restricted by the sticky bit.
+    pub fn may_delete(&self, dir: &FileAttr, child: &FileAttr) -> bool {
+        dir.mode & S_ISVTX == 0 || self.is_root() || self.uid == dir.uid || self.uid == child.uid
+    }
+}
+
+unsafe extern "Rust" {
+    fn _vfs_current_cred() -> Cred;
+}
+
+/// The credentials of the caller.
+pub fn current_cred() -> Cred {
+    unsafe { _vfs_current_cred() }
+}
+
+/// Check `access` to `inode` for the caller. Inodes without attributes,
+/// like pipes, are open to everyone.
+pub fn check_access(inode: &Arc<dyn Inode>, access: Access) -> VfsResult<()> {
+    check_access_with(&current_cred(), inode, access)
+}
+
+pub fn check_access_with(cred: &Cred, inode: &Arc<dyn Inode>, access: Access) -> VfsResult<()> {
+    let Ok(attr) = inode.getattr() else {
+        return Ok(());
+    };
+    match cred.may(&attr, access) {
+        true => Ok(()),
+        false => Err(VfsError::PermissionDenied),
+    }
+}
+
+/// Check that the caller may remove `name` from directory `dir`.
+pub fn check_delete(dir: &Arc<dyn Inode>, name: &str) -> VfsResult<()> {
+    let cred = current_cred();
+    check_access_with(&cred, dir, Access::WRITE | Access::EXEC)?;
+    let (Ok(dir_attr), Ok(child_attr)) = (dir.getattr(), dir.lookup(name)?.getattr()) else {
+        return Ok(());
+    };
+    match cred.may_delete(&dir_attr, &child_attr) {
+        true => Ok(()),
+        false => Err(VfsError::PermissionDenied),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(file_type: FileType, mode: u32, uid: u32, gid: u32) -> FileAttr {
+        FileAttr {
+            size: 0,
+            file_type,
+            mode,
+            nlinks: 1,
+            uid,
+            gid,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+            blk_size: 512,
+            blocks: 0,
+        }
+    }
+
+    fn user(uid: u32, gid: u32, groups: &[u32]) -> Cred {
+        Cred {
+            uid,
+            gid,
+            groups: groups.to_vec(),
+            umask: 0o022,
+        }
+    }
+
+    #[test]
+    fn test_owner_group_other() {
+        let file = attr(FileType::File, 0o640, 1000, 100);
+        assert!(user(1000, 1000, &[]).may(&file, Access::READ | Access::WRITE));
+        assert!(user(1001, 100, &[]).may(&file, Access::READ));
+        assert!(!user(1001, 100, &[]).may(&file, Access::WRITE));
+        assert!(user(1001, 1001, &[100]).may(&file, Access::READ));
+        assert!(!user(1001, 1001, &[]).may(&file, Access::READ));
+    }
+
+    #[test]
+    fn test_owner_class_wins() {
+        // The owner doesn't fall back to the group bits.
+        let file = attr(FileType::File, 0o070, 1000, 100);
+        assert!(!user(1000, 100, &[]).may(&file, Access::READ));
+    }
+
+    #[test]
+    fn test_root() {
+        let file = attr(FileType::File, 0o000, 1000, 100);
+        assert!(Cred::root().may(&file, Access::READ | Access::WRITE));
+        assert!(!Cred::root().may(&file, Access::EXEC));
+        assert!(Cred::root().may(&attr(FileType::File, 0o001, 1000, 100), Access::EXEC));
+        assert!(Cred::root().may(&attr(FileType::Directory, 0o000, 1000, 100), Access::EXEC));
+    }
+
+    #[test]
+    fn test_sticky_dir() {
+        let dir = attr(FileType::Directory, 0o1777, 0, 0);
+        let file = attr(FileType::File, 0o644, 1000, 100);
+        assert!(user(1000, 100, &[]).may_delete(&dir, &file));
+        assert!(!user(1001, 100, &[]).may_delete(&dir, &file));
+        assert!(Cred::root().may_delete(&dir, &file));
+        let plain = attr(FileType::Directory, 0o777, 0, 0);
+        assert!(user(1001, 100, &[]).may_delete(&plain, &file));
+    }
+}
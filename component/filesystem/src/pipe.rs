@@ -5,14 +5,33 @@ use alloc::{
     sync::{Arc, Weak},
 };
 
+use core::task::Waker;
 use struct_define::poll_event::PollEvent;
 use crate::vfs::VfsResult;
 use spin::Mutex;
 use crate::vfs::Inode;
 use crate::vfs::VfsError;
+use wait_queue::WaitQueue;
 
+/// Buffer shared by both ends of a pipe.
 #[derive(Debug)]
-pub struct PipeSender(Arc<Mutex<VecDeque<u8>>>);
+struct PipeBuffer {
+    queue: Mutex<VecDeque<u8>>,
+    /// Readers waiting for data or for the write end to close.
+    readers: WaitQueue,
+    /// Writers waiting for room in the buffer.
+    writers: WaitQueue,
+}
+
+#[derive(Debug)]
+pub struct PipeSender(Arc<PipeBuffer>);
+
+impl Drop for PipeSender {
+    fn drop(&mut self) {
+        // Readers blocked on an empty pipe must observe EOF.
+        self.0.readers.wake_all();
+    }
+}
 
 impl Inode for PipeSender {
     fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> VfsResult<usize> {
@@ -21,12 +40,14 @@ impl Inode for PipeSender {
 
     fn write_at(&self, _offset: usize, buf: &[u8]) -> VfsResult<usize> {
         log::warn!("write pipe:");
-        let mut queue = self.0.lock();
+        let mut queue = self.0.queue.lock();
         if queue.len() > 0x50000 {
             Err(VfsError::IoError)
         } else {
             let wlen = buf.len();
             queue.extend(buf.iter());
+            drop(queue);
+            self.0.readers.wake_all();
             Ok(wlen)
         }
     }
@@ -85,24 +106,29 @@ impl Inode for PipeSender {
 
     fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
         let mut res = PollEvent::NONE;
-        if events.contains(PollEvent::OUT) && self.0.lock().len() <= 0x50000 {
+        if events.contains(PollEvent::OUT) && self.0.queue.lock().len() <= 0x50000 {
             res |= PollEvent::OUT;
         }
         Ok(res)
     }
+
+    fn register_waker(&self, _events: PollEvent, waker: &Waker) -> VfsResult<()> {
+        self.0.writers.register(waker);
+        Ok(())
+    }
 }
 
 // pipe reader, just can read.
 #[derive(Debug)]
 pub struct PipeReceiver {
-    queue: Arc<Mutex<VecDeque<u8>>>,
+    buffer: Arc<PipeBuffer>,
     sender: Weak<PipeSender>,
 }
 
 
 impl Inode for PipeReceiver {
         fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> VfsResult<usize> {
-        let mut queue = self.queue.lock();
+        let mut queue = self.buffer.queue.lock();
         if queue.is_empty() {
             if Weak::strong_count(&self.sender) > 0 {
                 // 写入端还存在，但管道是空的，返回 Again
@@ -119,34 +145,45 @@ impl Inode for PipeReceiver {
             _buf[i] = queue.pop_front().unwrap();
             i += 1;
         }
+        drop(queue);
+        self.buffer.writers.wake_all();
         Ok(i)
     }
 
     fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
         let mut res = PollEvent::NONE;
         if events.contains(PollEvent::IN) {
-            if !self.queue.lock().is_empty() {
+            if !self.buffer.queue.lock().is_empty() {
                 res |= PollEvent::IN;
             } else if Weak::strong_count(&self.sender) == 0 {
                 res |= PollEvent::ERR;
             }
         }
         if events.contains(PollEvent::ERR)
-            && self.queue.lock().is_empty()
+            && self.buffer.queue.lock().is_empty()
             && Weak::strong_count(&self.sender) == 0
         {
             res |= PollEvent::ERR;
         }
         Ok(res)
     }
+
+    fn register_waker(&self, _events: PollEvent, waker: &Waker) -> VfsResult<()> {
+        self.buffer.readers.register(waker);
+        Ok(())
+    }
 }
 
 pub fn create_pipe() -> (Arc<PipeReceiver>, Arc<PipeSender>) {
-    let queue = Arc::new(Mutex::new(VecDeque::new()));
-    let sender = Arc::new(PipeSender(queue.clone()));
+    let buffer = Arc::new(PipeBuffer {
+        queue: Mutex::new(VecDeque::new()),
+        readers: WaitQueue::new(),
+        writers: WaitQueue::new(),
+    });
+    let sender = Arc::new(PipeSender(buffer.clone()));
     (
         Arc::new(PipeReceiver {
-            queue: queue.clone(),
+            buffer,
             sender: Arc::downgrade(&sender),
         }),
         sender,
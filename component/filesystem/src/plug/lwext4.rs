@@ -1,6 +1,7 @@
 use lwext4_rust;
 use lwext4_rust::bindings::{
-    O_CREAT, O_RDWR, O_TRUNC, O_WRONLY, SEEK_SET, ext4_inode, ext4_raw_inode_fill,
+    O_CREAT, O_RDWR, O_TRUNC, O_WRONLY, SEEK_SET, ext4_inode, ext4_mode_set, ext4_owner_set,
+    ext4_raw_inode_fill,
 };
 
 use alloc::format;
@@ -578,12 +579,13 @@ impl Inode for Ext4FileWrapper {
             Ok(FileAttr {
                 size: size_val as usize,
                 file_type: self.file_type,
-                nlinks: 1,
-                uid: 0,
-                gid: 0,
-                atime: 0,
-                mtime: 0,
-                ctime: 0,
+                mode: inode_info.mode as u32 & 0o7777,
+                nlinks: inode_info.links_count as u32,
+                uid: inode_info.uid as u32,
+                gid: inode_info.gid as u32,
+                atime: inode_info.access_time as u64,
+                mtime: inode_info.modification_time as u64,
+                ctime: inode_info.change_inode_time as u64,
                 blk_size: 512,
                 blocks: 0,
             })
@@ -596,6 +598,24 @@ impl Inode for Ext4FileWrapper {
         }
     }
 
+    fn chown(&self, uid: u32, gid: u32) -> VfsResult<()> {
+        let file_guard = self.inner.lock();
+        let path_cstr = file_guard.get_path();
+        match unsafe { ext4_owner_set(path_cstr.as_ptr(), uid, gid) } {
+            0 => Ok(()),
+            _ => Err(VfsError::IoError),
+        }
+    }
+
+    fn chmod(&self, mode: u32) -> VfsResult<()> {
+        let file_guard = self.inner.lock();
+        let path_cstr = file_guard.get_path();
+        match unsafe { ext4_mode_set(path_cstr.as_ptr(), mode & 0o7777) } {
+            0 => Ok(()),
+            _ => Err(VfsError::IoError),
+        }
+    }
+
     fn rename(&self, new_name: &str) -> VfsResult<()> {
         let mut file = self.inner.lock();
         let path = file.get_path();
@@ -8,6 +8,7 @@ extern crate alloc;
 use alloc::string::String;
 use alloc::sync::Arc;
 use alloc::vec::Vec;
+use core::task::Waker;
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum FileType {
     File,
@@ -39,6 +40,8 @@ pub enum VfsError {
     BadFileDescriptor,
     InvalidOperation,
     Again,
+    /// The fd table is at its limit.
+    TooManyOpenFiles,
 }
 
 
@@ -48,6 +51,8 @@ pub type VfsResult<T> = Result<T, VfsError>;
 pub struct FileAttr {
     pub size: usize,
     pub file_type: FileType,
+    /// Permission bits, with the set-id and sticky bits.
+    pub mode: u32,
     pub nlinks: u32,
     pub uid: u32,
     pub gid: u32,
@@ -107,6 +112,13 @@ pub trait Inode: DowncastSync + Send + Sync + core::fmt::Debug {
     fn getattr(&self) -> VfsResult<FileAttr> {
         Err(VfsError::NotSupported)
     }
+    fn chown(&self, _uid: u32, _gid: u32) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
+    /// Set the permission bits, `0o7777` of `mode`.
+    fn chmod(&self, _mode: u32) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
 
     fn get_type(&self) -> VfsResult<FileType> {
         Err(VfsError::NotSupported)
@@ -115,6 +127,14 @@ pub trait Inode: DowncastSync + Send + Sync + core::fmt::Debug {
     fn poll(&self, _event: PollEvent) -> VfsResult<PollEvent> {
         Err(VfsError::NotSupported)
     }
+
+    /// Ask to be woken once one of `events` may have become ready.
+    ///
+    /// Inodes that never block keep the default, and callers fall back to
+    /// re-polling.
+    fn register_waker(&self, _events: PollEvent, _waker: &Waker) -> VfsResult<()> {
+        Err(VfsError::NotSupported)
+    }
 }
 
 impl_downcast!(Inode);
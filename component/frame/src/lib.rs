@@ -2,6 +2,7 @@
 
 extern crate alloc;
 use log::debug;
+use alloc::collections::BTreeMap;
 use alloc::vec::Vec;
 use bitmap::Bitmap;
 use config::target::plat::{FRAME_SIZE, PAGE_SIZE,VIRT_ADDR_START};
@@ -55,6 +56,9 @@ pub struct FrameAllocator {
     start: PhysAddr,
     end: PhysAddr,
     bitmap: Bitmap,
+    /// Number of pages mapping a frame, only kept for frames mapped more
+    /// than once.
+    refs: BTreeMap<usize, usize>,
 }
 
 impl FrameAllocator {
@@ -70,7 +74,12 @@ impl FrameAllocator {
         let bitmap = Bitmap::new(frame_count);
         let end = start + frame_count * PAGE_SIZE;
         info!("FrameAllocator: start={:?}, end={:?}, frame_count={}", start, end, frame_count);
-        Self { start, end, bitmap }
+        Self {
+            start,
+            end,
+            bitmap,
+            refs: BTreeMap::new(),
+        }
     }
 
     pub fn get_start_end(&self) -> (usize,usize)
@@ -91,6 +100,30 @@ impl FrameAllocator {
         self.bitmap.clear(frame_idx);
     }
 
+    pub fn ref_count(&self, paddr: PhysAddr) -> usize {
+        self.refs.get(&paddr.as_usize()).copied().unwrap_or(1)
+    }
+
+    pub fn ref_inc(&mut self, paddr: PhysAddr) {
+        *self.refs.entry(paddr.as_usize()).or_insert(1) += 1;
+    }
+
+    /// Drop one mapping of `paddr`, the frame is freed with its last one.
+    /// Returns the mappings left.
+    pub fn ref_dec(&mut self, paddr: PhysAddr) -> usize {
+        let count = self.ref_count(paddr) - 1;
+        match count {
+            0 => self.dealloc(FrameTracer::new(paddr)),
+            1 => {
+                self.refs.remove(&paddr.as_usize());
+            }
+            _ => {
+                self.refs.insert(paddr.as_usize(), count);
+            }
+        }
+        count
+    }
+
     pub fn alloc_continues(&mut self, count: usize) -> Vec<FrameTracer> {
         let mut frames = Vec::with_capacity(count);
         let mut start_idx = 0;
@@ -149,6 +182,45 @@ pub fn alloc_continues(count: usize) -> Vec<FrameTracer> {
     FRAME_ALLOCATOR.lock().alloc_continues(count)
 }
 
+/// Number of pages mapping the frame at `paddr`.
+pub fn frame_ref_count(paddr: PhysAddr) -> usize {
+    FRAME_ALLOCATOR.lock().ref_count(paddr)
+}
+
+/// Record one more page mapping the frame at `paddr`.
+pub fn frame_ref_inc(paddr: PhysAddr) {
+    FRAME_ALLOCATOR.lock().ref_inc(paddr)
+}
+
+/// Drop one page mapping the frame at `paddr`, the frame is freed with the
+/// last one. Returns the mappings left.
+pub fn frame_ref_dec(paddr: PhysAddr) -> usize {
+    FRAME_ALLOCATOR.lock().ref_dec(paddr)
+}
+
+/// Get a frame the caller may write to in place of the shared `paddr`.
+///
+/// That is `paddr` itself if no other page maps it, otherwise a private
+/// copy and the caller's mapping of `paddr` is dropped. Checking and copying
+/// happen under one lock, so two pages racing for the same frame can't both
+/// keep it.
+pub fn cow_frame(paddr: PhysAddr) -> Option<PhysAddr> {
+    let mut allocator = FRAME_ALLOCATOR.lock();
+    if allocator.ref_count(paddr) == 1 {
+        return Some(paddr);
+    }
+    let frame = allocator.alloc()?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            paddr.as_usize() as *const u8,
+            frame.paddr.as_usize() as *mut u8,
+            PAGE_SIZE,
+        );
+    }
+    allocator.ref_dec(paddr);
+    Some(frame.paddr)
+}
+
 pub fn dealloc_continues(base: FrameTracer, count: usize) -> bool {
     FRAME_ALLOCATOR.lock().dealloc_continues(base, count)
 }
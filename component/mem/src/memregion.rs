@@ -15,6 +15,9 @@ pub enum MemRegionType {
     HEAP,
     ANONYMOUS,
     MMAP,
+    /// `MAP_SHARED` and SysV shared memory, shared with forked children
+    /// instead of copied.
+    SHARED,
 }
 
 /// Memory region
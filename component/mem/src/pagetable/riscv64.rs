@@ -5,10 +5,10 @@ use arch::change_pagetable;
 use config::target::plat::PAGE_SIZE;
 use config::target::plat::VIRT_ADDR_START;
 use console::println;
-use frame::alloc_continues;
+use frame::{alloc_continues, cow_frame, frame_ref_count, frame_ref_dec, frame_ref_inc};
 use log::error;
 use log::info;
-use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
+use memory_addr::{MemoryAddr, PhysAddr, VirtAddr, VirtAddrRange};
 use page_table_multiarch::{GenericPTE, MappingFlags, riscv::Sv39PageTable};
 use page_table_entry::riscv::Rv64PTE;
 use memory_addr::AddrRange;
@@ -176,6 +176,16 @@ impl PageTable {
         }
     }
 
+    /// Map the 4K page at `vaddr` to the frame at `paddr`.
+    pub fn map_page(&mut self, vaddr: VirtAddr, paddr: PhysAddr, flags: MappingFlags) -> Result<(), ()> {
+        self.page_table
+            .map(vaddr, paddr, page_table_multiarch::PageSize::Size4K, flags)
+            .map_err(|_| ())?
+            .ignore();
+        arch::flush_tlb();
+        Ok(())
+    }
+
     pub fn flush() {
         arch::flush_tlb();
     }
@@ -195,6 +205,106 @@ impl PageTable {
         }
     }
 
+    /// Mapping of the 4K page holding `vaddr`.
+    pub fn query_page(&self, vaddr: VirtAddr) -> Option<(PhysAddr, MappingFlags)> {
+        match self.page_table.query(vaddr.align_down_4k()) {
+            Ok((paddr, flags, page_table_multiarch::PageSize::Size4K)) => Some((paddr, flags)),
+            _ => None,
+        }
+    }
+
+    /// Share the user pages of `range` with `child` for copy-on-write. Both
+    /// sides lose write access, the first write copies the page.
+    pub fn share_cow(&mut self, child: &mut PageTable, range: VirtAddrRange) -> Result<(), ()> {
+        let start = range.start.align_down_4k().as_usize();
+        let end = range.end.align_up_4k().as_usize();
+        for vaddr in (start..end).step_by(PAGE_SIZE) {
+            let vaddr = VirtAddr::from_usize(vaddr);
+            let Some((paddr, flags)) = self.query_page(vaddr) else {
+                continue;
+            };
+            let flags = flags - MappingFlags::WRITE;
+            if let Ok((_, tlb)) = self.page_table.protect(vaddr, flags) {
+                tlb.ignore();
+            }
+            child
+                .page_table
+                .map(vaddr, paddr, page_table_multiarch::PageSize::Size4K, flags)
+                .map_err(|_| ())?
+                .ignore();
+            frame_ref_inc(paddr);
+        }
+        arch::flush_tlb();
+        Ok(())
+    }
+
+    /// Map the user pages of `range` in `child` as well, with the same
+    /// rights. Writes on either side are seen by the other.
+    pub fn share(&self, child: &mut PageTable, range: VirtAddrRange) -> Result<(), ()> {
+        let start = range.start.align_down_4k().as_usize();
+        let end = range.end.align_up_4k().as_usize();
+        for vaddr in (start..end).step_by(PAGE_SIZE) {
+            let vaddr = VirtAddr::from_usize(vaddr);
+            let Some((paddr, flags)) = self.query_page(vaddr) else {
+                continue;
+            };
+            child
+                .page_table
+                .map(vaddr, paddr, page_table_multiarch::PageSize::Size4K, flags)
+                .map_err(|_| ())?
+                .ignore();
+            frame_ref_inc(paddr);
+        }
+        Ok(())
+    }
+
+    /// Give write access back to the page holding `vaddr` after a store
+    /// fault on a copy-on-write page.
+    pub fn copy_on_write(&mut self, vaddr: VirtAddr) -> Result<(), ()> {
+        let vaddr = vaddr.align_down_4k();
+        let (paddr, flags) = self.query_page(vaddr).ok_or(())?;
+        if flags.contains(MappingFlags::WRITE) {
+            // Another thread of the process got here first.
+            return Ok(());
+        }
+        let flags = flags | MappingFlags::WRITE;
+        let new_paddr = cow_frame(paddr).ok_or(())?;
+        match new_paddr == paddr {
+            true => self.page_table.protect(vaddr, flags).map_err(|_| ())?.1.ignore(),
+            false => self.page_table.remap(vaddr, new_paddr, flags).map_err(|_| ())?.1.ignore(),
+        }
+        arch::flush_tlb();
+        Ok(())
+    }
+
+    /// Give the page holding `vaddr` a frame of its own, keeping its flags,
+    /// so the kernel may write to it in place of its user, as a debugger
+    /// does. Returns the frame.
+    pub fn private_page(&mut self, vaddr: VirtAddr) -> Result<PhysAddr, ()> {
+        let vaddr = vaddr.align_down_4k();
+        let (paddr, flags) = self.query_page(vaddr).ok_or(())?;
+        let new_paddr = cow_frame(paddr).ok_or(())?;
+        if new_paddr != paddr {
+            self.page_table.remap(vaddr, new_paddr, flags).map_err(|_| ())?.1.ignore();
+            arch::flush_tlb();
+        }
+        Ok(new_paddr)
+    }
+
+    /// Give up the shares of the user pages in `range`. The frames are kept,
+    /// the other side writes to them in place from now on.
+    pub fn unshare(&self, range: VirtAddrRange) {
+        let start = range.start.align_down_4k().as_usize();
+        let end = range.end.align_up_4k().as_usize();
+        for vaddr in (start..end).step_by(PAGE_SIZE) {
+            if let Some((paddr, _)) = self.query_page(VirtAddr::from_usize(vaddr)) {
+                if frame_ref_count(paddr) > 1 {
+                    frame_ref_dec(paddr);
+                }
+            }
+        }
+    }
+
     pub fn unmap_region(&mut self, region: &mut MemRegion) {
         let start_vaddr = region.vaddr_range.start;
         let size = region.vaddr_range.size();
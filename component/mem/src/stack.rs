@@ -1,7 +1,9 @@
-use alloc::string::ToString;
-use memory_addr::{align_up, PhysAddr, PhysAddrRange, VirtAddr, VirtAddrRange};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use memory_addr::{align_down, align_up, PhysAddr, PhysAddrRange, VirtAddr, VirtAddrRange};
 use core::mem::size_of;
 use page_table_entry::MappingFlags;
+use struct_define::aux::aux_type;
 
 use crate::{
     memregion::{MemRegion, MemRegionType},
@@ -131,3 +133,248 @@ impl StackRegion {
         new_sp
     }
 }
+
+/// The initial stack of a program, laid out per the RISC-V psABI.
+///
+/// From `sp` up: `argc`, the `argv` pointers and a null, the `envp` pointers
+/// and a null, the auxv pairs ending with `AT_NULL`, then the random bytes
+/// and the strings they point to. `sp` is 16 byte aligned.
+///
+/// The image is built in kernel memory and copied to the user stack in one
+/// go by [`StackRegion::push_init_stack`].
+#[derive(Debug, Clone)]
+pub struct InitStack {
+    top: usize,
+    /// Bytes from `sp` to `top`.
+    data: Vec<u8>,
+    /// The auxv on the stack, without the `AT_NULL` entry.
+    auxv: Vec<(usize, usize)>,
+}
+
+/// What goes on the initial stack.
+#[derive(Debug, Clone, Default)]
+pub struct InitStackInfo<'a> {
+    pub args: &'a [String],
+    pub envp: &'a [String],
+    /// Entries other than `AT_RANDOM`, `AT_EXECFN` and `AT_PLATFORM`, which
+    /// the builder adds as it places what they point to.
+    pub auxv: &'a [(usize, usize)],
+    /// Path of the program, for `AT_EXECFN`.
+    pub execfn: &'a str,
+    pub platform: &'a str,
+    /// The bytes `AT_RANDOM` points to.
+    pub random: [u8; 16],
+}
+
+impl InitStack {
+    pub fn build(top: usize, info: &InitStackInfo) -> Self {
+        let mut stack = Self { top, data: Vec::new(), auxv: Vec::new() };
+        let execfn = stack.push_str(info.execfn);
+        let envp: Vec<usize> = info.envp.iter().rev().map(|env| stack.push_str(env)).collect();
+        let args: Vec<usize> = info.args.iter().rev().map(|arg| stack.push_str(arg)).collect();
+        let platform = stack.push_str(info.platform);
+        stack.align(size_of::<usize>());
+        let random = stack.push_bytes(&info.random);
+
+        let mut auxv = info.auxv.to_vec();
+        auxv.push((aux_type::AT_RANDOM, random));
+        auxv.push((aux_type::AT_EXECFN, execfn));
+        auxv.push((aux_type::AT_PLATFORM, platform));
+
+        let mut words = Vec::with_capacity(args.len() + envp.len() + 2 * auxv.len() + 5);
+        words.push(args.len());
+        words.extend(args.iter().rev());
+        words.push(0);
+        words.extend(envp.iter().rev());
+        words.push(0);
+        auxv.iter().for_each(|&(key, value)| words.extend([key, value]));
+        words.extend([aux_type::AT_NULL, 0]);
+        stack.auxv = auxv;
+
+        // `argc` is the last word pushed and has to land 16 byte aligned.
+        stack.align(16);
+        if words.len() % 2 == 1 {
+            stack.push_bytes(&[0; size_of::<usize>()]);
+        }
+        let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+        stack.push_bytes(&bytes);
+        stack
+    }
+
+    pub fn sp(&self) -> usize {
+        self.top - self.data.len()
+    }
+
+    pub fn top(&self) -> usize {
+        self.top
+    }
+
+    pub fn auxv(&self) -> &[(usize, usize)] {
+        &self.auxv
+    }
+
+    /// The bytes from [`Self::sp`] to the top.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> usize {
+        self.data.splice(0..0, bytes.iter().copied());
+        self.sp()
+    }
+
+    fn push_str(&mut self, str: &str) -> usize {
+        self.push_bytes(&[0]);
+        self.push_bytes(str.as_bytes())
+    }
+
+    fn align(&mut self, align: usize) {
+        let pad = self.sp() - align_down(self.sp(), align);
+        self.data.splice(0..0, core::iter::repeat_n(0, pad));
+    }
+}
+
+impl StackRegion {
+    /// Copy `stack` to the top of the region and move `sp` below it.
+    /// Returns the new `sp`, `None` if the stack doesn't fit in the region.
+    pub fn push_init_stack(&mut self, stack: &InitStack) -> Option<usize> {
+        assert_eq!(stack.top(), self.get_top(), "initial stack built for another region");
+        let new_sp = stack.sp();
+        if new_sp < self.vaddr_range.start.as_usize() {
+            return None;
+        }
+        let dst_paddr = self.vaddr_to_paddr(VirtAddr::from_usize(new_sp));
+        unsafe {
+            core::slice::from_raw_parts_mut(dst_paddr.as_usize() as *mut u8, stack.data().len())
+                .copy_from_slice(stack.data());
+        }
+        self.sp = new_sp;
+        Some(new_sp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    const TOP: usize = 0x8000_0000;
+
+    fn word(stack: &InitStack, addr: usize) -> usize {
+        let offset = addr - stack.sp();
+        let mut bytes = [0; size_of::<usize>()];
+        bytes.copy_from_slice(&stack.data()[offset..offset + size_of::<usize>()]);
+        usize::from_ne_bytes(bytes)
+    }
+
+    fn string(stack: &InitStack, addr: usize) -> String {
+        let bytes = &stack.data()[addr - stack.sp()..];
+        let len = bytes.iter().position(|&b| b == 0).unwrap();
+        String::from_utf8(bytes[..len].to_vec()).unwrap()
+    }
+
+    /// The strings of a null terminated pointer array at `addr`, and the
+    /// address after the null.
+    fn strings(stack: &InitStack, mut addr: usize) -> (Vec<String>, usize) {
+        let mut strings = Vec::new();
+        loop {
+            let ptr = word(stack, addr);
+            addr += size_of::<usize>();
+            if ptr == 0 {
+                return (strings, addr);
+            }
+            strings.push(string(stack, ptr));
+        }
+    }
+
+    fn auxv(stack: &InitStack, mut addr: usize) -> Vec<(usize, usize)> {
+        let mut auxv = Vec::new();
+        loop {
+            let entry = (word(stack, addr), word(stack, addr + size_of::<usize>()));
+            addr += 2 * size_of::<usize>();
+            auxv.push(entry);
+            if entry.0 == aux_type::AT_NULL {
+                return auxv;
+            }
+        }
+    }
+
+    fn build(args: &[&str], envp: &[&str]) -> InitStack {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let envp: Vec<String> = envp.iter().map(|s| s.to_string()).collect();
+        let info = InitStackInfo {
+            args: &args,
+            envp: &envp,
+            auxv: &[(aux_type::AT_PAGESZ, 0x1000), (aux_type::AT_BASE, 0x2000_0000)],
+            execfn: "/bin/prog",
+            platform: "riscv64",
+            random: [0x5a; 16],
+        };
+        InitStack::build(TOP, &info)
+    }
+
+    #[test]
+    fn layout() {
+        let stack = build(&["prog", "-v", "file"], &["PATH=/bin", "HOME=/"]);
+        assert_eq!(stack.top(), TOP);
+        assert_eq!(stack.sp() + stack.data().len(), TOP);
+        assert_eq!(word(&stack, stack.sp()), 3);
+        let (args, envp_addr) = strings(&stack, stack.sp() + size_of::<usize>());
+        assert_eq!(args, ["prog", "-v", "file"]);
+        let (envp, auxv_addr) = strings(&stack, envp_addr);
+        assert_eq!(envp, ["PATH=/bin", "HOME=/"]);
+        let auxv = auxv(&stack, auxv_addr);
+        assert_eq!(auxv[..2], [(aux_type::AT_PAGESZ, 0x1000), (aux_type::AT_BASE, 0x2000_0000)]);
+        assert_eq!(auxv.last(), Some(&(aux_type::AT_NULL, 0)));
+        assert_eq!(stack.auxv(), &auxv[..auxv.len() - 1]);
+    }
+
+    #[test]
+    fn pointed_auxv() {
+        let stack = build(&["prog"], &[]);
+        let (_, envp_addr) = strings(&stack, stack.sp() + size_of::<usize>());
+        let (_, auxv_addr) = strings(&stack, envp_addr);
+        let auxv = auxv(&stack, auxv_addr);
+        let value = |key| auxv.iter().find(|(k, _)| *k == key).unwrap().1;
+        assert_eq!(string(&stack, value(aux_type::AT_EXECFN)), "/bin/prog");
+        assert_eq!(string(&stack, value(aux_type::AT_PLATFORM)), "riscv64");
+        let random = value(aux_type::AT_RANDOM);
+        assert_eq!(random % size_of::<usize>(), 0);
+        let offset = random - stack.sp();
+        assert_eq!(stack.data()[offset..offset + 16], [0x5a; 16]);
+    }
+
+    #[test]
+    fn sp_is_aligned() {
+        for argc in 0..4 {
+            for envc in 0..4 {
+                let args = vec!["a"; argc];
+                let envp = vec!["KEY=value1"; envc];
+                let stack = build(&args, &envp);
+                assert_eq!(stack.sp() % 16, 0, "argc {} envc {}", argc, envc);
+                assert_eq!(word(&stack, stack.sp()), argc);
+            }
+        }
+    }
+
+    #[test]
+    fn too_big_for_region() {
+        let envp = vec!["KEY=value"; 64];
+        let stack = build(&["prog"], &envp);
+        let mut region = StackRegion::new(
+            PhysAddrRange::new(PhysAddr::from_usize(0), PhysAddr::from_usize(0x100)),
+            VirtAddrRange::new(VirtAddr::from_usize(TOP - 0x100), VirtAddr::from_usize(TOP)),
+        );
+        assert_eq!(region.push_init_stack(&stack), None);
+        assert_eq!(region.get_sp(), TOP);
+    }
+
+    #[test]
+    fn empty_strings() {
+        let stack = build(&[""], &[""]);
+        let (args, envp_addr) = strings(&stack, stack.sp() + size_of::<usize>());
+        assert_eq!(args, [""]);
+        let (envp, _) = strings(&stack, envp_addr);
+        assert_eq!(envp, [""]);
+    }
+}
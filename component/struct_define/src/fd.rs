@@ -38,10 +38,23 @@ pub enum FutexFlags {
     UnlockPi = 7,
     TrylockPi = 8,
     WaitBitset = 9,
+    WakeBitset = 10,
 }
 
+/// `struct robust_list_head` of `set_robust_list`.
 #[repr(C)]
-#[derive(Clone)]
+#[derive(Debug, Clone, Copy)]
+pub struct RobustListHead {
+    /// First entry, the list is circular and ends at the head.
+    pub list: usize,
+    /// Offset from an entry to its futex word.
+    pub futex_offset: isize,
+    /// Entry being locked or unlocked.
+    pub list_op_pending: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
 pub struct IoVec {
     pub base: usize,
     pub len: usize,
@@ -5,4 +5,8 @@ pub mod timespec;
 pub mod tms;
 pub mod uname;
 pub mod aux;
-pub mod fd;
\ No newline at end of file
+pub mod fd;
+pub mod sched;
+pub mod rusage;
+pub mod signal;
+pub mod resource;
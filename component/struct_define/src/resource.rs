@@ -0,0 +1,49 @@
+/// Resources of `getrlimit` and `prlimit64`.
+pub const RLIMIT_CPU: usize = 0;
+pub const RLIMIT_FSIZE: usize = 1;
+pub const RLIMIT_DATA: usize = 2;
+pub const RLIMIT_STACK: usize = 3;
+pub const RLIMIT_CORE: usize = 4;
+pub const RLIMIT_RSS: usize = 5;
+pub const RLIMIT_NPROC: usize = 6;
+pub const RLIMIT_NOFILE: usize = 7;
+pub const RLIMIT_MEMLOCK: usize = 8;
+pub const RLIMIT_AS: usize = 9;
+pub const RLIMIT_LOCKS: usize = 10;
+pub const RLIMIT_SIGPENDING: usize = 11;
+pub const RLIMIT_MSGQUEUE: usize = 12;
+pub const RLIMIT_NICE: usize = 13;
+pub const RLIMIT_RTPRIO: usize = 14;
+pub const RLIMIT_RTTIME: usize = 15;
+pub const RLIM_NLIMITS: usize = 16;
+
+pub const RLIM_INFINITY: u64 = u64::MAX;
+
+/// `struct rlimit`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RLimit {
+    /// Soft limit.
+    pub rlim_cur: u64,
+    /// Hard limit, the ceiling of the soft one.
+    pub rlim_max: u64,
+}
+
+impl RLimit {
+    pub const INFINITY: Self = Self::new(RLIM_INFINITY, RLIM_INFINITY);
+
+    pub const fn new(rlim_cur: u64, rlim_max: u64) -> Self {
+        Self { rlim_cur, rlim_max }
+    }
+
+    /// Whether `value` is beyond the soft limit.
+    pub fn exceeded_by(&self, value: u64) -> bool {
+        self.rlim_cur != RLIM_INFINITY && value > self.rlim_cur
+    }
+}
+
+impl Default for RLimit {
+    fn default() -> Self {
+        Self::INFINITY
+    }
+}
@@ -0,0 +1,23 @@
+use crate::timespec::TimeVal;
+
+/// `struct rusage` of `getrusage` and `wait4`.
+#[repr(C)]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct RUsage {
+    pub utime: TimeVal,
+    pub stime: TimeVal,
+    pub maxrss: isize,
+    pub ixrss: isize,
+    pub idrss: isize,
+    pub isrss: isize,
+    pub minflt: isize,
+    pub majflt: isize,
+    pub nswap: isize,
+    pub inblock: isize,
+    pub oublock: isize,
+    pub msgsnd: isize,
+    pub msgrcv: isize,
+    pub nsignals: isize,
+    pub nvcsw: isize,
+    pub nivcsw: isize,
+}
@@ -0,0 +1,6 @@
+/// `struct sched_param` of `sched_setparam` and friends.
+#[repr(C)]
+#[derive(Default, Clone, Copy, Debug)]
+pub struct SchedParam {
+    pub sched_priority: i32,
+}
@@ -0,0 +1,260 @@
+//! Signal numbers and the structures signals share with userspace.
+
+use bitflags::bitflags;
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGABRT: usize = 6;
+pub const SIGBUS: usize = 7;
+pub const SIGFPE: usize = 8;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGSTKFLT: usize = 16;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGXCPU: usize = 24;
+pub const SIGXFSZ: usize = 25;
+pub const SIGVTALRM: usize = 26;
+pub const SIGPROF: usize = 27;
+pub const SIGWINCH: usize = 28;
+pub const SIGIO: usize = 29;
+pub const SIGPWR: usize = 30;
+pub const SIGSYS: usize = 31;
+pub const SIGRTMIN: usize = 32;
+pub const SIGRTMAX: usize = 64;
+
+/// Entries of a signal handler table, signal 0 is unused.
+pub const SIGNAL_COUNT: usize = SIGRTMAX + 1;
+
+/// `sa_handler` values that are not functions.
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+
+/// `how` of `rt_sigprocmask`.
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// `si_code` values.
+pub const SI_USER: i32 = 0;
+pub const SI_KERNEL: i32 = 0x80;
+pub const SI_TKILL: i32 = -6;
+pub const SI_TIMER: i32 = -2;
+pub const CLD_EXITED: i32 = 1;
+pub const CLD_KILLED: i32 = 2;
+pub const CLD_DUMPED: i32 = 3;
+pub const CLD_TRAPPED: i32 = 4;
+pub const CLD_STOPPED: i32 = 5;
+pub const CLD_CONTINUED: i32 = 6;
+pub const SEGV_MAPERR: i32 = 1;
+pub const SEGV_ACCERR: i32 = 2;
+pub const BUS_ADRALN: i32 = 1;
+pub const BUS_ADRERR: i32 = 2;
+pub const ILL_ILLOPC: i32 = 1;
+pub const FPE_INTDIV: i32 = 1;
+pub const TRAP_BRKPT: i32 = 1;
+pub const TRAP_TRACE: i32 = 2;
+
+bitflags! {
+    /// `sa_flags` of `struct sigaction`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct SigActionFlags: usize {
+        const NOCLDSTOP = 0x1;
+        const NOCLDWAIT = 0x2;
+        const SIGINFO = 0x4;
+        const ONSTACK = 0x0800_0000;
+        const RESTART = 0x1000_0000;
+        const NODEFER = 0x4000_0000;
+        const RESETHAND = 0x8000_0000;
+    }
+}
+
+/// Kernel `sigset_t`, bit `n - 1` is signal `n`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SigSet(pub u64);
+
+impl SigSet {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn from_signal(signo: usize) -> Self {
+        Self(1 << (signo - 1))
+    }
+
+    pub fn contains(&self, signo: usize) -> bool {
+        self.0 & Self::from_signal(signo).0 != 0
+    }
+
+    pub fn insert(&mut self, signo: usize) {
+        self.0 |= Self::from_signal(signo).0;
+    }
+
+    pub fn remove(&mut self, signo: usize) {
+        self.0 &= !Self::from_signal(signo).0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// The lowest signal in the set.
+    pub fn first(&self) -> Option<usize> {
+        match self.0 {
+            0 => None,
+            bits => Some(bits.trailing_zeros() as usize + 1),
+        }
+    }
+
+    pub fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+/// `struct sigaction` of `rt_sigaction`, riscv64 has no `sa_restorer`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SigAction {
+    pub handler: usize,
+    pub flags: SigActionFlags,
+    pub mask: SigSet,
+}
+
+/// `siginfo_t`, the union is kept as raw words.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigInfo {
+    pub signo: i32,
+    pub errno: i32,
+    pub code: i32,
+    _pad: i32,
+    pub fields: [usize; 14],
+}
+
+impl SigInfo {
+    pub fn new(signo: usize, code: i32) -> Self {
+        Self {
+            signo: signo as i32,
+            errno: 0,
+            code,
+            _pad: 0,
+            fields: [0; 14],
+        }
+    }
+
+    /// Sent by `kill` and friends, carries the sender.
+    pub fn user(signo: usize, code: i32, pid: usize, uid: u32) -> Self {
+        let mut info = Self::new(signo, code);
+        info.fields[0] = pid as u32 as usize | (uid as usize) << 32;
+        info
+    }
+
+    /// `SIGCHLD` describing a child state change.
+    pub fn child(code: i32, pid: usize, uid: u32, status: i32) -> Self {
+        let mut info = Self::user(SIGCHLD, code, pid, uid);
+        info.fields[1] = status as u32 as usize;
+        info
+    }
+
+    /// Sent by POSIX timer `timerid`, which expired `overrun` more times
+    /// than were delivered.
+    pub fn timer(signo: usize, timerid: usize, overrun: usize, value: usize) -> Self {
+        let mut info = Self::new(signo, SI_TIMER);
+        info.fields[0] = timerid as u32 as usize | (overrun as u32 as usize) << 32;
+        info.fields[1] = value;
+        info
+    }
+
+    /// Sent by a fault at `addr`.
+    pub fn fault(signo: usize, code: i32, addr: usize) -> Self {
+        let mut info = Self::new(signo, code);
+        info.fields[0] = addr;
+        info
+    }
+}
+
+/// `sigev_notify` of `struct sigevent`.
+pub const SIGEV_SIGNAL: i32 = 0;
+pub const SIGEV_NONE: i32 = 1;
+pub const SIGEV_THREAD: i32 = 2;
+pub const SIGEV_THREAD_ID: i32 = 4;
+
+/// `struct sigevent` of `timer_create`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SigEvent {
+    pub value: usize,
+    pub signo: i32,
+    pub notify: i32,
+    /// The thread of `SIGEV_THREAD_ID`, the rest pads the union.
+    pub tid: i32,
+    _pad: [i32; 11],
+}
+
+/// `stack_t`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SignalStack {
+    pub sp: usize,
+    pub flags: i32,
+    pub size: usize,
+}
+
+/// `mcontext_t` of riscv64: `pc` then `x1` to `x31`, then the FP state.
+#[repr(C, align(16))]
+#[derive(Debug, Clone, Copy)]
+pub struct MContext {
+    pub gregs: [usize; 32],
+    pub fpregs: [u64; 66],
+}
+
+/// `ucontext_t` of riscv64.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct UContext {
+    pub flags: usize,
+    pub link: usize,
+    pub stack: SignalStack,
+    pub sigmask: SigSet,
+    /// Room for a 1024 bit `sigset_t`.
+    _unused: [u8; 120],
+    pub mcontext: MContext,
+}
+
+impl UContext {
+    pub fn new(sigmask: SigSet, gregs: [usize; 32]) -> Self {
+        Self {
+            flags: 0,
+            link: 0,
+            stack: SignalStack::default(),
+            sigmask,
+            _unused: [0; 120],
+            mcontext: MContext {
+                gregs,
+                fpregs: [0; 66],
+            },
+        }
+    }
+}
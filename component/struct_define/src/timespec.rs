@@ -5,3 +5,66 @@ pub struct TimeSpec {
     pub sec: usize,
     pub nsec: usize,
 }
+
+impl TimeSpec {
+    /// Convert to a `Duration`, `None` if `nsec` is out of range.
+    pub fn to_duration(&self) -> Option<core::time::Duration> {
+        match self.nsec < 1_000_000_000 {
+            true => Some(core::time::Duration::new(self.sec as u64, self.nsec as u32)),
+            false => None,
+        }
+    }
+}
+
+impl From<core::time::Duration> for TimeSpec {
+    fn from(duration: core::time::Duration) -> Self {
+        Self {
+            sec: duration.as_secs() as usize,
+            nsec: duration.subsec_nanos() as usize,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeVal {
+    /// seconds, range in 0~999999999
+    pub sec: usize,
+    /// microseconds, range in 0~999999
+    pub usec: usize,
+}
+
+impl From<core::time::Duration> for TimeVal {
+    fn from(duration: core::time::Duration) -> Self {
+        TimeVal {
+            sec: duration.as_secs() as usize,
+            usec: (duration.subsec_micros()) as usize,
+        }
+    }
+}
+
+impl TimeVal {
+    /// Convert to a `Duration`, `None` if `usec` is out of range.
+    pub fn to_duration(&self) -> Option<core::time::Duration> {
+        match self.usec < 1_000_000 {
+            true => Some(core::time::Duration::new(self.sec as u64, self.usec as u32 * 1000)),
+            false => None,
+        }
+    }
+}
+
+/// `struct itimerval` of `getitimer` and `setitimer`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ITimerVal {
+    pub interval: TimeVal,
+    pub value: TimeVal,
+}
+
+/// `struct itimerspec` of `timer_settime` and `timer_gettime`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ITimerSpec {
+    pub interval: TimeSpec,
+    pub value: TimeSpec,
+}
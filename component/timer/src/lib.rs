@@ -2,17 +2,25 @@
 
 #[macro_use]
 extern crate log;
+extern crate alloc;
+
+mod queue;
+pub use queue::{
+    SleepUntil, TimedOut, Timeout, add_timer, cancel_timer, expire_timers, next_deadline, sleep,
+    sleep_until, timeout, timeout_at,
+};
 
 use cfg_if::cfg_if;
 
 cfg_if! {
     if #[cfg(target_arch = "riscv64")] {
         mod riscv64;
-        pub use riscv64::{init, set_next_timeout, get_time_ms, get_time, get_clock_freq};
+        pub use riscv64::{init, set_next_timeout, set_idle_timeout, get_time_ms, get_time, get_clock_freq};
     } else if #[cfg(target_arch = "aarch64")] {
         // Placeholder for aarch64
         pub fn init() { warn!("Timer for aarch64 not implemented"); }
         pub fn set_next_timeout() { warn!("Timer for aarch64 not implemented"); }
+        pub fn set_idle_timeout() { warn!("Timer for aarch64 not implemented"); }
         pub fn get_time_ms() -> u64 { warn!("Timer for aarch64 not implemented"); 0 }
         pub fn get_time() -> core::time::Duration { warn!("Timer for aarch64 not implemented"); core::time::Duration::ZERO }
         pub fn get_clock_freq() -> u64 { warn!("Timer for aarch64 not implemented"); 0 }
@@ -20,6 +28,7 @@ cfg_if! {
         // Placeholder for x86_64
         pub fn init() { warn!("Timer for x86_64 not implemented"); }
         pub fn set_next_timeout() { warn!("Timer for x86_64 not implemented"); }
+        pub fn set_idle_timeout() { warn!("Timer for x86_64 not implemented"); }
         pub fn get_time_ms() -> u64 { warn!("Timer for x86_64 not implemented"); 0 }
         pub fn get_time() -> core::time::Duration { warn!("Timer for x86_64 not implemented"); core::time::Duration::ZERO }
         pub fn get_clock_freq() -> u64 { warn!("Timer for x86_64 not implemented"); 0 }
@@ -27,6 +36,7 @@ cfg_if! {
         // Placeholder for loongarch64
         pub fn init() { warn!("Timer for loongarch64 not implemented"); }
         pub fn set_next_timeout() { warn!("Timer for loongarch64 not implemented"); }
+        pub fn set_idle_timeout() { warn!("Timer for loongarch64 not implemented"); }
         pub fn get_time_ms() -> u64 { warn!("Timer for loongarch64 not implemented"); 0 }
         pub fn get_time() -> core::time::Duration { warn!("Timer for loongarch64 not implemented"); core::time::Duration::ZERO }
         pub fn get_clock_freq() -> u64 { warn!("Timer for loongarch64 not implemented"); 0 }
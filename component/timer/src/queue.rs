@@ -0,0 +1,185 @@
+//! Deadline ordered timer queue.
+//!
+//! Futures register a waker with a deadline, the nearest deadline is what the
+//! hardware timer gets programmed to. Deadlines are time since boot, the same
+//! clock as [`get_time`].
+
+use crate::get_time;
+use alloc::collections::BinaryHeap;
+use core::cmp::Ordering;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use spin::Mutex;
+
+struct TimerEntry {
+    deadline: Duration,
+    /// Keeps timers with the same deadline in registration order.
+    seq: u64,
+    waker: Waker,
+}
+
+impl PartialEq for TimerEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for TimerEntry {}
+
+impl PartialOrd for TimerEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEntry {
+    // Reversed, `BinaryHeap` is a max-heap and we want the nearest deadline.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+struct TimerQueue {
+    heap: BinaryHeap<TimerEntry>,
+    seq: u64,
+}
+
+static TIMER_QUEUE: Mutex<TimerQueue> = Mutex::new(TimerQueue {
+    heap: BinaryHeap::new(),
+    seq: 0,
+});
+
+/// Wake `waker` once `deadline` has passed, returns the id of the timer for
+/// [`cancel_timer`].
+pub fn add_timer(deadline: Duration, waker: Waker) -> u64 {
+    let mut queue = TIMER_QUEUE.lock();
+    let seq = queue.seq;
+    queue.seq += 1;
+    queue.heap.push(TimerEntry {
+        deadline,
+        seq,
+        waker,
+    });
+    seq
+}
+
+/// Remove the timer `id` if it has not fired yet.
+pub fn cancel_timer(id: u64) {
+    TIMER_QUEUE.lock().heap.retain(|entry| entry.seq != id);
+}
+
+/// The nearest deadline in the queue.
+pub fn next_deadline() -> Option<Duration> {
+    TIMER_QUEUE.lock().heap.peek().map(|entry| entry.deadline)
+}
+
+/// Wake every timer whose deadline has passed, returns how many fired.
+pub fn expire_timers() -> usize {
+    let now = get_time();
+    let mut expired = alloc::vec::Vec::new();
+    {
+        let mut queue = TIMER_QUEUE.lock();
+        while queue.heap.peek().is_some_and(|entry| entry.deadline <= now) {
+            expired.push(queue.heap.pop().unwrap().waker);
+        }
+    }
+    // Wake outside of the lock, wakers may register new timers.
+    let count = expired.len();
+    expired.into_iter().for_each(Waker::wake);
+    count
+}
+
+/// Future returned by [`sleep_until`] and [`sleep`].
+pub struct SleepUntil {
+    deadline: Duration,
+    /// The timer waking us, once polled.
+    timer: Option<u64>,
+}
+
+impl SleepUntil {
+    pub fn deadline(&self) -> Duration {
+        self.deadline
+    }
+}
+
+impl Future for SleepUntil {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if get_time() >= self.deadline {
+            return Poll::Ready(());
+        }
+        if self.timer.is_none() {
+            self.timer = Some(add_timer(self.deadline, cx.waker().clone()));
+        }
+        Poll::Pending
+    }
+}
+
+// A sleep dropped early, like the one of a `Timeout` whose future won, must
+// not stay in the queue until its deadline.
+impl Drop for SleepUntil {
+    fn drop(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            cancel_timer(timer);
+        }
+    }
+}
+
+/// Sleep until `deadline`, time since boot.
+pub fn sleep_until(deadline: Duration) -> SleepUntil {
+    SleepUntil {
+        deadline,
+        timer: None,
+    }
+}
+
+/// Sleep for `duration`.
+pub fn sleep(duration: Duration) -> SleepUntil {
+    sleep_until(get_time().saturating_add(duration))
+}
+
+/// Error of [`Timeout`], the deadline passed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimedOut;
+
+/// Future returned by [`timeout`] and [`timeout_at`].
+pub struct Timeout<F> {
+    future: F,
+    sleep: SleepUntil,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, TimedOut>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        match Pin::new(&mut this.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(TimedOut)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Run `future` until it completes or `duration` passes.
+pub fn timeout<F: Future>(future: F, duration: Duration) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(duration),
+    }
+}
+
+/// Run `future` until it completes or `deadline` passes.
+pub fn timeout_at<F: Future>(future: F, deadline: Duration) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep_until(deadline),
+    }
+}
@@ -1,3 +1,4 @@
+use crate::queue::next_deadline;
 use core::time::Duration;
 use riscv::register::time;
 
@@ -18,9 +19,27 @@ pub fn init() {
     );
 }
 
+/// Program the next tick, or the nearest timer if it comes first.
 pub fn set_next_timeout() {
     let current_ticks = time::read() as u64;
-    let next_trigger_ticks = current_ticks + TIMER_INTERVAL_TICKS;
+    let next_tick = current_ticks + TIMER_INTERVAL_TICKS;
+    let next_trigger_ticks = match next_deadline() {
+        Some(deadline) => duration_to_ticks(deadline).min(next_tick),
+        None => next_tick,
+    };
+    set_timer_ticks(next_trigger_ticks);
+}
+
+/// Program only the nearest timer, no periodic tick while the hart idles.
+pub fn set_idle_timeout() {
+    set_timer_ticks(next_deadline().map_or(u64::MAX, duration_to_ticks));
+}
+
+fn duration_to_ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() * CLOCK_FREQ as u128 / 1_000_000_000) as u64
+}
+
+fn set_timer_ticks(next_trigger_ticks: u64) {
     let ret = sbi_rt::set_timer(next_trigger_ticks);
     if ret.error == 0 {
     } else {
@@ -4,7 +4,7 @@
 
 pub use super::trapframe::TrapFrame;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TrapType {
     Breakpoint,
     SysCall,
@@ -15,6 +15,26 @@ pub enum TrapType {
     LoadPageFault(usize),
     InstructionPageFault(usize),
     IllegalInstruction(usize),
+    /// Access to a physical address that can't be accessed, with the address.
+    AccessFault(usize),
+    /// Misaligned access the hardware doesn't emulate, with the address.
+    MisalignedAccess(usize),
+}
+
+impl TrapType {
+    /// Whether the trap is an exception caused by the interrupted code.
+    pub fn is_fault(&self) -> bool {
+        matches!(
+            self,
+            TrapType::Breakpoint
+                | TrapType::StorePageFault(_)
+                | TrapType::LoadPageFault(_)
+                | TrapType::InstructionPageFault(_)
+                | TrapType::IllegalInstruction(_)
+                | TrapType::AccessFault(_)
+                | TrapType::MisalignedAccess(_)
+        )
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -23,6 +43,8 @@ pub enum EscapeReason {
     IRQ,
     Timer,
     SysCall,
+    /// The user code faulted, the user handler turns it into a signal.
+    Fault(TrapType),
 }
 
 // TODO: Add more trap types as needed
@@ -32,6 +54,7 @@ impl From<TrapType> for EscapeReason {
             TrapType::SysCall => EscapeReason::SysCall,
             TrapType::Timer => EscapeReason::Timer,
             TrapType::SupervisorExternal => EscapeReason::IRQ,
+            fault if fault.is_fault() => EscapeReason::Fault(fault),
             _ => EscapeReason::NoReason,
         }
     }
@@ -22,8 +22,8 @@ pub fn init() {
         stvec::write(stvec);
     }
 
-    // // Initialize the timer component
-    // timer::init();
+    // Initialize the timer component
+    timer::init();
 }
 
 // 内核中断回调
@@ -38,13 +38,14 @@ fn kernel_callback(context: &mut TrapFrame) -> TrapType {
             context.sepc += 2;
             TrapType::Breakpoint
         }
-        Trap::Exception(Exception::LoadFault) => {
-            panic!("kernel error: {:#x}", stval);
-            TrapType::Unknown
-        }
+        Trap::Exception(Exception::LoadFault) => TrapType::AccessFault(stval),
+        Trap::Exception(Exception::InstructionFault) => TrapType::AccessFault(stval),
+        Trap::Exception(Exception::LoadMisaligned) => TrapType::MisalignedAccess(stval),
+        Trap::Exception(Exception::StoreMisaligned) => TrapType::MisalignedAccess(stval),
         Trap::Exception(Exception::UserEnvCall) => TrapType::SysCall,
         // 时钟中断
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
+            timer::expire_timers();
             timer::set_next_timeout();
             TrapType::Timer
         }
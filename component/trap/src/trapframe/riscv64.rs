@@ -115,12 +115,12 @@ impl Index<TrapFrameArgs> for TrapFrame {
             TrapFrameArgs::RA => &self.x[1],
             TrapFrameArgs::SP => &self.x[2],
             TrapFrameArgs::RET => &self.x[10],
-            TrapFrameArgs::ARG0 => &self.x[11],
-            TrapFrameArgs::ARG1 => &self.x[12],
-            TrapFrameArgs::ARG2 => &self.x[13],
-            TrapFrameArgs::ARG3 => &self.x[14],
-            TrapFrameArgs::ARG4 => &self.x[15],
-            TrapFrameArgs::ARG5 => &self.x[16],
+            TrapFrameArgs::ARG0 => &self.x[10],
+            TrapFrameArgs::ARG1 => &self.x[11],
+            TrapFrameArgs::ARG2 => &self.x[12],
+            TrapFrameArgs::ARG3 => &self.x[13],
+            TrapFrameArgs::ARG4 => &self.x[14],
+            TrapFrameArgs::ARG5 => &self.x[15],
             TrapFrameArgs::TLS => &self.x[4],
             TrapFrameArgs::SYSCALL => &self.x[17],
         }
@@ -0,0 +1,75 @@
+//! Kernel wait queues.
+//!
+//! A [`WaitQueue`] holds the wakers of futures that are blocked on some
+//! condition. Whoever changes the condition calls [`WaitQueue::wake_one`] or
+//! [`WaitQueue::wake_all`], and the executor moves the parked tasks back onto
+//! its run queue.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::collections::VecDeque;
+use core::task::Waker;
+use spin::Mutex;
+
+pub struct WaitQueue {
+    waiters: Mutex<VecDeque<Waker>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Register `waker` to be woken by the next `wake_*` call.
+    ///
+    /// Registering a waker that would wake the same task twice is a no-op.
+    pub fn register(&self, waker: &Waker) {
+        let mut waiters = self.waiters.lock();
+        if !waiters.iter().any(|w| w.will_wake(waker)) {
+            waiters.push_back(waker.clone());
+        }
+    }
+
+    /// Wake the oldest waiter. Returns `false` if nobody was waiting.
+    pub fn wake_one(&self) -> bool {
+        let waker = self.waiters.lock().pop_front();
+        match waker {
+            Some(waker) => {
+                waker.wake();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wake every waiter and return how many there were.
+    pub fn wake_all(&self) -> usize {
+        let waiters = core::mem::take(&mut *self.waiters.lock());
+        let count = waiters.len();
+        for waker in waiters {
+            waker.wake();
+        }
+        count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.waiters.lock().is_empty()
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Debug for WaitQueue {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WaitQueue")
+            .field("waiters", &self.waiters.lock().len())
+            .finish()
+    }
+}
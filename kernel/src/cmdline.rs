@@ -0,0 +1,120 @@
+//! Kernel command line, the `bootargs` of the device tree `/chosen` node.
+//!
+//! Words are separated by spaces. The ones the kernel knows are:
+//!
+//! - `init=PATH`: the program run as the first user process.
+//! - `root=DEV`: the block device mounted at `/`, `/dev/vdX`, `vdX` or its
+//!   index.
+//! - `loglevel=N`: the console log level, `0` to `8` or a level name.
+//! - `testsuite=PATH`: run the commands listed in the file `PATH`, or every
+//!   program of the directory `PATH`, report each result and shut down.
+//!
+//! The words after `--` are the arguments of init.
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::str::FromStr;
+use log::{LevelFilter, warn};
+
+/// The init started when the command line names none.
+const DEFAULT_INIT: &str = "busybox";
+
+/// Its arguments, we want a shell.
+const DEFAULT_INIT_ARGS: &[&str] = &["sh"];
+
+#[derive(Debug, Clone)]
+pub struct Cmdline {
+    /// Path of init.
+    pub init: String,
+    /// Arguments of init, not counting its name.
+    pub init_args: Vec<String>,
+    /// Index of the block device mounted at `/`.
+    pub root: usize,
+    pub loglevel: Option<LevelFilter>,
+    pub testsuite: Option<String>,
+}
+
+impl Default for Cmdline {
+    fn default() -> Self {
+        Self {
+            init: DEFAULT_INIT.to_string(),
+            init_args: DEFAULT_INIT_ARGS.iter().map(|arg| arg.to_string()).collect(),
+            root: 0,
+            loglevel: None,
+            testsuite: None,
+        }
+    }
+}
+
+impl Cmdline {
+    pub fn parse(bootargs: &str) -> Self {
+        let mut cmdline = Self::default();
+        let mut words = bootargs.split(' ').filter(|word| !word.is_empty());
+        let mut init_args = None;
+        for word in words.by_ref() {
+            if word == "--" {
+                init_args = Some(words.map(|arg| arg.to_string()).collect());
+                break;
+            }
+            let Some((key, value)) = word.split_once('=') else {
+                warn!("cmdline: ignoring {}", word);
+                continue;
+            };
+            match key {
+                "init" => {
+                    cmdline.init = value.to_string();
+                    // Only the default init gets the default arguments.
+                    cmdline.init_args.clear();
+                }
+                "root" => match parse_root(value) {
+                    Some(root) => cmdline.root = root,
+                    None => warn!("cmdline: unknown root device {}", value),
+                },
+                "loglevel" => match parse_loglevel(value) {
+                    Some(level) => cmdline.loglevel = Some(level),
+                    None => warn!("cmdline: bad loglevel {}", value),
+                },
+                "testsuite" => cmdline.testsuite = Some(value.to_string()),
+                _ => warn!("cmdline: ignoring {}", word),
+            }
+        }
+        if let Some(args) = init_args {
+            cmdline.init_args = args;
+        }
+        cmdline
+    }
+
+    /// The argv of init.
+    pub fn init_argv(&self) -> Vec<&str> {
+        let mut argv = vec![self.init.as_str()];
+        argv.extend(self.init_args.iter().map(String::as_str));
+        argv
+    }
+}
+
+/// `/dev/vdb`, `vdb` and `1` all name the second block device.
+fn parse_root(value: &str) -> Option<usize> {
+    let name = value.strip_prefix("/dev/").unwrap_or(value);
+    if let Ok(index) = name.parse() {
+        return Some(index);
+    }
+    match name.strip_prefix("vd")?.as_bytes() {
+        [letter @ b'a'..=b'z'] => Some((letter - b'a') as usize),
+        _ => None,
+    }
+}
+
+/// Numbers are Linux console levels, a message shows when its priority is
+/// below the level. Level names are taken as well.
+fn parse_loglevel(value: &str) -> Option<LevelFilter> {
+    match value.parse::<usize>() {
+        Ok(0) => Some(LevelFilter::Off),
+        Ok(1..=4) => Some(LevelFilter::Error),
+        Ok(5) => Some(LevelFilter::Warn),
+        Ok(6..=7) => Some(LevelFilter::Info),
+        Ok(8) => Some(LevelFilter::Debug),
+        Ok(_) => Some(LevelFilter::Trace),
+        Err(_) => LevelFilter::from_str(value).ok(),
+    }
+}
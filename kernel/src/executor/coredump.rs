@@ -0,0 +1,305 @@
+//! ELF core files of processes killed by a signal that dumps core.
+//!
+//! The core goes to `/core.<pid>` in the layout of a RISC-V Linux core, so
+//! `gdb` opens it with the program: a `PT_NOTE` segment holding an
+//! `NT_PRSTATUS` note per thread and the `NT_AUXV` note, then a `PT_LOAD`
+//! segment per memory region. The faulting thread comes first, `gdb` takes
+//! the first `NT_PRSTATUS` as the current thread.
+
+use super::thread::UserTask;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use config::target::plat::PAGE_SIZE;
+use core::mem::size_of;
+use filesystem::file::{File, OpenFlags};
+use log::{info, warn};
+use memory_addr::{MemoryAddr, VirtAddr, VirtAddrRange};
+use page_table_multiarch::MappingFlags;
+use struct_define::resource::RLIMIT_CORE;
+use struct_define::timespec::TimeVal;
+
+/// Bit of a `wait4` status telling the process dumped core.
+pub const WCOREFLAG: i32 = 0x80;
+
+const ET_CORE: u16 = 4;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+const PT_NOTE: u32 = 4;
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+const NT_PRSTATUS: u32 = 1;
+const NT_AUXV: u32 = 6;
+
+#[repr(C)]
+struct ElfHeader {
+    ident: [u8; 16],
+    elf_type: u16,
+    machine: u16,
+    version: u32,
+    entry: u64,
+    phoff: u64,
+    shoff: u64,
+    flags: u32,
+    ehsize: u16,
+    phentsize: u16,
+    phnum: u16,
+    shentsize: u16,
+    shnum: u16,
+    shstrndx: u16,
+}
+
+#[repr(C)]
+struct ProgramHeader {
+    p_type: u32,
+    flags: u32,
+    offset: u64,
+    vaddr: u64,
+    paddr: u64,
+    filesz: u64,
+    memsz: u64,
+    align: u64,
+}
+
+#[repr(C)]
+struct NoteHeader {
+    namesz: u32,
+    descsz: u32,
+    n_type: u32,
+}
+
+/// `struct elf_prstatus` of riscv64, padding spelled out.
+#[repr(C)]
+struct PrStatus {
+    /// `si_signo`, `si_code` and `si_errno` of the signal.
+    info: [i32; 3],
+    cursig: u16,
+    _pad: u16,
+    sigpend: u64,
+    sighold: u64,
+    pid: i32,
+    ppid: i32,
+    pgrp: i32,
+    sid: i32,
+    utime: TimeVal,
+    stime: TimeVal,
+    cutime: TimeVal,
+    cstime: TimeVal,
+    /// `pc` in place of `x0`, then `x1` to `x31`.
+    reg: [usize; 32],
+    fpvalid: i32,
+    _pad2: i32,
+}
+
+/// The bytes of a `repr(C)` value without padding.
+fn bytes_of<T>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Append a note named `CORE`, name and desc padded to 4 bytes.
+fn push_note(buf: &mut Vec<u8>, n_type: u32, desc: &[u8]) {
+    let header = NoteHeader {
+        namesz: 5,
+        descsz: desc.len() as u32,
+        n_type,
+    };
+    buf.extend_from_slice(bytes_of(&header));
+    buf.extend_from_slice(b"CORE\0\0\0\0");
+    buf.extend_from_slice(desc);
+    buf.resize(buf.len().next_multiple_of(4), 0);
+}
+
+impl UserTask {
+    /// Ranges of the address space and their segment flags: the memory
+    /// set, the heap and the main stack.
+    fn core_segments(&self) -> Vec<(VirtAddrRange, u32)> {
+        let pcb = self.pcb.lock();
+        let tcb = self.tcb.read();
+        let mut segments: Vec<(VirtAddrRange, u32)> = pcb
+            .mem_set
+            .regions
+            .iter()
+            .map(|region| {
+                let flags = region.pte_flags;
+                let mut p_flags = 0;
+                if flags.contains(MappingFlags::READ) {
+                    p_flags |= PF_R;
+                }
+                if flags.contains(MappingFlags::WRITE) {
+                    p_flags |= PF_W;
+                }
+                if flags.contains(MappingFlags::EXECUTE) {
+                    p_flags |= PF_X;
+                }
+                (region.vaddr_range, p_flags)
+            })
+            .collect();
+        segments.push((pcb.heap.virt_range, PF_R | PF_W));
+        segments.push((tcb.stack_region.vaddr_range, PF_R | PF_W));
+        segments.retain(|(range, _)| !range.is_empty());
+        for (range, _) in segments.iter_mut() {
+            *range = VirtAddrRange::new(range.start.align_down_4k(), range.end.align_up_4k());
+        }
+        segments.sort_by_key(|(range, _)| range.start);
+        segments
+    }
+
+    /// `NT_PRSTATUS` of this thread, killed by `signo`.
+    fn prstatus(&self, signo: usize) -> PrStatus {
+        let (pgrp, sid) = {
+            let pcb = self.pcb.lock();
+            (pcb.pgid, pcb.sid)
+        };
+        let ppid = self.parent.read().upgrade().map_or(0, |parent| parent.process_id.0);
+        let tcb = self.tcb.read();
+        let mut reg = tcb.cx.x;
+        reg[0] = tcb.cx.sepc;
+        PrStatus {
+            info: [signo as i32, 0, 0],
+            cursig: signo as u16,
+            _pad: 0,
+            sigpend: tcb.sig_pending.set.0,
+            sighold: tcb.sig_mask.0,
+            pid: self.task_id.0 as i32,
+            ppid: ppid as i32,
+            pgrp: pgrp as i32,
+            sid: sid as i32,
+            utime: tcb.times.utime.into(),
+            stime: tcb.times.stime.into(),
+            cutime: TimeVal::default(),
+            cstime: TimeVal::default(),
+            reg,
+            fpvalid: 0,
+            _pad2: 0,
+        }
+    }
+
+    /// Copy of the user page at `vaddr`, zeros if it isn't mapped.
+    fn core_page(&self, vaddr: usize) -> Vec<u8> {
+        let mut page = vec![0u8; PAGE_SIZE];
+        let page_table = self.page_table.lock();
+        if let Some((paddr, flags)) = page_table.query_page(VirtAddr::from_usize(vaddr)) {
+            if flags.contains(MappingFlags::USER) {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(paddr.as_usize() as *const u8, page.as_mut_ptr(), PAGE_SIZE);
+                }
+            }
+        }
+        page
+    }
+
+    /// Write the core file of the process killed by `signo`, the calling
+    /// thread being the one that took it and the others being off the harts
+    /// already. Returns whether a core was written, `RLIMIT_CORE` of 0 and
+    /// set-user-ID programs get none.
+    pub fn dump_core(self: &Arc<Self>, signo: usize) -> bool {
+        if self.cred.read().is_secure() {
+            return false;
+        }
+        let limit = self.rlimit(RLIMIT_CORE);
+        if limit.rlim_cur == 0 {
+            return false;
+        }
+
+        let mut threads = self.threads();
+        threads.sort_by_key(|thread| thread.task_id != self.task_id);
+        let mut notes = Vec::new();
+        for thread in threads.iter() {
+            push_note(&mut notes, NT_PRSTATUS, bytes_of(&thread.prstatus(signo)));
+        }
+        let auxv: Vec<u8> = self
+            .pcb
+            .lock()
+            .auxv
+            .iter()
+            .chain([(0, 0)].iter())
+            .flat_map(|(key, value)| [*key as u64, *value as u64])
+            .flat_map(u64::to_le_bytes)
+            .collect();
+        push_note(&mut notes, NT_AUXV, &auxv);
+
+        let segments = self.core_segments();
+        let phnum = segments.len() + 1;
+        let notes_offset = size_of::<ElfHeader>() + phnum * size_of::<ProgramHeader>();
+        let mut offset = (notes_offset + notes.len()).next_multiple_of(PAGE_SIZE);
+        let size = offset + segments.iter().map(|(range, _)| range.size()).sum::<usize>();
+        // A cut-off core is of no use to a debugger.
+        if limit.exceeded_by(size as u64) {
+            warn!("[coredump] core of {} bytes is over RLIMIT_CORE", size);
+            return false;
+        }
+
+        let mut header = Vec::with_capacity(notes_offset + notes.len());
+        let mut ident = [0u8; 16];
+        // ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_NONE.
+        ident[..7].copy_from_slice(&[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+        let ehdr = ElfHeader {
+            ident,
+            elf_type: ET_CORE,
+            machine: EM_RISCV,
+            version: 1,
+            entry: 0,
+            phoff: size_of::<ElfHeader>() as u64,
+            shoff: 0,
+            flags: 0,
+            ehsize: size_of::<ElfHeader>() as u16,
+            phentsize: size_of::<ProgramHeader>() as u16,
+            phnum: phnum as u16,
+            shentsize: 0,
+            shnum: 0,
+            shstrndx: 0,
+        };
+        header.extend_from_slice(bytes_of(&ehdr));
+        let note_phdr = ProgramHeader {
+            p_type: PT_NOTE,
+            flags: 0,
+            offset: notes_offset as u64,
+            vaddr: 0,
+            paddr: 0,
+            filesz: notes.len() as u64,
+            memsz: 0,
+            align: 4,
+        };
+        header.extend_from_slice(bytes_of(&note_phdr));
+        for (range, flags) in segments.iter() {
+            let phdr = ProgramHeader {
+                p_type: PT_LOAD,
+                flags: *flags,
+                offset: offset as u64,
+                vaddr: range.start.as_usize() as u64,
+                paddr: 0,
+                filesz: range.size() as u64,
+                memsz: range.size() as u64,
+                align: PAGE_SIZE as u64,
+            };
+            header.extend_from_slice(bytes_of(&phdr));
+            offset += range.size();
+        }
+        header.extend_from_slice(&notes);
+
+        let path = format!("/core.{}", self.process_id.0);
+        let flags = OpenFlags::O_CREAT | OpenFlags::O_WRONLY | OpenFlags::O_TRUNC;
+        let Ok(file) = File::open(&path, flags) else {
+            warn!("[coredump] can't create {}", path);
+            return false;
+        };
+        let mut offset = (notes_offset + notes.len()).next_multiple_of(PAGE_SIZE);
+        let write = |offset: usize, buf: &[u8]| file.write_at(offset, buf).is_ok();
+        if !write(0, &header) {
+            return false;
+        }
+        for (range, _) in segments.iter() {
+            for vaddr in (range.start.as_usize()..range.end.as_usize()).step_by(PAGE_SIZE) {
+                if !write(offset, &self.core_page(vaddr)) {
+                    return false;
+                }
+                offset += PAGE_SIZE;
+            }
+        }
+        info!("[coredump] signal {} dumped core to {}", signo, path);
+        self.pcb.lock().core_dumped = true;
+        true
+    }
+}
@@ -0,0 +1,233 @@
+//! User and group ids of a process.
+//!
+//! The ids are shared by the threads of a process and kept out of the PCB,
+//! the VFS reads them while callers hold the PCB lock.
+
+use super::error::TaskError;
+use super::executor::get_cur_usr_task;
+use alloc::vec::Vec;
+use filesystem::perm::Cred;
+
+/// Most supplementary groups `setgroups` takes.
+pub const NGROUPS_MAX: usize = 65536;
+
+/// An id argument of `setreuid` and friends that leaves the id as it is.
+const KEEP: u32 = u32::MAX;
+
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub uid: u32,
+    pub euid: u32,
+    pub suid: u32,
+    pub gid: u32,
+    pub egid: u32,
+    pub sgid: u32,
+    /// Supplementary groups.
+    pub groups: Vec<u32>,
+    /// File mode creation mask, kept with the ids as the VFS reads it too.
+    pub umask: u32,
+}
+
+impl Default for Credentials {
+    /// Root, with the usual umask.
+    fn default() -> Self {
+        Self {
+            uid: 0,
+            euid: 0,
+            suid: 0,
+            gid: 0,
+            egid: 0,
+            sgid: 0,
+            groups: Vec::new(),
+            umask: 0o022,
+        }
+    }
+}
+
+impl Credentials {
+    /// Whether the credentials are root's.
+    pub fn privileged(&self) -> bool {
+        self.euid == 0
+    }
+
+    /// Whether an unprivileged caller may switch to `uid`.
+    fn is_uid(&self, uid: u32) -> bool {
+        uid == self.uid || uid == self.euid || uid == self.suid
+    }
+
+    fn is_gid(&self, gid: u32) -> bool {
+        gid == self.gid || gid == self.egid || gid == self.sgid
+    }
+
+    /// The ids files are checked with.
+    pub fn fs_cred(&self) -> Cred {
+        Cred {
+            uid: self.euid,
+            gid: self.egid,
+            groups: self.groups.clone(),
+            umask: self.umask,
+        }
+    }
+
+    /// The real ids, which `faccessat` checks with.
+    pub fn real_cred(&self) -> Cred {
+        Cred {
+            uid: self.uid,
+            gid: self.gid,
+            groups: self.groups.clone(),
+            umask: self.umask,
+        }
+    }
+
+    /// Whether the ids differ from the ones the program was started by,
+    /// `AT_SECURE` of a set-user-ID program.
+    pub fn is_secure(&self) -> bool {
+        self.uid != self.euid || self.gid != self.egid
+    }
+
+    /// Whether these credentials may send a signal to a process running
+    /// with `target`.
+    pub fn may_signal(&self, target: &Credentials) -> bool {
+        self.privileged()
+            || self.uid == target.uid
+            || self.uid == target.suid
+            || self.euid == target.uid
+            || self.euid == target.suid
+    }
+
+    /// Whether a process running with `target` is fully ours, which lets
+    /// `prlimit64` change its limits.
+    pub fn owns(&self, target: &Credentials) -> bool {
+        self.privileged()
+            || ([target.uid, target.euid, target.suid].iter().all(|&id| id == self.uid)
+                && [target.gid, target.egid, target.sgid].iter().all(|&id| id == self.gid))
+    }
+
+    pub fn setuid(&mut self, uid: u32) -> Result<(), TaskError> {
+        if self.privileged() {
+            (self.uid, self.euid, self.suid) = (uid, uid, uid);
+        } else if uid == self.uid || uid == self.suid {
+            self.euid = uid;
+        } else {
+            return Err(TaskError::EPERM);
+        }
+        Ok(())
+    }
+
+    pub fn setgid(&mut self, gid: u32) -> Result<(), TaskError> {
+        if self.privileged() {
+            (self.gid, self.egid, self.sgid) = (gid, gid, gid);
+        } else if gid == self.gid || gid == self.sgid {
+            self.egid = gid;
+        } else {
+            return Err(TaskError::EPERM);
+        }
+        Ok(())
+    }
+
+    pub fn setreuid(&mut self, ruid: u32, euid: u32) -> Result<(), TaskError> {
+        if !self.privileged() {
+            if ruid != KEEP && ruid != self.uid && ruid != self.euid {
+                return Err(TaskError::EPERM);
+            }
+            if euid != KEEP && !self.is_uid(euid) {
+                return Err(TaskError::EPERM);
+            }
+        }
+        let old_ruid = self.uid;
+        if ruid != KEEP {
+            self.uid = ruid;
+        }
+        if euid != KEEP {
+            self.euid = euid;
+        }
+        // The saved id follows the effective one when the real id changes
+        // or the effective one leaves it.
+        if ruid != KEEP || (euid != KEEP && euid != old_ruid) {
+            self.suid = self.euid;
+        }
+        Ok(())
+    }
+
+    pub fn setregid(&mut self, rgid: u32, egid: u32) -> Result<(), TaskError> {
+        if !self.privileged() {
+            if rgid != KEEP && rgid != self.gid && rgid != self.egid {
+                return Err(TaskError::EPERM);
+            }
+            if egid != KEEP && !self.is_gid(egid) {
+                return Err(TaskError::EPERM);
+            }
+        }
+        let old_rgid = self.gid;
+        if rgid != KEEP {
+            self.gid = rgid;
+        }
+        if egid != KEEP {
+            self.egid = egid;
+        }
+        if rgid != KEEP || (egid != KEEP && egid != old_rgid) {
+            self.sgid = self.egid;
+        }
+        Ok(())
+    }
+
+    pub fn setresuid(&mut self, ruid: u32, euid: u32, suid: u32) -> Result<(), TaskError> {
+        let ids = [ruid, euid, suid];
+        if !self.privileged() && ids.iter().any(|&id| id != KEEP && !self.is_uid(id)) {
+            return Err(TaskError::EPERM);
+        }
+        for (id, new) in [&mut self.uid, &mut self.euid, &mut self.suid].into_iter().zip(ids) {
+            if new != KEEP {
+                *id = new;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn setresgid(&mut self, rgid: u32, egid: u32, sgid: u32) -> Result<(), TaskError> {
+        let ids = [rgid, egid, sgid];
+        if !self.privileged() && ids.iter().any(|&id| id != KEEP && !self.is_gid(id)) {
+            return Err(TaskError::EPERM);
+        }
+        for (id, new) in [&mut self.gid, &mut self.egid, &mut self.sgid].into_iter().zip(ids) {
+            if new != KEEP {
+                *id = new;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn setgroups(&mut self, groups: Vec<u32>) -> Result<(), TaskError> {
+        if !self.privileged() {
+            return Err(TaskError::EPERM);
+        }
+        if groups.len() > NGROUPS_MAX {
+            return Err(TaskError::EINVAL);
+        }
+        self.groups = groups;
+        Ok(())
+    }
+
+    /// Take the owner of a set-user-ID or set-group-ID program being
+    /// executed. The saved ids are the effective ones afterwards.
+    pub fn exec_setid(&mut self, setuid: Option<u32>, setgid: Option<u32>) {
+        if let Some(uid) = setuid {
+            self.euid = uid;
+        }
+        if let Some(gid) = setgid {
+            self.egid = gid;
+        }
+        self.suid = self.euid;
+        self.sgid = self.egid;
+    }
+}
+
+/// Who the VFS checks file accesses for. Kernel work outside a user task is
+/// done as root.
+#[unsafe(no_mangle)]
+pub fn _vfs_current_cred() -> Cred {
+    match get_cur_usr_task() {
+        Some(task) => task.cred.read().fs_cred(),
+        None => Cred::root(),
+    }
+}
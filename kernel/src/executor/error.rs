@@ -1,3 +1,5 @@
+use elf_ext::LoadError;
+use elf_ext::binfmt::BinfmtError;
 use filesystem::vfs::VfsError;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,6 +14,17 @@ pub enum TaskError {
     ECHILD,
     ENOMEM,
     ENFILE,
+    ESRCH,
+    ENOEXEC,
+    EAGAIN,
+    ETIMEDOUT,
+    ENOSYS,
+    EINTR,
+    EIO,
+    ENOTTY,
+    EACCES,
+    ELOOP,
+    E2BIG,
     Vfs(VfsError),
 }
 
@@ -21,6 +34,27 @@ impl From<VfsError> for TaskError {
     }
 }
 
+impl From<BinfmtError> for TaskError {
+    fn from(e: BinfmtError) -> Self {
+        match e {
+            BinfmtError::Vfs(e) => Self::Vfs(e),
+            BinfmtError::NoExec => Self::ENOEXEC,
+            BinfmtError::Loop => Self::ELOOP,
+        }
+    }
+}
+
+impl From<LoadError> for TaskError {
+    fn from(e: LoadError) -> Self {
+        match e {
+            LoadError::Vfs(VfsError::PermissionDenied) => Self::EACCES,
+            LoadError::Vfs(e) => Self::Vfs(e),
+            LoadError::BadElf(_) => Self::ENOEXEC,
+            LoadError::NoMemory => Self::ENOMEM,
+        }
+    }
+}
+
 impl TaskError {
     pub fn as_str(&self) -> &str {
         match self {
@@ -35,6 +69,17 @@ impl TaskError {
             TaskError::Vfs(_) => "VfsError",
             TaskError::EFAULT => "Bad address",
             TaskError::ENFILE => "Too many open files",
+            TaskError::ESRCH => "No such process",
+            TaskError::ENOEXEC => "Exec format error",
+            TaskError::EAGAIN => "Try again",
+            TaskError::ETIMEDOUT => "Timed out",
+            TaskError::ENOSYS => "Function not implemented",
+            TaskError::EINTR => "Interrupted system call",
+            TaskError::EIO => "I/O error",
+            TaskError::ENOTTY => "Not a typewriter",
+            TaskError::EACCES => "Permission denied",
+            TaskError::ELOOP => "Too many symbolic links encountered",
+            TaskError::E2BIG => "Argument list too long",
         }
     }
 
@@ -49,8 +94,21 @@ impl TaskError {
             TaskError::InvalidCloneFlags => 22, // EINVAL
             TaskError::ECHILD => 10, // ECHILD
             TaskError::ENOMEM => 12, // ENOMEM
+            TaskError::Vfs(VfsError::PermissionDenied) => 13, // EACCES
+            TaskError::Vfs(VfsError::TooManyOpenFiles) => 24, // EMFILE
             TaskError::Vfs(_) => 2, // ENOENT
             TaskError::EFAULT => 14, // EFAULT
+            TaskError::ESRCH => 3, // ESRCH
+            TaskError::ENOEXEC => 8, // ENOEXEC
+            TaskError::EAGAIN => 11, // EAGAIN
+            TaskError::ETIMEDOUT => 110, // ETIMEDOUT
+            TaskError::ENOSYS => 38, // ENOSYS
+            TaskError::EINTR => 4, // EINTR
+            TaskError::EIO => 5, // EIO
+            TaskError::ENOTTY => 25, // ENOTTY
+            TaskError::EACCES => 13, // EACCES
+            TaskError::ELOOP => 40, // ELOOP
+            TaskError::E2BIG => 7, // E2BIG
         }
     }
 }
@@ -1,21 +1,50 @@
 use super::task::AsyncTask;
 use super::task::AsyncTaskItem;
 use alloc::{sync::Arc, vec::Vec};
-use arch::{get_cpu_num, get_cur_cpu_id};
+use arch::{enable_timer_interrupt, get_cpu_num, get_cur_cpu_id, send_ipi, wait_for_ipi_or_interrupt};
 use log::info;
-use core::sync::atomic::AtomicBool;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use spin::Mutex;
 use crate::executor::id_alloc::TaskId;
 use crate::executor::thread::UserTask;
-use alloc::collections::{BTreeMap, VecDeque};
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::task::Wake;
 use core::task::Context;
 use core::task::Poll;
 use lazy_static::*;
 use alloc::boxed::Box;
 use crate::executor::task::KernelTask;
-/// Global task queue
-pub(crate) static TASK_QUEUE: Mutex<VecDeque<AsyncTaskItem>> = Mutex::new(VecDeque::new());
+use crate::executor::sched::{RunQueue, SchedEntity};
+use timer::get_time;
+use config::target::plat::MAX_CPUS;
+/// Per-CPU run queues, indexed by hart id.
+pub(crate) static TASK_QUEUES: [Mutex<RunQueue>; MAX_CPUS] =
+    [const { Mutex::new(RunQueue::new()) }; MAX_CPUS];
+
+/// The task each hart is polling, `NO_TASK` when it polls none.
+static POLLING: [AtomicUsize; MAX_CPUS] = [const { AtomicUsize::new(NO_TASK) }; MAX_CPUS];
+const NO_TASK: usize = usize::MAX;
+
+/// Harts sleeping in the idle loop, bit `n` is hart `n`.
+static IDLE_HARTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Tasks whose future returned `Pending` and that wait for their waker.
+struct ParkedTasks {
+    tasks: BTreeMap<TaskId, AsyncTaskItem>,
+    /// Tasks woken while they were being polled; they go straight back to
+    /// the run queue instead of being parked.
+    woken: BTreeSet<TaskId>,
+}
+
+static PARKED: Mutex<ParkedTasks> = Mutex::new(ParkedTasks {
+    tasks: BTreeMap::new(),
+    woken: BTreeSet::new(),
+});
+
+/// The waker of each task that may be polled again. A task gets the same
+/// one on every poll, so a wait queue it registers with again sees it
+/// there already.
+static WAKERS: Mutex<BTreeMap<TaskId, core::task::Waker>> = Mutex::new(BTreeMap::new());
 
 lazy_static! {
     pub static ref GLOBLE_EXECUTOR: Executor = Executor::new();
@@ -30,7 +59,6 @@ pub struct Executor {
 
 /// Waker
 pub struct Waker {
-    #[allow(dead_code)]
     task_id: TaskId,
 }
 
@@ -39,15 +67,18 @@ impl Wake for Waker {
         self.wake_by_ref();
     }
 
-    fn wake_by_ref(self: &Arc<Self>) {}
+    fn wake_by_ref(self: &Arc<Self>) {
+        wake_task(self.task_id);
+    }
 }
 
 impl Executor {
     /// Create a new executor
     pub fn new() -> Self {
-        let cpu_num = get_cpu_num();
-        let mut cores: Vec<spin::mutex::Mutex<Option<Arc<dyn AsyncTask + 'static>>>> = Vec::with_capacity(cpu_num);
-        for _ in 0..cpu_num {
+        // Sized for every hart that may boot, the executor can be touched
+        // before the number of harts is known.
+        let mut cores: Vec<spin::mutex::Mutex<Option<Arc<dyn AsyncTask + 'static>>>> = Vec::with_capacity(MAX_CPUS);
+        for _ in 0..MAX_CPUS {
             cores.push(Mutex::new(None));
         }
         Self {
@@ -60,21 +91,17 @@ impl Executor {
     pub fn spawn(&self, task: AsyncTaskItem) {
         let task_id = task.task.get_task_id();
         TASK_MAP.lock().insert(task_id, task.task.clone());
-        TASK_QUEUE.lock().push_back(task);
+        push_ready_task(task);
     }
 
-    /// Run a ready task
-    pub fn run_ready_task(&self) {
+    /// Run a ready task, returns `false` if the run queue was empty.
+    pub fn run_ready_task(&self) -> bool {
         assert!(
             self.is_inited.load(core::sync::atomic::Ordering::Acquire),
             "Executor not initialized"
         );
 
-        let task = {
-            let mut task_queue = TASK_QUEUE.lock();
-            task_queue.pop_front()
-        };
-        if let Some(task) = task {
+        if let Some(task) = pop_ready_task() {
             let task_id = task.task.get_task_id();
             info!("Running task with ID: {:?}", task_id);
             
@@ -101,24 +128,57 @@ impl Executor {
             // info!("task : {:?}", task);
             let cur_cpu_id = get_cur_cpu_id();
             *self.cores[cur_cpu_id].lock() = Some(task.clone());
-            let waker = Arc::new(Waker {
-                task_id: task.get_task_id(),
-            })
-            .into();
+            let waker = task_waker(task_id);
             let mut context = Context::from_waker(&waker);
 
-            match future.as_mut().poll(&mut context) {
+            let start = get_time();
+            POLLING[cur_cpu_id].store(task_id.0, Ordering::Release);
+            let res = future.as_mut().poll(&mut context);
+            POLLING[cur_cpu_id].store(NO_TASK, Ordering::Release);
+            task.after_run();
+            task.sched().lock().charge(get_time().saturating_sub(start));
+            match res {
                 Poll::Ready(()) => {
                     info!("Task {:?} completed", task_id);
+                    PARKED.lock().woken.remove(&task_id);
+                    WAKERS.lock().remove(&task_id);
+                }
+                Poll::Pending => {
+                    let mut parked = PARKED.lock();
+                    if parked.woken.remove(&task_id) {
+                        push_ready_task(AsyncTaskItem { future, task });
+                    } else {
+                        parked.tasks.insert(task_id, AsyncTaskItem { future, task });
+                    }
                 }
-                Poll::Pending => TASK_QUEUE.lock().push_back(AsyncTaskItem { future, task }),
             }
+            true
+        } else {
+            false
         }
     }
 
     pub fn run(&self) {
         loop {
-            self.run_ready_task();
+            if self.run_ready_task() {
+                continue;
+            }
+            if timer::expire_timers() > 0 {
+                continue;
+            }
+            // Announce the idle state before the last check, so a task pushed
+            // after it sends us an IPI that ends the wfi below.
+            let cpu_bit = 1 << get_cur_cpu_id();
+            IDLE_HARTS.fetch_or(cpu_bit, Ordering::AcqRel);
+            if TASK_QUEUES.iter().all(|queue| queue.lock().is_empty()) {
+                // Nothing is runnable, sleep until the nearest timer, an IPI
+                // or a device interrupt.
+                timer::set_idle_timeout();
+                enable_timer_interrupt();
+                wait_for_ipi_or_interrupt();
+                timer::set_next_timeout();
+            }
+            IDLE_HARTS.fetch_and(!cpu_bit, Ordering::AcqRel);
         }
     }
 
@@ -126,7 +186,83 @@ impl Executor {
 
 /// Add a ready task to the task queue
 pub fn add_ready_task(task: AsyncTaskItem) {
-    TASK_QUEUE.lock().push_back(task);
+    push_ready_task(task);
+}
+
+/// Push a task onto the run queue of the current CPU, or of the first CPU
+/// its affinity allows.
+fn push_ready_task(task: AsyncTaskItem) {
+    let cur_cpu_id = get_cur_cpu_id();
+    let cpu_id = {
+        let entity = task.task.sched().lock();
+        match entity.allows_cpu(cur_cpu_id) {
+            true => cur_cpu_id,
+            false => (0..get_cpu_num())
+                .find(|&id| entity.allows_cpu(id))
+                .unwrap_or(cur_cpu_id),
+        }
+    };
+    TASK_QUEUES[cpu_id].lock().push(task);
+    // Idle harts don't tick, kick them so they can take or steal the task.
+    let idle = IDLE_HARTS.load(Ordering::Acquire) & !(1 << cur_cpu_id);
+    if idle != 0 {
+        send_ipi(idle);
+    }
+}
+
+/// Pop a task from the local run queue, or steal one from the busiest other
+/// CPU if the local queue is empty.
+fn pop_ready_task() -> Option<AsyncTaskItem> {
+    let cpu_id = get_cur_cpu_id();
+    if let Some(task) = TASK_QUEUES[cpu_id].lock().pop() {
+        return Some(task);
+    }
+    let mut victims: Vec<usize> = (0..get_cpu_num()).filter(|&id| id != cpu_id).collect();
+    victims.sort_by_key(|&id| core::cmp::Reverse(TASK_QUEUES[id].lock().len()));
+    victims
+        .into_iter()
+        .find_map(|id| TASK_QUEUES[id].lock().steal(cpu_id))
+}
+
+/// Whether a task waiting on the current CPU should preempt `current`.
+pub fn need_resched(current: &SchedEntity) -> bool {
+    TASK_QUEUES[get_cur_cpu_id()].lock().should_preempt(current)
+}
+
+/// Move a parked task back onto the run queue.
+///
+/// If the task is not parked yet (it is running or already queued), it is
+/// remembered so that its next `Pending` does not park it.
+pub fn wake_task(task_id: TaskId) {
+    let mut parked = PARKED.lock();
+    match parked.tasks.remove(&task_id) {
+        Some(task) => push_ready_task(task),
+        None => {
+            parked.woken.insert(task_id);
+        }
+    }
+}
+
+/// The waker of a task, made on its first poll.
+fn task_waker(task_id: TaskId) -> core::task::Waker {
+    WAKERS
+        .lock()
+        .entry(task_id)
+        .or_insert_with(|| Arc::new(Waker { task_id }).into())
+        .clone()
+}
+
+/// Whether a hart is polling the task right now, in user mode or in the
+/// kernel.
+pub fn is_task_running(task_id: TaskId) -> bool {
+    task_cpu(task_id).is_some()
+}
+
+/// The hart polling the task, if one is.
+pub fn task_cpu(task_id: TaskId) -> Option<usize> {
+    POLLING
+        .iter()
+        .position(|polling| polling.load(Ordering::Acquire) == task_id.0)
 }
 
 /// Get a task by its task ID
@@ -147,9 +283,28 @@ pub fn get_cur_usr_task() -> Option<Arc<UserTask>> {
 /// Release a task
 pub fn release_task(task_id: TaskId) {
     TASK_MAP.lock().remove(&task_id);
+    cancel_task(task_id);
+}
+
+/// Drop the future of a task so it never runs again, the task itself stays
+/// in the task map.
+pub fn cancel_task(task_id: TaskId) {
     let executor = &GLOBLE_EXECUTOR;
-    executor.cores[get_cur_cpu_id()].lock().take();
-    TASK_QUEUE.lock().retain(|item| item.task.get_task_id() != task_id);    
+    // The task may still be the current one of whichever CPU ran it last.
+    for core in executor.cores.iter() {
+        let mut core = core.lock();
+        if core.as_ref().is_some_and(|task| task.get_task_id() == task_id) {
+            core.take();
+        }
+    }
+    for queue in TASK_QUEUES.iter() {
+        queue.lock().retain(|item| item.task.get_task_id() != task_id);
+    }
+    let mut parked = PARKED.lock();
+    parked.tasks.remove(&task_id);
+    parked.woken.remove(&task_id);
+    drop(parked);
+    WAKERS.lock().remove(&task_id);
 }
 
 /// Spawn a blank task
@@ -157,7 +312,7 @@ pub fn release_task(task_id: TaskId) {
 #[inline]
 pub fn spawn_blank(future: impl Future<Output = ()> + Send + 'static) {
     let task: Arc<dyn AsyncTask> = Arc::new(KernelTask::new());
-    TASK_QUEUE.lock().push_back(AsyncTaskItem {
+    push_ready_task(AsyncTaskItem {
         future: Box::pin(future),
         task,
     })
@@ -165,8 +320,10 @@ pub fn spawn_blank(future: impl Future<Output = ()> + Send + 'static) {
 
 
 pub fn info_task_queue() {
-    for task in TASK_QUEUE.lock().iter() {
-        let inner = task.task.clone();
-        info!("task : {:?}", inner);
+    for (cpu_id, queue) in TASK_QUEUES.iter().enumerate() {
+        for task in queue.lock().iter() {
+            let inner = task.task.clone();
+            info!("cpu {} task : {:?}", cpu_id, inner);
+        }
     }
 }
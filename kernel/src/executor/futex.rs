@@ -0,0 +1,227 @@
+//! Futex wait queues.
+//!
+//! Shared futexes are keyed by the physical address of the futex word, so
+//! processes mapping the same frame meet on the same queue. Private futexes
+//! (`FUTEX_PRIVATE_FLAG`) are keyed by process and virtual address.
+
+use super::id_alloc::TaskId;
+use super::thread::UserTask;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, Waker};
+use config::target::plat::PAGE_SIZE;
+use memory_addr::VirtAddr;
+use spin::Mutex;
+use struct_define::fd::RobustListHead;
+
+/// Bitset matching every waiter.
+pub const FUTEX_BITSET_MATCH_ANY: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FutexKey {
+    Private(TaskId, usize),
+    Shared(usize),
+}
+
+struct FutexWaiter {
+    /// Queue the waiter is on, changed by requeue.
+    key: Mutex<FutexKey>,
+    bitset: u32,
+    woken: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl FutexWaiter {
+    fn wake(&self) {
+        self.woken.store(true, Ordering::Release);
+        if let Some(waker) = self.waker.lock().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Every futex queue, locked while the futex word is checked so a wake can't
+/// slip in between the check and the enqueue.
+pub struct FutexTable {
+    queues: BTreeMap<FutexKey, VecDeque<Arc<FutexWaiter>>>,
+}
+
+pub static FUTEX_TABLE: Mutex<FutexTable> = Mutex::new(FutexTable {
+    queues: BTreeMap::new(),
+});
+
+impl FutexTable {
+    /// Queue a waiter on `key`, the returned future resolves once it is woken.
+    pub fn wait(&mut self, key: FutexKey, bitset: u32) -> FutexWait {
+        let waiter = Arc::new(FutexWaiter {
+            key: Mutex::new(key),
+            bitset,
+            woken: AtomicBool::new(false),
+            waker: Mutex::new(None),
+        });
+        self.queues.entry(key).or_default().push_back(waiter.clone());
+        FutexWait { waiter }
+    }
+
+    /// Wake up to `count` waiters of `key` whose bitset intersects `bitset`.
+    pub fn wake(&mut self, key: FutexKey, count: usize, bitset: u32) -> usize {
+        let Some(queue) = self.queues.get_mut(&key) else {
+            return 0;
+        };
+        let mut woken = Vec::new();
+        queue.retain(|waiter| {
+            let wake = woken.len() < count && waiter.bitset & bitset != 0;
+            if wake {
+                woken.push(waiter.clone());
+            }
+            !wake
+        });
+        if queue.is_empty() {
+            self.queues.remove(&key);
+        }
+        woken.iter().for_each(|waiter| waiter.wake());
+        woken.len()
+    }
+
+    /// Wake up to `count` waiters of `from` and move up to `requeue` of the
+    /// rest to `to`. Returns how many were woken and moved.
+    pub fn requeue(&mut self, from: FutexKey, to: FutexKey, count: usize, requeue: usize) -> usize {
+        let woken = self.wake(from, count, FUTEX_BITSET_MATCH_ANY);
+        let Some(queue) = self.queues.get_mut(&from) else {
+            return woken;
+        };
+        let moved: Vec<Arc<FutexWaiter>> = queue.drain(..requeue.min(queue.len())).collect();
+        if queue.is_empty() {
+            self.queues.remove(&from);
+        }
+        let count = moved.len();
+        let target = self.queues.entry(to).or_default();
+        for waiter in moved {
+            *waiter.key.lock() = to;
+            target.push_back(waiter);
+        }
+        woken + count
+    }
+
+    fn remove(&mut self, waiter: &Arc<FutexWaiter>) {
+        let key = *waiter.key.lock();
+        if let Some(queue) = self.queues.get_mut(&key) {
+            queue.retain(|x| !Arc::ptr_eq(x, waiter));
+            if queue.is_empty() {
+                self.queues.remove(&key);
+            }
+        }
+    }
+}
+
+/// Future of [`FutexTable::wait`]. Dropping it before it resolves, on a
+/// timeout for example, takes the waiter off its queue.
+pub struct FutexWait {
+    waiter: Arc<FutexWaiter>,
+}
+
+impl Future for FutexWait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.waiter.woken.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        *self.waiter.waker.lock() = Some(cx.waker().clone());
+        // The waiter may have been woken before the waker was stored.
+        match self.waiter.woken.load(Ordering::Acquire) {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for FutexWait {
+    fn drop(&mut self) {
+        if !self.waiter.woken.load(Ordering::Acquire) {
+            FUTEX_TABLE.lock().remove(&self.waiter);
+        }
+    }
+}
+
+/// Key of the futex word at `uaddr` of `task`, `None` if it isn't mapped.
+pub fn futex_key(task: &UserTask, uaddr: usize, private: bool) -> Option<FutexKey> {
+    let (paddr, _) = task.page_table.lock().query_page(VirtAddr::from_usize(uaddr))?;
+    match private {
+        true => Some(FutexKey::Private(task.process_id, uaddr)),
+        false => Some(FutexKey::Shared(paddr.as_usize() + uaddr % PAGE_SIZE)),
+    }
+}
+
+/// Wake up to `count` waiters on `uaddr`, whether they wait on the private or
+/// the shared futex. Used by the kernel, which doesn't know which one
+/// userspace picked.
+pub fn futex_wake_any(task: &UserTask, uaddr: usize, count: usize) -> usize {
+    let keys = [futex_key(task, uaddr, true), futex_key(task, uaddr, false)];
+    let mut table = FUTEX_TABLE.lock();
+    let mut woken = 0;
+    for key in keys.into_iter().flatten() {
+        woken += table.wake(key, count - woken, FUTEX_BITSET_MATCH_ANY);
+    }
+    woken
+}
+
+const FUTEX_WAITERS: u32 = 0x8000_0000;
+const FUTEX_OWNER_DIED: u32 = 0x4000_0000;
+const FUTEX_TID_MASK: u32 = 0x3fff_ffff;
+/// Bound on the robust list walk, a corrupted list may loop.
+const ROBUST_LIST_LIMIT: usize = 2048;
+
+/// Read a user word of the current address space, `None` if it isn't mapped.
+fn read_user<T: Copy>(task: &UserTask, uaddr: usize) -> Option<T> {
+    task.page_table.lock().query_page(VirtAddr::from_usize(uaddr))?;
+    Some(unsafe { (uaddr as *const T).read_volatile() })
+}
+
+/// Mark the robust futex at `uaddr` as owner-died if `tid` holds it.
+fn handle_futex_death(task: &UserTask, uaddr: usize, tid: u32) {
+    let Some(value) = read_user::<u32>(task, uaddr) else {
+        return;
+    };
+    if value & FUTEX_TID_MASK != tid {
+        return;
+    }
+    unsafe {
+        (uaddr as *mut u32).write_volatile(value & FUTEX_WAITERS | FUTEX_OWNER_DIED);
+    }
+    if value & FUTEX_WAITERS != 0 {
+        futex_wake_any(task, uaddr, 1);
+    }
+}
+
+/// Release the robust futexes still held by the dying thread `task`, `head`
+/// is what it passed to `set_robust_list`.
+pub fn exit_robust_list(task: &UserTask, head: usize) {
+    let tid = task.task_id.0 as u32;
+    let Some(list) = read_user::<RobustListHead>(task, head) else {
+        return;
+    };
+    // Bit 0 of an entry marks a PI futex, it doesn't change the walk.
+    let futex_of = |entry: usize| entry.wrapping_add_signed(list.futex_offset);
+    let mut entry = list.list & !1;
+    for _ in 0..ROBUST_LIST_LIMIT {
+        if entry == head {
+            break;
+        }
+        let Some(next) = read_user::<usize>(task, entry) else {
+            break;
+        };
+        // The entry being locked or unlocked is handled below.
+        if entry != list.list_op_pending & !1 {
+            handle_futex_death(task, futex_of(entry), tid);
+        }
+        entry = next & !1;
+    }
+    if list.list_op_pending != 0 {
+        handle_futex_death(task, futex_of(list.list_op_pending & !1), tid);
+    }
+}
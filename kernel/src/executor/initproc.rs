@@ -1,5 +1,6 @@
 use arch::os_shut_down;
-use crate::executor::ops::yield_now;
+use crate::executor::sync::WaitExit;
+use crate::executor::thread::UserTask;
 use crate::executor::executor::{release_task, TASK_MAP, tid2task};
 use alloc::vec::Vec;
 use filesystem::file::OpenFlags;
@@ -10,73 +11,129 @@ use log::debug;
 use crate::executor::task::TaskType;
 use crate::executor::thread::add_user_task;
 use alloc::vec;
+use alloc::format;
+use alloc::string::{String, ToString};
+use filesystem::vfs::FileType;
+use struct_define::signal::CLD_EXITED;
+use crate::cmdline::Cmdline;
 
-async fn command(cmd: &str) {
+/// Environment of the programs started by the kernel.
+const INIT_ENVP: &[&str] = &[
+    "LD_LIBRARY_PATH=/",
+    "PS1=\x1b[1m\x1b[32mByteOS\x1b[0m:\x1b[1m\x1b[34m\\w\x1b[0m\\$ ",
+    "PATH=/:/bin:/usr/bin",
+    "UB_BINDIR=./",
+];
+
+/// Run `cmd` and wait for it, giving the `si_code` and `si_status` it exited
+/// with, or `None` if there is no such program.
+async fn command(cmd: &str) -> Option<(i32, i32)> {
     info!("Command started: {}", cmd);
-    let mut args: Vec<&str> = cmd.split(" ").filter(|x| *x != "").collect();
+    let args: Vec<&str> = cmd.split(" ").filter(|x| *x != "").collect();
     debug!("cmd: {}  args: {:?}", cmd, args);
-    let filename = args.drain(..1).last().unwrap();
+    run(args).await
+}
+
+/// Run the program `argv[0]` with `argv` and wait for it.
+async fn run(argv: Vec<&str>) -> Option<(i32, i32)> {
+    let filename = *argv.first()?;
     info!("Attempting to execute file: {}", filename);
     match File::open(filename.into(), OpenFlags::O_RDONLY) {
         Ok(_) => {
             info!("File exists, preparing to execute: {}", filename);
-            let mut args_extend = vec![filename];
-            args_extend.extend(args.into_iter());
-            info!("Final arguments: {:?}", args_extend);
-            let task_id = add_user_task(&filename, args_extend, Vec::new());
-            info!("Task created with ID: {:?}", task_id);
-            let task = tid2task(task_id).unwrap();
-            loop {
-                if task.exit_code().is_some() {
-                    release_task(task_id);
-                    break;
+            info!("Final arguments: {:?}", argv);
+            let task_id = match add_user_task(&filename, argv.clone(), INIT_ENVP.to_vec()) {
+                Ok(task_id) => task_id,
+                Err(e) => {
+                    println!("{}: {}", filename, e.as_str());
+                    return None;
                 }
-                yield_now().await;
-            }
-            info!("Command completed: {}", cmd);
+            };
+            info!("Task created with ID: {:?}", task_id);
+            let task = tid2task(task_id)
+                .and_then(|task| task.downcast_arc::<UserTask>().ok())
+                .unwrap();
+            WaitExit(task.clone()).await;
+            let status = task.exit_info();
+            release_task(task_id);
+            info!("Command completed: {:?}", argv);
+            Some(status)
         }
         Err(e) => {
             info!("Failed to open file: {}, error: {:?}", filename, e);
-            println!("unknown command: {}", cmd);
+            println!("unknown command: {}", argv.join(" "));
+            None
+        }
+    }
+}
+
+/// The tests of the suite at `path`: the lines of a list file, or the
+/// programs and scripts of a directory.
+fn testsuite_commands(path: &str) -> Vec<String> {
+    let file = match File::open(path, OpenFlags::O_RDONLY) {
+        Ok(file) => file,
+        Err(e) => {
+            println!("testsuite {}: {:?}", path, e);
+            return Vec::new();
         }
+    };
+    let is_dir = file.inner.getattr().is_ok_and(|attr| attr.file_type == FileType::Directory);
+    if is_dir {
+        let mut entries = file.read_dir().unwrap_or_default();
+        entries.retain(|entry| entry.file_type == FileType::File);
+        entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+        return entries
+            .iter()
+            .map(|entry| {
+                let script = format!("{}/{}", path.trim_end_matches('/'), entry.filename);
+                match script.ends_with(".sh") {
+                    true => format!("busybox sh {}", script),
+                    false => script,
+                }
+            })
+            .collect();
     }
+    let mut buf = vec![0u8; file.get_file_size().unwrap_or(0)];
+    let len = file.read_at(0, &mut buf).unwrap_or(0);
+    String::from_utf8_lossy(&buf[..len])
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(String::from)
+        .collect()
 }
 
-pub async fn initproc() {
+/// Run every test of the suite at `path`, print whether each passed, then
+/// shut down.
+async fn testsuite(path: &str) -> ! {
+    let tests = testsuite_commands(path);
+    let mut passed = 0;
+    for test in &tests {
+        println!("========== START {} ==========", test);
+        let result = match command(test).await {
+            Some((CLD_EXITED, 0)) => {
+                passed += 1;
+                "PASS".to_string()
+            }
+            Some((CLD_EXITED, code)) => format!("FAIL (exit code {})", code),
+            Some((_, signo)) => format!("FAIL (signal {})", signo),
+            None => "FAIL (not found)".to_string(),
+        };
+        println!("========== END {} ==========", test);
+        println!("testsuite: {}: {}", test, result);
+    }
+    println!("testsuite: {} of {} tests passed", passed, tests.len());
+    os_shut_down();
+}
+
+pub async fn initproc(cmdline: Cmdline) {
     println!("start kernel tasks");
-    command("busybox sh").await;
-    //command("bin/ls").await;
-    // command("basic/brk").await;
-    // command("basic/chdir").await;
-    // command("basic/clone").await;
-    // command("basic/close").await;
-    // command("basic/dup").await;
-    // command("basic/dup2").await;
-    // command("basic/execve").await;
-    // command("basic/exit").await;
-    // command("basic/fork").await;
-    // command("basic/fstat").await;
-    // command("basic/getcwd").await;
-    // command("basic/getdents").await;
-    // command("basic/getpid").await;
-    // command("basic/getppid").await;
-    // command("basic/gettimeofday").await;
-    // command("basic/mkdir").await;
-    // command("basic/mmap").await;
-    // command("basic/mount").await;
-    // command("basic/munmap").await;
-    // command("basic/open").await;
-    // command("basic/openat").await;
-    // command("basic/pipe").await;
-    // command("basic/read").await;
-    // command("basic/sleep").await;
-    // command("basic/times").await;
-    // command("basic/umount").await;
-    // command("basic/uname").await;
-    // command("basic/unlink").await;
-    // command("basic/wait").await;
-    // command("basic/waitpid").await;
-    // command("basic/yield").await;
+    match &cmdline.testsuite {
+        Some(path) => testsuite(path).await,
+        None => {
+            run(cmdline.init_argv()).await;
+        }
+    }
 
     // Shutdown if there just have blankkernel task.
     if TASK_MAP
@@ -0,0 +1,363 @@
+//! Interval timers: the `setitimer` timers and the POSIX timers of
+//! `timer_create`.
+//!
+//! Timers on the wall clock are driven by a kernel task sleeping until they
+//! expire, so they fire while the process is blocked. Timers on the CPU
+//! clocks of the process are checked on the way back to user mode, which is
+//! where CPU time gets charged.
+
+use super::error::TaskError;
+use super::executor::{spawn_blank, tid2task};
+use super::id_alloc::TaskId;
+use super::thread::{ProcessControlBlock, UserTask};
+use alloc::sync::{Arc, Weak};
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+use struct_define::signal::{SI_KERNEL, SIGALRM, SIGPROF, SIGVTALRM, SigInfo};
+use timer::{SleepUntil, get_time, sleep_until};
+
+/// `which` of `setitimer`.
+pub const ITIMER_REAL: usize = 0;
+pub const ITIMER_VIRTUAL: usize = 1;
+pub const ITIMER_PROF: usize = 2;
+
+/// Most POSIX timers a process may have.
+pub const TIMER_MAX: usize = 1024;
+
+/// Tells the kernel tasks of wall clock timers apart, so the task of a
+/// deleted timer leaves alone a new timer that got its id.
+static NEXT_DRIVER: AtomicU64 = AtomicU64::new(1);
+
+/// The clock a timer runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerClock {
+    /// Time since boot, all the wall clocks are this one.
+    Real,
+    /// User CPU time of the process.
+    Virtual,
+    /// User and system CPU time of the process.
+    Prof,
+}
+
+/// A timer of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerKey {
+    ITimer(usize),
+    Posix(usize),
+}
+
+/// How an expiration is made known, `None` for `SIGEV_NONE`.
+#[derive(Debug, Clone, Copy)]
+pub struct TimerNotify {
+    pub signo: usize,
+    /// Thread of `SIGEV_THREAD_ID`, the process otherwise.
+    pub tid: Option<TaskId>,
+    /// `sigev_value`.
+    pub value: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IntervalTimer {
+    pub clock: TimerClock,
+    /// Next expiration on `clock`, `None` while disarmed.
+    pub expires: Option<Duration>,
+    pub interval: Duration,
+    pub notify: Option<TimerNotify>,
+    /// Expirations missed before the last one was delivered.
+    pub overrun: usize,
+    /// The kernel task driving the timer on the wall clock, if it has one.
+    driver: Option<u64>,
+    /// Wakes that task while it sleeps, for it to see the timer changed.
+    waker: Option<Waker>,
+}
+
+impl IntervalTimer {
+    pub fn new(clock: TimerClock, notify: Option<TimerNotify>) -> Self {
+        Self {
+            clock,
+            expires: None,
+            interval: Duration::ZERO,
+            notify,
+            overrun: 0,
+            driver: None,
+            waker: None,
+        }
+    }
+
+    /// The disarmed `setitimer` timer `which`.
+    pub fn itimer(which: usize) -> Self {
+        let (clock, signo) = match which {
+            ITIMER_REAL => (TimerClock::Real, SIGALRM),
+            ITIMER_VIRTUAL => (TimerClock::Virtual, SIGVTALRM),
+            _ => (TimerClock::Prof, SIGPROF),
+        };
+        let notify = TimerNotify {
+            signo,
+            tid: None,
+            value: 0,
+        };
+        Self::new(clock, Some(notify))
+    }
+
+    /// Time left until the next expiration, zero if disarmed, and the
+    /// interval.
+    pub fn get(&self, now: Duration) -> (Duration, Duration) {
+        let left = self.expires.map_or(Duration::ZERO, |x| x.saturating_sub(now));
+        (left, self.interval)
+    }
+
+    /// Arm the timer to expire at `expires`, or disarm it with `None`.
+    fn arm(&mut self, expires: Option<Duration>, interval: Duration) {
+        self.expires = expires;
+        self.interval = interval;
+        self.overrun = 0;
+        self.wake_driver();
+    }
+
+    /// Wake the kernel task driving the timer after it changed, or before
+    /// it is deleted.
+    pub fn wake_driver(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// Expire the timer if it is due at `now`. Returns the expirations that
+    /// were missed on the way, the timer is moved past `now`.
+    fn expire(&mut self, now: Duration) -> Option<usize> {
+        let expires = self.expires.filter(|x| *x <= now)?;
+        if self.interval.is_zero() {
+            self.expires = None;
+            self.overrun = 0;
+            return Some(0);
+        }
+        let missed = (now - expires).as_nanos() / self.interval.as_nanos();
+        let next = expires.as_nanos() + (missed + 1) * self.interval.as_nanos();
+        self.expires = Some(Duration::from_nanos(next as u64));
+        self.overrun = missed.min(i32::MAX as u128) as usize;
+        Some(self.overrun)
+    }
+}
+
+/// The `setitimer` timers of a new process.
+pub fn default_itimers() -> [IntervalTimer; 3] {
+    [ITIMER_REAL, ITIMER_VIRTUAL, ITIMER_PROF].map(IntervalTimer::itimer)
+}
+
+impl ProcessControlBlock {
+    pub fn timer_mut(&mut self, key: TimerKey) -> Option<&mut IntervalTimer> {
+        match key {
+            TimerKey::ITimer(which) => self.itimers.get_mut(which),
+            TimerKey::Posix(id) => self.posix_timers.get_mut(&id),
+        }
+    }
+
+    /// Add a POSIX timer with the lowest free id.
+    pub fn add_posix_timer(&mut self, timer: IntervalTimer) -> Result<usize, TaskError> {
+        let id = (0..TIMER_MAX)
+            .find(|id| !self.posix_timers.contains_key(id))
+            .ok_or(TaskError::EAGAIN)?;
+        self.posix_timers.insert(id, timer);
+        Ok(id)
+    }
+}
+
+impl UserTask {
+    /// Current time of `clock`.
+    pub fn clock_now(&self, clock: TimerClock) -> Duration {
+        match clock {
+            TimerClock::Real => get_time(),
+            TimerClock::Virtual => self.process_times().utime,
+            TimerClock::Prof => self.process_times().total(),
+        }
+    }
+
+    /// Time left on timer `key` and its interval.
+    pub fn get_timer(&self, key: TimerKey) -> Result<(Duration, Duration), TaskError> {
+        let clock = self.timer_clock(key)?;
+        let now = self.clock_now(clock);
+        let mut pcb = self.pcb.lock();
+        let timer = pcb.timer_mut(key).ok_or(TaskError::EINVAL)?;
+        Ok(timer.get(now))
+    }
+
+    /// Arm timer `key` to expire after `value`, or at `value` on its clock if
+    /// `absolute`, then every `interval`. A zero `value` disarms it. Returns
+    /// what `get_timer` did before.
+    pub fn set_timer(
+        self: &Arc<Self>,
+        key: TimerKey,
+        value: Duration,
+        interval: Duration,
+        absolute: bool,
+    ) -> Result<(Duration, Duration), TaskError> {
+        let clock = self.timer_clock(key)?;
+        let now = self.clock_now(clock);
+        let expires = match (value.is_zero(), absolute) {
+            (true, _) => None,
+            (false, true) => Some(value),
+            (false, false) => Some(now.saturating_add(value)),
+        };
+        let (old, driver) = {
+            let mut pcb = self.pcb.lock();
+            let timer = pcb.timer_mut(key).ok_or(TaskError::EINVAL)?;
+            let old = timer.get(now);
+            timer.arm(expires, interval);
+            // A timer that already has a task gets it woken, it picks up the
+            // new expiration.
+            let mut driver = None;
+            if clock == TimerClock::Real && expires.is_some() && timer.driver.is_none() {
+                driver = Some(NEXT_DRIVER.fetch_add(1, Ordering::Relaxed));
+                timer.driver = driver;
+            }
+            (old, driver)
+        };
+        if let Some(driver) = driver {
+            let process = Arc::downgrade(&self.leader().unwrap_or_else(|| self.clone()));
+            spawn_blank(run_real_timer(process, key, driver));
+        }
+        Ok(old)
+    }
+
+    /// Expirations missed before the last delivered one of POSIX timer `id`.
+    pub fn timer_overrun(&self, id: usize) -> Result<usize, TaskError> {
+        let mut pcb = self.pcb.lock();
+        let timer = pcb.timer_mut(TimerKey::Posix(id)).ok_or(TaskError::EINVAL)?;
+        Ok(timer.overrun)
+    }
+
+    fn timer_clock(&self, key: TimerKey) -> Result<TimerClock, TaskError> {
+        let mut pcb = self.pcb.lock();
+        let timer = pcb.timer_mut(key).ok_or(TaskError::EINVAL)?;
+        Ok(timer.clock)
+    }
+
+    /// Expire timer `key` if it is due at `now`, and send its signal.
+    fn fire_timer(&self, key: TimerKey, now: Duration) {
+        let fired = {
+            let mut pcb = self.pcb.lock();
+            let Some(timer) = pcb.timer_mut(key) else {
+                return;
+            };
+            timer.expire(now).map(|overrun| (timer.notify, overrun))
+        };
+        let Some((Some(notify), overrun)) = fired else {
+            return;
+        };
+        let info = match key {
+            TimerKey::ITimer(_) => SigInfo::new(notify.signo, SI_KERNEL),
+            TimerKey::Posix(id) => SigInfo::timer(notify.signo, id, overrun, notify.value),
+        };
+        let thread = notify.tid.and_then(|tid| {
+            tid2task(tid)
+                .and_then(|task| task.downcast_arc::<UserTask>().ok())
+                .filter(|task| task.process_id == self.process_id)
+        });
+        match thread {
+            Some(thread) => thread.signal_thread(info),
+            None => self.signal_process(info),
+        }
+    }
+
+    /// Fire the timers on the CPU clocks that are due, called on the way
+    /// back to user mode.
+    pub fn check_cpu_timers(&self) {
+        let keys: alloc::vec::Vec<(TimerKey, TimerClock)> = {
+            let pcb = self.pcb.lock();
+            let itimers = pcb
+                .itimers
+                .iter()
+                .enumerate()
+                .map(|(which, timer)| (TimerKey::ITimer(which), timer));
+            let posix = pcb
+                .posix_timers
+                .iter()
+                .map(|(id, timer)| (TimerKey::Posix(*id), timer));
+            itimers
+                .chain(posix)
+                .filter(|(_, timer)| timer.clock != TimerClock::Real && timer.expires.is_some())
+                .map(|(key, timer)| (key, timer.clock))
+                .collect()
+        };
+        if keys.is_empty() {
+            return;
+        }
+        let times = self.process_times();
+        for (key, clock) in keys {
+            let now = match clock {
+                TimerClock::Virtual => times.utime,
+                _ => times.total(),
+            };
+            self.fire_timer(key, now);
+        }
+    }
+}
+
+/// Kernel task of an armed wall clock timer, the only one the timer has.
+/// Sleeps until the timer expires and sends the signal, until the timer is
+/// disarmed or deleted or the process is gone.
+async fn run_real_timer(process: Weak<UserTask>, key: TimerKey, driver: u64) {
+    loop {
+        let deadline = {
+            let Some(task) = process.upgrade().filter(|task| !task.is_zombie()) else {
+                return;
+            };
+            let mut pcb = task.pcb.lock();
+            let Some(timer) = pcb.timer_mut(key).filter(|timer| timer.driver == Some(driver)) else {
+                return;
+            };
+            match timer.expires {
+                Some(deadline) => deadline,
+                None => {
+                    timer.driver = None;
+                    timer.waker = None;
+                    return;
+                }
+            }
+        };
+        TimerSleep {
+            process: process.clone(),
+            key,
+            deadline,
+            sleep: sleep_until(deadline),
+        }
+        .await;
+        let Some(task) = process.upgrade() else {
+            return;
+        };
+        task.fire_timer(key, get_time());
+    }
+}
+
+/// Sleep of the kernel task of a wall clock timer, until `deadline` or until
+/// the timer no longer expires then.
+struct TimerSleep {
+    process: Weak<UserTask>,
+    key: TimerKey,
+    deadline: Duration,
+    sleep: SleepUntil,
+}
+
+impl Future for TimerSleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if Pin::new(&mut self.sleep).poll(cx).is_ready() {
+            return Poll::Ready(());
+        }
+        let Some(task) = self.process.upgrade() else {
+            return Poll::Ready(());
+        };
+        let mut pcb = task.pcb.lock();
+        match pcb.timer_mut(self.key) {
+            Some(timer) if timer.expires == Some(self.deadline) => {
+                timer.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            _ => Poll::Ready(()),
+        }
+    }
+}
@@ -0,0 +1,165 @@
+//! Process groups, sessions and the controlling terminal.
+//!
+//! The console is the only terminal. The processes the kernel starts lead a
+//! session each and make the console its controlling terminal, the last one
+//! started keeps it. A process that calls `setsid` leaves it behind.
+
+use super::error::TaskError;
+use super::signal::user_processes;
+use super::thread::UserTask;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use struct_define::signal::*;
+
+/// A stop or continue of a process, reported by `wait4` with `WUNTRACED`
+/// or `WCONTINUED`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobEvent {
+    Stopped(usize),
+    Continued,
+}
+
+impl JobEvent {
+    /// Wait status of the event.
+    pub fn status(&self) -> i32 {
+        match self {
+            JobEvent::Stopped(signo) => (*signo as i32) << 8 | 0x7f,
+            JobEvent::Continued => 0xffff,
+        }
+    }
+}
+
+/// The controlling terminal state of the console.
+#[derive(Debug, Default)]
+struct Terminal {
+    /// Session the console belongs to.
+    session: Option<usize>,
+    /// Foreground process group.
+    foreground: Option<usize>,
+}
+
+lazy_static! {
+    static ref CONSOLE: Mutex<Terminal> = Mutex::new(Terminal::default());
+}
+
+/// Make the console the controlling terminal of session `sid`, with the
+/// session leader's group in the foreground.
+pub fn claim_console(sid: usize) {
+    let mut console = CONSOLE.lock();
+    console.session = Some(sid);
+    console.foreground = Some(sid);
+}
+
+/// The live processes of group `pgid`.
+pub fn process_group(pgid: usize) -> Vec<Arc<UserTask>> {
+    user_processes()
+        .into_iter()
+        .filter(|task| !task.is_zombie())
+        .filter(|task| task.pcb.lock().pgid == pgid)
+        .collect()
+}
+
+impl UserTask {
+    pub fn pgid(&self) -> usize {
+        self.pcb.lock().pgid
+    }
+
+    pub fn sid(&self) -> usize {
+        self.pcb.lock().sid
+    }
+
+    /// Whether a process group is named after this process.
+    fn is_group_leader(&self) -> bool {
+        !process_group(self.process_id.0).is_empty()
+    }
+
+    /// Move the process to group `pgid`, a new one if it is its own pid.
+    pub fn set_pgid(&self, pgid: usize) -> Result<(), TaskError> {
+        let sid = self.sid();
+        if sid == self.process_id.0 {
+            return Err(TaskError::EPERM);
+        }
+        let joinable = pgid == self.process_id.0
+            || process_group(pgid).iter().any(|task| task.sid() == sid);
+        if !joinable {
+            return Err(TaskError::EPERM);
+        }
+        self.pcb.lock().pgid = pgid;
+        Ok(())
+    }
+
+    /// Start a new session and process group led by this process.
+    pub fn set_sid(&self) -> Result<usize, TaskError> {
+        if self.is_group_leader() {
+            return Err(TaskError::EPERM);
+        }
+        let pid = self.process_id.0;
+        let mut pcb = self.pcb.lock();
+        pcb.sid = pid;
+        pcb.pgid = pid;
+        Ok(pid)
+    }
+
+    /// Fail with `ENOTTY` unless the console is the controlling terminal.
+    fn check_ctty(&self) -> Result<(), TaskError> {
+        match CONSOLE.lock().session == Some(self.sid()) {
+            true => Ok(()),
+            false => Err(TaskError::ENOTTY),
+        }
+    }
+
+    /// `TIOCGPGRP` of the console.
+    pub fn console_foreground(&self) -> Result<usize, TaskError> {
+        self.check_ctty()?;
+        // A group that is gone reports a number no group has.
+        Ok(CONSOLE.lock().foreground.unwrap_or(usize::MAX >> 1))
+    }
+
+    /// `TIOCSPGRP` of the console.
+    pub fn set_console_foreground(&self, pgid: usize) -> Result<(), TaskError> {
+        self.check_ctty()?;
+        self.tty_job_check(SIGTTOU)?;
+        let sid = self.sid();
+        if !process_group(pgid).iter().any(|task| task.sid() == sid) {
+            return Err(TaskError::EPERM);
+        }
+        CONSOLE.lock().foreground = Some(pgid);
+        Ok(())
+    }
+
+    /// `TIOCGSID` of the console.
+    pub fn console_session(&self) -> Result<usize, TaskError> {
+        self.check_ctty()?;
+        Ok(self.sid())
+    }
+
+    /// Job control of a background process using the console: a read
+    /// (`SIGTTIN`) or a change of the terminal (`SIGTTOU`) stops its group.
+    /// The call fails with `EINTR` and starts over once the group continues.
+    /// If the thread ignores or blocks the signal, reads fail with `EIO` and
+    /// changes go through.
+    pub fn tty_job_check(&self, signo: usize) -> Result<(), TaskError> {
+        let (pgid, sid, handler) = {
+            let pcb = self.pcb.lock();
+            (pcb.pgid, pcb.sid, pcb.sig_actions[signo].handler)
+        };
+        let console = CONSOLE.lock();
+        if console.session != Some(sid) || console.foreground == Some(pgid) {
+            return Ok(());
+        }
+        drop(console);
+        if handler == SIG_IGN || self.tcb.read().sig_mask.contains(signo) {
+            return match signo {
+                SIGTTIN => Err(TaskError::EIO),
+                _ => Ok(()),
+            };
+        }
+        let info = SigInfo::new(signo, SI_KERNEL);
+        process_group(pgid)
+            .iter()
+            .for_each(|task| task.signal_process(info));
+        Err(TaskError::EINTR)
+    }
+}
@@ -1,5 +1,6 @@
 use trap::trap::{TrapFrame, TrapType};
-use log::warn;
+use log::{trace, warn};
+use memory_addr::VirtAddr;
 
 
 pub mod executor;
@@ -10,17 +11,34 @@ pub mod error;
 pub mod initproc;
 pub mod ops;
 pub mod sync;
+pub mod futex;
+pub mod signal;
+pub mod jobctl;
+pub mod cred;
+pub mod namespace;
+pub mod rlimit;
+pub mod itimer;
+pub mod coredump;
+pub mod ptrace;
+pub mod sched;
 
 /// Architecture-specific interrupt handler.
+///
+/// Faults of user code are left to the user handler, which gets them back
+/// from `run_user_task` and signals the task. Kernel faults are fatal, except
+/// for kernel writes to copy-on-write user pages.
 #[unsafe(no_mangle)]
 pub unsafe extern "Rust" fn _interrupt_for_arch(ctx: &mut TrapFrame, trap_type: TrapType, _: usize) {
-    warn!("Interrupt: {:?}, PC: 0x{:x}", trap_type, ctx.sepc);
+    if trap_type.is_fault() && ctx.from_user() {
+        return;
+    }
     match trap_type {
         TrapType::SysCall => {
             warn!("System call from PC: 0x{:x}", ctx.sepc);
         }
         TrapType::Timer => {
-            warn!("Timer interrupt received at PC: 0x{:x}", ctx.sepc);
+            // Fires every tick, the time slice is checked by the user handler.
+            trace!("Timer interrupt received at PC: 0x{:x}", ctx.sepc);
         }
         TrapType::SupervisorExternal => {
             warn!("Supervisor external interrupt received at PC: 0x{:x}", ctx.sepc);
@@ -30,7 +48,12 @@ pub unsafe extern "Rust" fn _interrupt_for_arch(ctx: &mut TrapFrame, trap_type:
             panic!("Breakpoint exception at PC: 0x{:x}", ctx.sepc);
         }
         TrapType::StorePageFault(addr) => {
-              
+            // A write to a page shared by fork copies it and retries.
+            let cow = executor::get_cur_usr_task()
+                .is_some_and(|task| task.handle_cow_fault(VirtAddr::from_usize(addr)));
+            if cow {
+                return;
+            }
             panic!("Store page fault at address 0x{:x}, PC: 0x{:x}, trap frame: {:#x?}", addr, ctx.sepc, ctx);
         }
         TrapType::LoadPageFault(addr) => {
@@ -45,8 +68,14 @@ pub unsafe extern "Rust" fn _interrupt_for_arch(ctx: &mut TrapFrame, trap_type:
               
             panic!("Illegal instruction: 0x{:x} at PC: 0x{:x}, trap frame: {:#x?}", inst, ctx.sepc, ctx);
         }
+        TrapType::AccessFault(addr) => {
+            panic!("Access fault at address 0x{:x}, PC: 0x{:x}, trap frame: {:#x?}", addr, ctx.sepc, ctx);
+        }
+        TrapType::MisalignedAccess(addr) => {
+            panic!("Misaligned access at address 0x{:x}, PC: 0x{:x}, trap frame: {:#x?}", addr, ctx.sepc, ctx);
+        }
         TrapType::Unknown => {
             panic!("Unknown trap type at PC: 0x{:x}, trap frame: {:#x?}", ctx.sepc, ctx);
         }
     }
-}
+}
\ No newline at end of file
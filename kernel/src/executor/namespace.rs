@@ -0,0 +1,297 @@
+//! Mount, UTS and PID namespaces of a process.
+//!
+//! The namespaces are shared by the threads of a process and kept out of
+//! the PCB like the credentials, the VFS looks up mounts while callers hold
+//! the PCB lock. `clone` and `unshare` make new ones with the `CLONE_NEW*`
+//! flags, `setns` joins the ones of the process behind a pidfd.
+//!
+//! Tasks have their task id as pid in the initial PID namespace. A nested
+//! namespace numbers its tasks from 1, the ones of the namespaces below it
+//! included, and the task with pid 1 is the init of the namespace.
+//!
+//! The kernel has no System V IPC objects, an IPC namespace would have
+//! nothing to hold. `CLONE_NEWIPC` is accepted and makes none.
+
+use super::error::TaskError;
+use super::executor::{get_cur_usr_task, tid2task};
+use super::id_alloc::TaskId;
+use super::task::CloneFlags;
+use super::thread::UserTask;
+use alloc::collections::btree_map::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use filesystem::mount::{INIT_MNT_NS, MountNamespace};
+use filesystem::vfs::{Inode, VfsError, VfsResult};
+use lazy_static::lazy_static;
+use spin::{Mutex, RwLock};
+use struct_define::poll_event::PollEvent;
+use struct_define::signal::{SI_KERNEL, SIGKILL, SigInfo};
+
+/// Longest host name and domain name, without the NUL.
+pub const HOST_NAME_MAX: usize = 64;
+
+/// The namespaces `clone`, `unshare` and `setns` take.
+pub const NS_FLAGS: CloneFlags = CloneFlags::NEWNS
+    .union(CloneFlags::NEWUTS)
+    .union(CloneFlags::NEWPID)
+    .union(CloneFlags::NEWIPC);
+
+lazy_static! {
+    pub static ref INIT_UTS_NS: Arc<UtsNamespace> = Arc::new(UtsNamespace {
+        hostname: RwLock::new(String::from("debian")),
+        domainname: RwLock::new(String::new()),
+    });
+    pub static ref INIT_PID_NS: Arc<PidNamespace> = Arc::new(PidNamespace {
+        parent: None,
+        pids: Mutex::new(PidMap::default()),
+    });
+}
+
+/// Host name and NIS domain name, as `uname` reports them.
+#[derive(Debug)]
+pub struct UtsNamespace {
+    pub hostname: RwLock<String>,
+    pub domainname: RwLock<String>,
+}
+
+impl UtsNamespace {
+    /// A new namespace starting out with the names of this one.
+    pub fn fork(&self) -> Arc<Self> {
+        Arc::new(UtsNamespace {
+            hostname: RwLock::new(self.hostname.read().clone()),
+            domainname: RwLock::new(self.domainname.read().clone()),
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct PidMap {
+    /// Last pid handed out.
+    last: usize,
+    pids: BTreeMap<TaskId, usize>,
+    tasks: BTreeMap<usize, TaskId>,
+}
+
+/// A PID namespace. The initial one keeps no map, pids there are task ids.
+#[derive(Debug)]
+pub struct PidNamespace {
+    parent: Option<Arc<PidNamespace>>,
+    pids: Mutex<PidMap>,
+}
+
+impl PidNamespace {
+    /// A namespace nested in this one.
+    pub fn new_child(self: &Arc<Self>) -> Arc<Self> {
+        Arc::new(PidNamespace {
+            parent: Some(self.clone()),
+            pids: Mutex::new(PidMap::default()),
+        })
+    }
+
+    /// This namespace and the ones it is nested in, up to the initial one
+    /// which is left out.
+    fn nested(self: &Arc<Self>) -> Vec<Arc<PidNamespace>> {
+        let mut chain = Vec::new();
+        let mut ns = Some(self.clone());
+        while let Some(cur) = ns.filter(|x| x.parent.is_some()) {
+            ns = cur.parent.clone();
+            chain.push(cur);
+        }
+        chain
+    }
+
+    /// Give task `id` a pid here and in every namespace above.
+    pub fn attach(self: &Arc<Self>, id: TaskId) {
+        for ns in self.nested() {
+            let mut map = ns.pids.lock();
+            map.last += 1;
+            let pid = map.last;
+            map.pids.insert(id, pid);
+            map.tasks.insert(pid, id);
+        }
+    }
+
+    /// The task is gone, so are its pids.
+    pub fn detach(self: &Arc<Self>, id: TaskId) {
+        for ns in self.nested() {
+            let mut map = ns.pids.lock();
+            if let Some(pid) = map.pids.remove(&id) {
+                map.tasks.remove(&pid);
+            }
+        }
+    }
+
+    /// The pid of task `id` here, `None` if it is outside the namespace.
+    pub fn pid_of(&self, id: TaskId) -> Option<usize> {
+        match self.parent {
+            None => Some(id.0),
+            Some(_) => self.pids.lock().pids.get(&id).copied(),
+        }
+    }
+
+    /// The task with pid `pid` here.
+    pub fn task_id(&self, pid: usize) -> Option<TaskId> {
+        match self.parent {
+            None => Some(TaskId(pid)),
+            Some(_) => self.pids.lock().tasks.get(&pid).copied(),
+        }
+    }
+
+    /// Whether `ns` is this namespace or one nested in it.
+    pub fn contains(&self, ns: &Arc<PidNamespace>) -> bool {
+        let mut cur = Some(ns.clone());
+        while let Some(ns) = cur {
+            if core::ptr::eq(Arc::as_ptr(&ns), self) {
+                return true;
+            }
+            cur = ns.parent.clone();
+        }
+        false
+    }
+
+    /// The tasks with a pid here.
+    fn tasks(&self) -> Vec<TaskId> {
+        self.pids.lock().tasks.values().copied().collect()
+    }
+}
+
+/// The namespaces of a process.
+#[derive(Clone)]
+pub struct Namespaces {
+    pub mnt: Arc<MountNamespace>,
+    pub uts: Arc<UtsNamespace>,
+    /// The PID namespace of the process, fixed for its life.
+    pub pid: Arc<PidNamespace>,
+    /// Where children go, moved by `unshare` and `setns`.
+    pub pid_for_children: Arc<PidNamespace>,
+}
+
+impl Default for Namespaces {
+    fn default() -> Self {
+        Namespaces {
+            mnt: INIT_MNT_NS.clone(),
+            uts: INIT_UTS_NS.clone(),
+            pid: INIT_PID_NS.clone(),
+            pid_for_children: INIT_PID_NS.clone(),
+        }
+    }
+}
+
+impl Namespaces {
+    /// Check the namespace flags of `clone` or `unshare`: only the ones we
+    /// have, and only for root.
+    pub fn check_flags(flags: CloneFlags, euid: u32) -> Result<(), TaskError> {
+        let unsupported = CloneFlags::NEWUSER | CloneFlags::NEWNET | CloneFlags::NEWCGROUP;
+        if flags.intersects(unsupported) {
+            return Err(TaskError::EINVAL);
+        }
+        if flags.intersects(NS_FLAGS) && euid != 0 {
+            return Err(TaskError::EPERM);
+        }
+        Ok(())
+    }
+
+    /// Replace the namespaces `flags` name with new ones. A new PID
+    /// namespace is for the children, the caller keeps its pid.
+    pub fn unshare(&mut self, flags: CloneFlags) {
+        if flags.contains(CloneFlags::NEWNS) {
+            self.mnt = self.mnt.fork();
+        }
+        if flags.contains(CloneFlags::NEWUTS) {
+            self.uts = self.uts.fork();
+        }
+        if flags.contains(CloneFlags::NEWPID) {
+            self.pid_for_children = self.pid_for_children.new_child();
+        }
+    }
+
+    /// The namespaces of a child process `clone` makes with `flags`.
+    pub fn for_child(&self, flags: CloneFlags) -> Namespaces {
+        let mut ns = self.clone();
+        ns.unshare(flags);
+        ns.pid = ns.pid_for_children.clone();
+        ns
+    }
+}
+
+impl UserTask {
+    pub fn pid_ns(&self) -> Arc<PidNamespace> {
+        self.ns.read().pid.clone()
+    }
+
+    /// The pid of task `id` as this task sees it, 0 if it is out of sight.
+    pub fn pid_of(&self, id: TaskId) -> usize {
+        self.pid_ns().pid_of(id).unwrap_or(0)
+    }
+
+    /// The thread `pid` names as this task sees it.
+    pub fn task_by_pid(&self, pid: usize) -> Option<Arc<UserTask>> {
+        let id = self.pid_ns().task_id(pid)?;
+        tid2task(id).and_then(|task| task.downcast_arc::<UserTask>().ok())
+    }
+
+    /// The init of the PID namespace of the process, `None` in the initial
+    /// namespace.
+    pub fn ns_init(&self) -> Option<Arc<UserTask>> {
+        match self.pid_ns().parent {
+            Some(_) => self.task_by_pid(1),
+            None => None,
+        }
+    }
+
+    /// Whether the process is the init of its PID namespace, which takes
+    /// the namespace down with it.
+    fn is_ns_init(&self) -> bool {
+        let ns = self.pid_ns();
+        ns.parent.is_some() && ns.task_id(1) == Some(self.process_id)
+    }
+
+    /// The process is exiting. If it was the init of its PID namespace,
+    /// kill everyone left in there.
+    pub fn exit_pid_ns(&self) {
+        if !self.is_ns_init() {
+            return;
+        }
+        let tasks = self.pid_ns().tasks();
+        for task in tasks.into_iter().filter_map(tid2task) {
+            let Ok(task) = task.downcast_arc::<UserTask>() else {
+                continue;
+            };
+            if task.task_id == task.process_id && task.process_id != self.process_id {
+                task.signal_process(SigInfo::new(SIGKILL, SI_KERNEL));
+            }
+        }
+    }
+}
+
+/// A pidfd: a file standing for a process, readable once it exited.
+/// `setns` takes one to join the namespaces of the process.
+#[derive(Debug)]
+pub struct PidFd(pub Arc<UserTask>);
+
+impl Inode for PidFd {
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidArgument)
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> VfsResult<usize> {
+        Err(VfsError::InvalidArgument)
+    }
+
+    fn poll(&self, events: PollEvent) -> VfsResult<PollEvent> {
+        match events.contains(PollEvent::IN) && self.0.is_zombie() {
+            true => Ok(PollEvent::IN),
+            false => Ok(PollEvent::NONE),
+        }
+    }
+}
+
+/// Mount namespace of the caller, for the VFS.
+#[unsafe(no_mangle)]
+pub fn _vfs_current_mnt_ns() -> Arc<MountNamespace> {
+    match get_cur_usr_task() {
+        Some(task) => task.ns.read().mnt.clone(),
+        None => INIT_MNT_NS.clone(),
+    }
+}
@@ -15,11 +15,13 @@ impl Yield {
 impl Future for Yield {
     type Output = ();
 
-    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         match self.0 {
             true => Poll::Ready(()),
             false => {
                 self.0 = true;
+                // Go to the back of the run queue instead of being parked.
+                cx.waker().wake_by_ref();
                 Poll::Pending
             }
         }
@@ -0,0 +1,409 @@
+//! Process tracing for `ptrace`.
+//!
+//! A process is traced by the process that attached to it, or by its parent
+//! after `PTRACE_TRACEME`. Stops are per thread: a traced thread stops at
+//! signal delivery, at system call entry and exit while `PTRACE_SYSCALL`
+//! asks for it, and after a single step. The tracer collects the stops with
+//! `wait4` and resumes the thread with the next request.
+//!
+//! RISC-V has no hardware single-step. A step puts `ebreak` on every
+//! instruction that may run next and takes them out again at the trap. The
+//! other threads run the same code: one that runs into such a breakpoint
+//! takes the trap as spurious and runs the instruction again.
+
+use super::executor::wake_task;
+use super::thread::{ProcessControlBlock, UserTask};
+use alloc::sync::{Arc, Weak};
+use alloc::vec;
+use alloc::vec::Vec;
+use config::target::plat::PAGE_SIZE;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use memory_addr::VirtAddr;
+use page_table_multiarch::MappingFlags;
+use struct_define::signal::{SIGKILL, SigInfo};
+
+/// `PTRACE_SETOPTIONS` option marking system call stops with 0x80.
+pub const PTRACE_O_TRACESYSGOOD: usize = 1;
+
+/// `ebreak` and `c.ebreak`.
+const EBREAK: [u8; 4] = 0x0010_0073u32.to_le_bytes();
+const C_EBREAK: [u8; 2] = 0x9002u16.to_le_bytes();
+
+/// The tracer of a process.
+#[derive(Debug, Clone)]
+pub struct Tracing {
+    /// Main thread of the tracer process.
+    pub tracer: Weak<UserTask>,
+    /// `PTRACE_SETOPTIONS` options.
+    pub options: usize,
+    /// The process exited, and the tracer saw it before the parent. The
+    /// parent hears of it once the tracer lets go.
+    pub held_exit: bool,
+}
+
+/// How a traced thread runs once resumed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TraceMode {
+    #[default]
+    Cont,
+    /// Stop at system call entry and exit.
+    Syscall,
+    /// Stop after the next instruction.
+    Step,
+}
+
+/// A thread stopped for its tracer.
+#[derive(Debug, Clone, Copy)]
+pub struct TraceStop {
+    /// Signal of the stop, `SIGTRAP | 0x80` for a system call stop under
+    /// `PTRACE_O_TRACESYSGOOD`.
+    pub sig: i32,
+    pub info: SigInfo,
+    /// `wait4` reported it already.
+    pub reported: bool,
+}
+
+/// Tracing state of a thread.
+#[derive(Debug, Clone, Default)]
+pub struct ThreadTrace {
+    pub stop: Option<TraceStop>,
+    pub mode: TraceMode,
+    /// Signal the tracer resumed the thread with, 0 for none.
+    pub resume_signal: usize,
+    /// Step breakpoints and the bytes they took the place of.
+    pub breakpoints: Vec<(usize, Vec<u8>)>,
+}
+
+/// Sign-extend the low `bits` bits of `value`.
+fn sext(value: u32, bits: u32) -> isize {
+    ((value << (32 - bits)) as i32 >> (32 - bits)) as isize
+}
+
+impl UserTask {
+    /// The tracer process, if the process is traced.
+    pub fn tracer(&self) -> Option<Arc<UserTask>> {
+        self.pcb.lock().ptrace.as_ref().and_then(|x| x.tracer.upgrade())
+    }
+
+    pub fn trace_options(&self) -> usize {
+        self.pcb.lock().ptrace.as_ref().map_or(0, |x| x.options)
+    }
+
+    /// Whether the process is traced by the process of `tracer`.
+    pub fn traced_by(&self, tracer: &UserTask) -> bool {
+        self.tracer().is_some_and(|x| x.process_id == tracer.process_id)
+    }
+
+    /// Make `tracer` the tracer of this process.
+    pub fn attach_tracer(self: &Arc<Self>, tracer: &Arc<UserTask>) {
+        let tracer = tracer.leader().unwrap_or_else(|| tracer.clone());
+        self.pcb.lock().ptrace = Some(Tracing {
+            tracer: Arc::downgrade(&tracer),
+            options: 0,
+            held_exit: false,
+        });
+        let process = self.leader().unwrap_or_else(|| self.clone());
+        tracer.pcb.lock().tracees.push(process);
+    }
+
+    /// Stop tracing the process and let its threads run on, or pass its
+    /// exit on to the parent if it exited.
+    pub fn detach_tracer(&self) {
+        let Some(tracing) = self.pcb.lock().ptrace.take() else {
+            return;
+        };
+        if let Some(tracer) = tracing.tracer.upgrade() {
+            tracer.pcb.lock().tracees.retain(|x| x.process_id != self.process_id);
+        }
+        for thread in self.threads() {
+            thread.remove_step_breakpoints();
+            let mut tcb = thread.tcb.write();
+            tcb.trace.mode = TraceMode::Cont;
+            if tcb.trace.stop.take().is_some() {
+                drop(tcb);
+                wake_task(thread.task_id);
+            }
+        }
+        if tracing.held_exit {
+            self.report_exit();
+            self.exit_wq.wake_all();
+        }
+    }
+
+    /// Report the exit of the process to its tracer, if that isn't its
+    /// parent. The process stays traced until the tracer collects it with
+    /// `wait4` or goes away. Returns whether there was such a tracer.
+    pub fn notify_tracer_exit(&self) -> bool {
+        let parent = self.parent.read().upgrade();
+        let tracer = {
+            let mut pcb = self.pcb.lock();
+            let Some(tracing) = pcb.ptrace.as_mut() else {
+                return false;
+            };
+            let Some(tracer) = tracing.tracer.upgrade() else {
+                return false;
+            };
+            if parent.is_some_and(|parent| parent.process_id == tracer.process_id) {
+                return false;
+            }
+            tracing.held_exit = true;
+            tracer
+        };
+        let (code, status) = self.exit_info();
+        let info = SigInfo::child(code, tracer.pid_of(self.process_id), self.cred.read().uid, status);
+        tracer.signal_process(info);
+        true
+    }
+
+    /// Whether the exit of the process waits for its tracer to collect it
+    /// before the parent may.
+    pub fn exit_held(&self) -> bool {
+        self.pcb.lock().ptrace.as_ref().is_some_and(|x| x.held_exit)
+    }
+
+    /// Let the processes this one traces go, it is exiting.
+    pub fn detach_tracees(&self) {
+        let tracees = core::mem::take(&mut self.pcb.lock().tracees);
+        tracees.iter().for_each(|tracee| tracee.detach_tracer());
+    }
+
+    /// Resume this stopped thread, delivering `signo` unless it is 0.
+    pub fn trace_resume(&self, mode: TraceMode, signo: usize) {
+        let mut tcb = self.tcb.write();
+        tcb.trace.mode = mode;
+        tcb.trace.resume_signal = signo;
+        tcb.trace.stop = None;
+        drop(tcb);
+        wake_task(self.task_id);
+    }
+
+    /// The stop of a thread that `wait4` has yet to report, and the thread.
+    pub fn unreported_trace_stop(&self) -> Option<(Arc<UserTask>, i32)> {
+        self.threads().into_iter().find_map(|thread| {
+            let stop = thread.tcb.read().trace.stop.filter(|x| !x.reported)?;
+            Some((thread, stop.sig))
+        })
+    }
+
+    /// Copy user memory at `vaddr` into `buf`. Fails on unmapped pages.
+    pub fn peek_user(&self, vaddr: usize, buf: &mut [u8]) -> bool {
+        let page_table = self.page_table.lock();
+        for (i, byte) in buf.iter_mut().enumerate() {
+            let addr = vaddr + i;
+            match page_table.query_page(VirtAddr::from_usize(addr)) {
+                Some((paddr, flags)) if flags.contains(MappingFlags::USER) => {
+                    let paddr = paddr.as_usize() + addr % PAGE_SIZE;
+                    *byte = unsafe { (paddr as *const u8).read_volatile() };
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Write `data` to user memory at `vaddr`, read-only pages included.
+    /// Pages shared with another process get a copy first.
+    pub fn poke_user(&self, vaddr: usize, data: &[u8]) -> bool {
+        let mut page_table = self.page_table.lock();
+        for (i, byte) in data.iter().enumerate() {
+            let addr = vaddr + i;
+            let user = page_table
+                .query_page(VirtAddr::from_usize(addr))
+                .is_some_and(|(_, flags)| flags.contains(MappingFlags::USER));
+            if !user {
+                return false;
+            }
+            let Ok(paddr) = page_table.private_page(VirtAddr::from_usize(addr)) else {
+                return false;
+            };
+            let paddr = paddr.as_usize() + addr % PAGE_SIZE;
+            unsafe { (paddr as *mut u8).write_volatile(*byte) };
+        }
+        arch::flush_icache();
+        true
+    }
+
+    /// Length of the instruction at `pc`, and the instruction.
+    fn fetch_insn(&self, pc: usize) -> Option<(usize, u32)> {
+        let mut low = [0u8; 2];
+        if !self.peek_user(pc, &mut low) {
+            return None;
+        }
+        let low = u16::from_le_bytes(low) as u32;
+        if low & 3 != 3 {
+            return Some((2, low));
+        }
+        let mut high = [0u8; 2];
+        if !self.peek_user(pc + 2, &mut high) {
+            return None;
+        }
+        Some((4, low | (u16::from_le_bytes(high) as u32) << 16))
+    }
+
+    /// Addresses the instruction at `pc` may continue at: the next one,
+    /// and the target of a jump or both sides of a branch.
+    fn next_pcs(&self, pc: usize, regs: &[usize; 32]) -> Vec<usize> {
+        let Some((len, insn)) = self.fetch_insn(pc) else {
+            return vec![];
+        };
+        let reg = |i: u32| regs[i as usize & 31];
+        let next = pc + len;
+        let target = |offset: isize| pc.wrapping_add_signed(offset);
+        if len == 4 {
+            let rs1 = (insn >> 15) & 31;
+            return match insn & 0x7f {
+                // jal
+                0x6f => {
+                    let imm = (insn >> 31) << 20
+                        | ((insn >> 12) & 0xff) << 12
+                        | ((insn >> 20) & 1) << 11
+                        | ((insn >> 21) & 0x3ff) << 1;
+                    vec![target(sext(imm, 21))]
+                }
+                // jalr
+                0x67 => vec![reg(rs1).wrapping_add_signed(sext(insn >> 20, 12)) & !1],
+                // beq, bne, blt, bge, bltu, bgeu
+                0x63 => {
+                    let imm = (insn >> 31) << 12
+                        | ((insn >> 7) & 1) << 11
+                        | ((insn >> 25) & 0x3f) << 5
+                        | ((insn >> 8) & 0xf) << 1;
+                    vec![next, target(sext(imm, 13))]
+                }
+                _ => vec![next],
+            };
+        }
+        let funct3 = insn >> 13;
+        let rs1 = (insn >> 7) & 31;
+        let rs2 = (insn >> 2) & 31;
+        match (insn & 3, funct3) {
+            // c.j
+            (1, 0b101) => {
+                let imm = ((insn >> 12) & 1) << 11
+                    | ((insn >> 11) & 1) << 4
+                    | ((insn >> 9) & 3) << 8
+                    | ((insn >> 8) & 1) << 10
+                    | ((insn >> 7) & 1) << 6
+                    | ((insn >> 6) & 1) << 7
+                    | ((insn >> 3) & 7) << 1
+                    | ((insn >> 2) & 1) << 5;
+                vec![target(sext(imm, 12))]
+            }
+            // c.beqz, c.bnez
+            (1, 0b110 | 0b111) => {
+                let imm = ((insn >> 12) & 1) << 8
+                    | ((insn >> 10) & 3) << 3
+                    | ((insn >> 5) & 3) << 6
+                    | ((insn >> 3) & 3) << 1
+                    | ((insn >> 2) & 1) << 5;
+                vec![next, target(sext(imm, 9))]
+            }
+            // c.jr, c.jalr
+            (2, 0b100) if rs2 == 0 && rs1 != 0 => vec![reg(rs1)],
+            _ => vec![next],
+        }
+    }
+
+    /// Put the breakpoints of a single step from the current user pc, unless
+    /// they are in place already.
+    pub fn insert_step_breakpoints(&self) {
+        let (pc, regs) = {
+            let tcb = self.tcb.read();
+            if !tcb.trace.breakpoints.is_empty() {
+                return;
+            }
+            (tcb.cx.sepc, tcb.cx.x)
+        };
+        let mut targets = self.next_pcs(pc, &regs);
+        targets.dedup();
+        // Keeps the other threads from planting or taking out theirs meanwhile.
+        let pcb = self.pcb.lock();
+        let mut breakpoints = Vec::new();
+        for addr in targets {
+            let Some((len, _)) = self.fetch_insn(addr) else {
+                continue;
+            };
+            // Another thread stepping onto the same instruction put it there
+            // already, and kept what it replaced.
+            if let Some(saved) = self.sibling_breakpoint(&pcb, addr) {
+                breakpoints.push((addr, saved));
+                continue;
+            }
+            let mut saved = vec![0u8; len];
+            let ebreak: &[u8] = match len {
+                2 => &C_EBREAK,
+                _ => &EBREAK,
+            };
+            if self.peek_user(addr, &mut saved) && self.poke_user(addr, ebreak) {
+                breakpoints.push((addr, saved));
+            }
+        }
+        self.tcb.write().trace.breakpoints = breakpoints;
+    }
+
+    /// Take the step breakpoints out. Returns whether there were any.
+    pub fn remove_step_breakpoints(&self) -> bool {
+        let pcb = self.pcb.lock();
+        let breakpoints = core::mem::take(&mut self.tcb.write().trace.breakpoints);
+        for (addr, saved) in breakpoints.iter() {
+            // The breakpoint stays for another thread that is stepping too.
+            if self.sibling_breakpoint(&pcb, *addr).is_none() {
+                self.poke_user(*addr, saved);
+            }
+        }
+        !breakpoints.is_empty()
+    }
+
+    /// The bytes another thread of the process replaced with a step
+    /// breakpoint at `addr`, if it has one there.
+    fn sibling_breakpoint(&self, pcb: &ProcessControlBlock, addr: usize) -> Option<Vec<u8>> {
+        pcb.threads
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|thread| thread.task_id != self.task_id)
+            .find_map(|thread| {
+                let tcb = thread.tcb.read();
+                let (_, saved) = tcb.trace.breakpoints.iter().find(|(x, _)| *x == addr)?;
+                Some(saved.clone())
+            })
+    }
+
+    /// Whether a breakpoint trap at `pc` came from the step of another
+    /// thread: its breakpoint is there, or was and got taken out since.
+    pub fn sibling_step_trap(&self, pc: usize) -> bool {
+        if self.sibling_breakpoint(&self.pcb.lock(), pc).is_some() {
+            return true;
+        }
+        self.fetch_insn(pc).is_some_and(|(len, insn)| {
+            let ebreak: &[u8] = match len {
+                2 => &C_EBREAK,
+                _ => &EBREAK,
+            };
+            insn.to_le_bytes()[..len] != *ebreak
+        })
+    }
+
+    /// Whether `SIGKILL` is on its way to the thread, which ends any stop.
+    fn kill_pending(&self) -> bool {
+        self.tcb.read().sig_pending.set.contains(SIGKILL)
+            || self.pcb.lock().sig_pending.set.contains(SIGKILL)
+    }
+}
+
+/// Wait until the tracer resumes the stopped thread, or it gets killed.
+pub struct TraceWait(pub Arc<UserTask>);
+
+impl Future for TraceWait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The tracer and `SIGKILL` wake the thread.
+        let stopped = self.0.tcb.read().trace.stop.is_some();
+        match stopped && !self.0.kill_pending() {
+            true => Poll::Pending,
+            false => Poll::Ready(()),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Resource limits of a process.
+//!
+//! The limits live in the PCB, so `fork` copies them and `execve` keeps
+//! them. `RLIMIT_NOFILE` is also kept by the fd table, which enforces it.
+
+use super::error::TaskError;
+use super::signal::user_processes;
+use super::thread::UserTask;
+use alloc::string::ToString;
+use config::target::plat::{PAGE_SIZE, USER_STACK_INIT_SIZE, USER_STACK_TOP};
+use filesystem::fd_table::DEFAULT_NOFILE;
+use mem::memregion::{MemRegion, MemRegionType};
+use memory_addr::{VirtAddr, align_down};
+use page_table_multiarch::MappingFlags;
+use struct_define::resource::*;
+use struct_define::signal::{SI_KERNEL, SIGKILL, SIGXCPU, SigInfo};
+
+/// Ceiling of `RLIMIT_NOFILE`.
+pub const NR_OPEN: u64 = 1 << 20;
+
+/// Default `RLIMIT_STACK` soft limit.
+const DEFAULT_STACK: u64 = 8 * 1024 * 1024;
+
+/// The limits of the processes the kernel starts.
+pub fn default_rlimits() -> [RLimit; RLIM_NLIMITS] {
+    let mut limits = [RLimit::INFINITY; RLIM_NLIMITS];
+    limits[RLIMIT_NOFILE] = RLimit::new(DEFAULT_NOFILE as u64, 4096);
+    limits[RLIMIT_STACK] = RLimit::new(DEFAULT_STACK, RLIM_INFINITY);
+    limits
+}
+
+impl UserTask {
+    pub fn rlimit(&self, resource: usize) -> RLimit {
+        self.pcb.lock().rlimits[resource]
+    }
+
+    /// Set a limit of the process. Only root raises a hard limit.
+    pub fn set_rlimit(&self, resource: usize, limit: RLimit) -> Result<(), TaskError> {
+        if resource >= RLIM_NLIMITS || limit.rlim_cur > limit.rlim_max {
+            return Err(TaskError::EINVAL);
+        }
+        let privileged = self.cred.read().euid == 0;
+        let mut pcb = self.pcb.lock();
+        if limit.rlim_max > pcb.rlimits[resource].rlim_max && !privileged {
+            return Err(TaskError::EPERM);
+        }
+        if resource == RLIMIT_NOFILE {
+            if limit.rlim_max > NR_OPEN {
+                return Err(TaskError::EPERM);
+            }
+            pcb.fd_table.limit = limit.rlim_cur as usize;
+        }
+        if resource == RLIMIT_CPU {
+            pcb.xcpu_next = 0;
+        }
+        pcb.rlimits[resource] = limit;
+        Ok(())
+    }
+
+    /// Size of the initial stack of a new program, which `RLIMIT_STACK`
+    /// may cut down.
+    pub fn initial_stack_size(&self) -> usize {
+        let limit = self.rlimit(RLIMIT_STACK).rlim_cur;
+        USER_STACK_INIT_SIZE.min(limit.try_into().unwrap_or(usize::MAX))
+    }
+
+    /// Bytes of user address space in use, checked against `RLIMIT_AS`.
+    pub fn address_space_size(&self) -> usize {
+        let pcb = self.pcb.lock();
+        let tcb = self.tcb.read();
+        pcb.user_ranges(&tcb.stack_region)
+            .iter()
+            .map(|(range, _)| range.size())
+            .sum()
+    }
+
+    /// Whether the address space may grow by `bytes` under `RLIMIT_AS`.
+    pub fn may_grow(&self, bytes: usize) -> bool {
+        let size = self.address_space_size() + bytes;
+        !self.rlimit(RLIMIT_AS).exceeded_by(size as u64)
+    }
+
+    /// Bytes of private writable memory, the heap and private `mmap`s,
+    /// checked against `RLIMIT_DATA`.
+    pub fn data_size(&self) -> usize {
+        let pcb = self.pcb.lock();
+        let mapped: usize = pcb
+            .mem_set
+            .regions
+            .iter()
+            .filter(|region| matches!(region.region_type, MemRegionType::MMAP))
+            .map(|region| region.vaddr_range.size())
+            .sum();
+        mapped + (pcb.heap.get_top() - pcb.heap.get_bottom())
+    }
+
+    /// Whether the private writable memory may grow by `bytes` under
+    /// `RLIMIT_DATA`.
+    pub fn may_grow_data(&self, bytes: usize) -> bool {
+        let size = self.data_size() + bytes;
+        !self.rlimit(RLIMIT_DATA).exceeded_by(size as u64)
+    }
+
+    /// Whether the caller's user may start another process.
+    pub fn may_fork(&self) -> bool {
+        let cred = self.cred.read().clone();
+        if cred.euid == 0 {
+            return true;
+        }
+        let count = user_processes()
+            .into_iter()
+            .filter(|task| !task.is_zombie())
+            .filter(|task| task.cred.read().uid == cred.uid)
+            .count();
+        !self.rlimit(RLIMIT_NPROC).exceeded_by(count as u64 + 1)
+    }
+
+    /// Grow the main stack down to `addr` after a fault there. Returns false
+    /// if `addr` is beyond `RLIMIT_STACK` or the stack can't grow.
+    pub fn grow_stack(&self, addr: usize) -> bool {
+        let limit = self.rlimit(RLIMIT_STACK).rlim_cur;
+        let lowest = USER_STACK_TOP.saturating_sub(limit.try_into().unwrap_or(usize::MAX));
+        if addr < lowest || addr >= USER_STACK_TOP {
+            return false;
+        }
+        let start = align_down(addr, PAGE_SIZE);
+        let mut pcb = self.pcb.lock();
+        // The bottom of the stack, with the pages it already grew by.
+        let mut bottom = self.tcb.read().stack_region.vaddr_range.start.as_usize();
+        loop {
+            let below = pcb.mem_set.regions.iter().find(|region| {
+                matches!(region.region_type, MemRegionType::STACK)
+                    && region.vaddr_range.end.as_usize() == bottom
+            });
+            match below {
+                Some(region) => bottom = region.vaddr_range.start.as_usize(),
+                None => break,
+            }
+        }
+        if start >= bottom {
+            return false;
+        }
+        let overlaps = pcb.mem_set.regions.iter().any(|region| {
+            region.vaddr_range.start.as_usize() < bottom && region.vaddr_range.end.as_usize() > start
+        });
+        if overlaps {
+            return false;
+        }
+        let used: usize = pcb
+            .user_ranges(&self.tcb.read().stack_region)
+            .iter()
+            .map(|(range, _)| range.size())
+            .sum();
+        if pcb.rlimits[RLIMIT_AS].exceeded_by((used + bottom - start) as u64) {
+            return false;
+        }
+        let mut region = MemRegion::new_anonymous(
+            VirtAddr::from_usize(start),
+            VirtAddr::from_usize(bottom),
+            MappingFlags::USER | MappingFlags::READ | MappingFlags::WRITE,
+            "user_stack_grow".to_string(),
+            MemRegionType::STACK,
+        );
+        self.page_table.lock().map_region_user_frame(&mut region);
+        pcb.mem_set.push_region(region);
+        true
+    }
+
+    /// Send `SIGXCPU` once the process used up its `RLIMIT_CPU` soft limit,
+    /// and every second after that, and `SIGKILL` at the hard limit.
+    pub fn check_cpu_limit(&self) {
+        let limit = self.rlimit(RLIMIT_CPU);
+        if limit.rlim_cur == RLIM_INFINITY {
+            return;
+        }
+        let secs = self.process_times().total().as_secs();
+        if limit.rlim_max != RLIM_INFINITY && secs >= limit.rlim_max {
+            self.signal_process(SigInfo::new(SIGKILL, SI_KERNEL));
+            return;
+        }
+        let due = {
+            let mut pcb = self.pcb.lock();
+            let due = secs >= pcb.xcpu_next.max(limit.rlim_cur);
+            if due {
+                pcb.xcpu_next = secs + 1;
+            }
+            due
+        };
+        if due {
+            self.signal_process(SigInfo::new(SIGXCPU, SI_KERNEL));
+        }
+    }
+}
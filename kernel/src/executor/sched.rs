@@ -0,0 +1,199 @@
+//! Scheduling classes.
+//!
+//! Every CPU owns a [`RunQueue`]. Real-time tasks (`SCHED_FIFO`/`SCHED_RR`)
+//! always run before the fair class. Inside the fair class the task with the
+//! smallest virtual runtime runs next, and a lower nice value makes virtual
+//! runtime grow slower.
+
+use super::id_alloc::TaskId;
+use super::task::AsyncTaskItem;
+use alloc::collections::{BTreeMap, VecDeque};
+use core::time::Duration;
+
+pub const MIN_RT_PRIO: u32 = 1;
+pub const MAX_RT_PRIO: u32 = 99;
+pub const MIN_NICE: i32 = -20;
+pub const MAX_NICE: i32 = 19;
+
+/// `SCHED_*` policies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchedPolicy {
+    Normal = 0,
+    Fifo = 1,
+    RoundRobin = 2,
+    Batch = 3,
+    Idle = 5,
+}
+
+impl SchedPolicy {
+    pub fn from_raw(policy: usize) -> Option<Self> {
+        match policy {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Fifo),
+            2 => Some(Self::RoundRobin),
+            3 => Some(Self::Batch),
+            5 => Some(Self::Idle),
+            _ => None,
+        }
+    }
+
+    pub fn is_realtime(self) -> bool {
+        matches!(self, Self::Fifo | Self::RoundRobin)
+    }
+}
+
+/// Weight of a nice 0 task.
+const NICE_0_WEIGHT: u64 = 1024;
+
+/// Weights indexed by `nice - MIN_NICE`, each level is about 1.25x apart.
+const NICE_TO_WEIGHT: [u64; 40] = [
+    88761, 71755, 56483, 46273, 36291, 29154, 23254, 18705, 14949, 11916, 9548, 7620, 6100, 4904,
+    3906, 3121, 2501, 1991, 1586, 1277, 1024, 820, 655, 526, 423, 335, 272, 215, 172, 137, 110, 87,
+    70, 56, 45, 36, 29, 23, 18, 15,
+];
+
+/// Scheduling state of a task.
+#[derive(Debug, Clone)]
+pub struct SchedEntity {
+    pub policy: SchedPolicy,
+    /// `MIN_RT_PRIO..=MAX_RT_PRIO` for real-time policies, 0 otherwise.
+    pub rt_priority: u32,
+    pub nice: i32,
+    /// Weighted run time in nanoseconds, orders the fair class.
+    pub vruntime: u64,
+    /// Bit `n` allows the task to run on hart `n`.
+    pub affinity: usize,
+}
+
+impl SchedEntity {
+    pub const fn new() -> Self {
+        Self {
+            policy: SchedPolicy::Normal,
+            rt_priority: 0,
+            nice: 0,
+            vruntime: 0,
+            affinity: usize::MAX,
+        }
+    }
+
+    pub fn weight(&self) -> u64 {
+        NICE_TO_WEIGHT[(self.nice.clamp(MIN_NICE, MAX_NICE) - MIN_NICE) as usize]
+    }
+
+    /// Charge `delta` of CPU time to the task.
+    pub fn charge(&mut self, delta: Duration) {
+        if !self.policy.is_realtime() {
+            self.vruntime += delta.as_nanos() as u64 * NICE_0_WEIGHT / self.weight();
+        }
+    }
+
+    pub fn allows_cpu(&self, cpu_id: usize) -> bool {
+        cpu_id < usize::BITS as usize && self.affinity & (1 << cpu_id) != 0
+    }
+}
+
+impl Default for SchedEntity {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Run queue of one CPU.
+pub struct RunQueue {
+    /// Real-time tasks keyed by priority, FIFO inside one priority.
+    rt: BTreeMap<u32, VecDeque<AsyncTaskItem>>,
+    /// Fair tasks ordered by virtual runtime.
+    fair: BTreeMap<(u64, TaskId), AsyncTaskItem>,
+    /// Virtual runtime of the last picked fair task.
+    min_vruntime: u64,
+}
+
+impl RunQueue {
+    pub const fn new() -> Self {
+        Self {
+            rt: BTreeMap::new(),
+            fair: BTreeMap::new(),
+            min_vruntime: 0,
+        }
+    }
+
+    pub fn push(&mut self, item: AsyncTaskItem) {
+        let (realtime, rt_priority, vruntime) = {
+            let mut entity = item.task.sched().lock();
+            // A task that slept for long must not starve everybody else.
+            entity.vruntime = entity.vruntime.max(self.min_vruntime);
+            (entity.policy.is_realtime(), entity.rt_priority, entity.vruntime)
+        };
+        if realtime {
+            self.rt.entry(rt_priority).or_default().push_back(item);
+        } else {
+            self.fair.insert((vruntime, item.task.get_task_id()), item);
+        }
+    }
+
+    /// Pick the next task to run.
+    pub fn pop(&mut self) -> Option<AsyncTaskItem> {
+        if let Some(mut entry) = self.rt.last_entry() {
+            let item = entry.get_mut().pop_front();
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+            return item;
+        }
+        let ((vruntime, _), item) = self.fair.pop_first()?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        Some(item)
+    }
+
+    /// Hand a task that may run on `cpu_id` to another CPU. Takes the task
+    /// that would run last here.
+    pub fn steal(&mut self, cpu_id: usize) -> Option<AsyncTaskItem> {
+        let allowed = |item: &AsyncTaskItem| item.task.sched().lock().allows_cpu(cpu_id);
+        let key = self
+            .fair
+            .iter()
+            .rev()
+            .find(|&(_, item)| allowed(item))
+            .map(|(key, _)| *key);
+        if let Some(key) = key {
+            return self.fair.remove(&key);
+        }
+        let (priority, index) = self.rt.iter().find_map(|(&priority, queue)| {
+            queue.iter().rposition(|item| allowed(item)).map(|index| (priority, index))
+        })?;
+        let queue = self.rt.get_mut(&priority)?;
+        let item = queue.remove(index);
+        if queue.is_empty() {
+            self.rt.remove(&priority);
+        }
+        item
+    }
+
+    /// Whether a queued task should take the CPU from `current`.
+    pub fn should_preempt(&self, current: &SchedEntity) -> bool {
+        match self.rt.last_key_value() {
+            Some((&priority, _)) => {
+                !current.policy.is_realtime() || priority > current.rt_priority
+            }
+            None => false,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.fair.len() + self.rt.values().map(VecDeque::len).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn retain(&mut self, mut f: impl FnMut(&AsyncTaskItem) -> bool) {
+        self.fair.retain(|_, item| f(item));
+        self.rt.values_mut().for_each(|queue| queue.retain(|item| f(item)));
+        self.rt.retain(|_, queue| !queue.is_empty());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AsyncTaskItem> {
+        self.rt.values().rev().flatten().chain(self.fair.values())
+    }
+}
@@ -0,0 +1,280 @@
+//! Signal generation and bookkeeping.
+//!
+//! Handlers are per process, pending sets and masks are per thread. A signal
+//! sent to a process waits in the process pending set until a thread that
+//! doesn't block it takes it. Delivery happens in the user handler, right
+//! before the thread returns to user mode.
+
+use super::executor::{TASK_MAP, wake_task};
+use super::jobctl::JobEvent;
+use super::thread::UserTask;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use config::target::plat::SIGRETURN_TRAMPOLINE;
+use frame::alloc_frame;
+use lazy_static::lazy_static;
+use memory_addr::{PhysAddr, VirtAddr};
+use mem::pagetable::PageTable;
+use page_table_multiarch::MappingFlags;
+use struct_define::signal::*;
+
+/// What a signal does when its handler is `SIG_DFL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultAction {
+    Terminate,
+    /// Terminate and dump core.
+    Core,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+impl DefaultAction {
+    pub fn of(signo: usize) -> Self {
+        match signo {
+            SIGQUIT | SIGILL | SIGTRAP | SIGABRT | SIGBUS | SIGFPE | SIGSEGV | SIGXCPU
+            | SIGXFSZ | SIGSYS => Self::Core,
+            SIGCHLD | SIGURG | SIGWINCH => Self::Ignore,
+            SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => Self::Stop,
+            SIGCONT => Self::Continue,
+            _ => Self::Terminate,
+        }
+    }
+}
+
+/// Signals that can't be caught, blocked or ignored.
+pub const UNBLOCKABLE: SigSet = SigSet(SigSet::from_signal(SIGKILL).0 | SigSet::from_signal(SIGSTOP).0);
+
+const STOP_SIGNALS: [usize; 4] = [SIGSTOP, SIGTSTP, SIGTTIN, SIGTTOU];
+
+/// Whether `signo` is a valid signal number, 0 is not.
+pub fn valid_signal(signo: usize) -> bool {
+    (1..SIGNAL_COUNT).contains(&signo)
+}
+
+/// Pending signals of a thread or a process. Standard signals are pending
+/// at most once, real-time signals queue up.
+#[derive(Debug, Clone, Default)]
+pub struct SigPending {
+    pub set: SigSet,
+    infos: Vec<SigInfo>,
+}
+
+impl SigPending {
+    pub fn push(&mut self, info: SigInfo) {
+        let signo = info.signo as usize;
+        if signo < SIGRTMIN && self.set.contains(signo) {
+            return;
+        }
+        self.set.insert(signo);
+        self.infos.push(info);
+    }
+
+    /// Take the lowest pending signal of `allowed`.
+    pub fn pop(&mut self, allowed: SigSet) -> Option<SigInfo> {
+        let signo = self.set.intersection(allowed).first()?;
+        let index = self.infos.iter().position(|info| info.signo as usize == signo)?;
+        let info = self.infos.remove(index);
+        if !self.infos.iter().any(|info| info.signo as usize == signo) {
+            self.set.remove(signo);
+        }
+        Some(info)
+    }
+
+    /// Drop every pending instance of `signo`.
+    pub fn discard(&mut self, signo: usize) {
+        self.set.remove(signo);
+        self.infos.retain(|info| info.signo as usize != signo);
+    }
+}
+
+lazy_static! {
+    /// The frame of the `rt_sigreturn` trampoline, shared by every process.
+    static ref SIGRETURN_FRAME: PhysAddr = {
+        // li a7, 139 (SYS_SIGRETURN); ecall
+        const CODE: [u32; 2] = [0x08b0_0893, 0x0000_0073];
+        let paddr = alloc_frame().expect("no frame for the sigreturn trampoline").paddr;
+        unsafe {
+            core::ptr::write_bytes(paddr.as_usize() as *mut u8, 0, config::target::plat::PAGE_SIZE);
+            core::ptr::copy_nonoverlapping(CODE.as_ptr(), paddr.as_usize() as *mut u32, CODE.len());
+        }
+        paddr
+    };
+}
+
+/// Map the `rt_sigreturn` trampoline, signal handlers return to it.
+pub fn map_sigreturn_trampoline(page_table: &mut PageTable) {
+    let flags = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+    let _ = page_table.map_page(VirtAddr::from_usize(SIGRETURN_TRAMPOLINE), *SIGRETURN_FRAME, flags);
+}
+
+impl UserTask {
+    /// The live threads of the process.
+    pub fn threads(&self) -> Vec<Arc<UserTask>> {
+        self.pcb.lock().threads.iter().filter_map(Weak::upgrade).collect()
+    }
+
+    /// Whether `signo` would be dropped right away, because it is ignored
+    /// and no thread would see it.
+    fn signal_ignored(&self, signo: usize, blocked: bool) -> bool {
+        if blocked || UNBLOCKABLE.contains(signo) {
+            return false;
+        }
+        let action = self.pcb.lock().sig_actions[signo];
+        match action.handler {
+            SIG_IGN => true,
+            SIG_DFL => DefaultAction::of(signo) == DefaultAction::Ignore,
+            _ => false,
+        }
+    }
+
+    /// Side effects of generating `signo`: stop and continue signals cancel
+    /// each other, and `SIGCONT` and `SIGKILL` resume a stopped process.
+    fn prepare_signal(&self, signo: usize) {
+        let threads = self.threads();
+        let discard = |signos: &[usize]| {
+            let mut pcb = self.pcb.lock();
+            signos.iter().for_each(|&x| pcb.sig_pending.discard(x));
+            drop(pcb);
+            for thread in threads.iter() {
+                let mut tcb = thread.tcb.write();
+                signos.iter().for_each(|&x| tcb.sig_pending.discard(x));
+            }
+        };
+        match signo {
+            SIGCONT | SIGKILL => {
+                if signo == SIGCONT {
+                    discard(&STOP_SIGNALS);
+                }
+                let mut pcb = self.pcb.lock();
+                let stopped = pcb.stopped.take().is_some();
+                if stopped && signo == SIGCONT {
+                    pcb.job_event = Some(JobEvent::Continued);
+                }
+                drop(pcb);
+                if stopped {
+                    if signo == SIGCONT {
+                        self.notify_parent(CLD_CONTINUED, SIGCONT as i32);
+                        self.wake_waiting_parent();
+                    }
+                    threads.iter().for_each(|thread| wake_task(thread.task_id));
+                }
+            }
+            SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => discard(&[SIGCONT]),
+            _ => {}
+        }
+    }
+
+    /// Send a signal to this thread.
+    pub fn signal_thread(&self, info: SigInfo) {
+        let signo = info.signo as usize;
+        self.prepare_signal(signo);
+        let blocked = self.tcb.read().sig_mask.contains(signo);
+        if self.signal_ignored(signo, blocked) {
+            return;
+        }
+        self.tcb.write().sig_pending.push(info);
+        wake_task(self.task_id);
+    }
+
+    /// Send a signal the thread caused itself, a fault. It can't be ignored
+    /// or blocked, if it is the default action runs.
+    pub fn force_signal(&self, info: SigInfo) {
+        let signo = info.signo as usize;
+        let blocked = {
+            let mut tcb = self.tcb.write();
+            let blocked = tcb.sig_mask.contains(signo);
+            tcb.sig_mask.remove(signo);
+            blocked
+        };
+        let mut pcb = self.pcb.lock();
+        if blocked || pcb.sig_actions[signo].handler == SIG_IGN {
+            pcb.sig_actions[signo] = SigAction::default();
+        }
+        drop(pcb);
+        self.signal_thread(info);
+    }
+
+    /// Send a signal to the process of this thread.
+    pub fn signal_process(&self, info: SigInfo) {
+        let signo = info.signo as usize;
+        self.prepare_signal(signo);
+        let threads = self.threads();
+        let blocked = threads
+            .iter()
+            .all(|thread| thread.tcb.read().sig_mask.contains(signo));
+        if self.signal_ignored(signo, blocked) {
+            return;
+        }
+        self.pcb.lock().sig_pending.push(info);
+        // Any thread may take it, `sigtimedwait` takes blocked ones too.
+        threads.iter().for_each(|thread| wake_task(thread.task_id));
+    }
+
+    /// Take the next signal to deliver, thread signals first.
+    pub fn dequeue_signal(&self, allowed: SigSet) -> Option<SigInfo> {
+        let info = self.tcb.write().sig_pending.pop(allowed);
+        info.or_else(|| self.pcb.lock().sig_pending.pop(allowed))
+    }
+
+    /// Whether a signal the thread doesn't block is pending, which cuts a
+    /// blocking system call short.
+    pub fn signal_pending(&self) -> bool {
+        let (pending, mask) = {
+            let tcb = self.tcb.read();
+            (tcb.sig_pending.set, tcb.sig_mask)
+        };
+        let pending = pending.union(self.pcb.lock().sig_pending.set);
+        !pending.difference(mask).is_empty()
+    }
+
+    /// Tell the parent about a state change of this process with `SIGCHLD`.
+    pub fn notify_parent(&self, code: i32, status: i32) {
+        let Some(parent) = self.parent.read().upgrade() else {
+            return;
+        };
+        let flags = parent.pcb.lock().sig_actions[SIGCHLD].flags;
+        if matches!(code, CLD_STOPPED | CLD_CONTINUED) && flags.contains(SigActionFlags::NOCLDSTOP) {
+            return;
+        }
+        let pid = parent.pid_of(self.process_id);
+        parent.signal_process(SigInfo::child(code, pid, self.cred.read().uid, status));
+    }
+
+    /// Stop the process with `signo`, the threads park until `SIGCONT`.
+    pub fn stop(&self, signo: usize) {
+        let mut pcb = self.pcb.lock();
+        if pcb.stopped.is_some() {
+            return;
+        }
+        pcb.stopped = Some(signo);
+        pcb.job_event = Some(JobEvent::Stopped(signo));
+        drop(pcb);
+        self.notify_parent(CLD_STOPPED, signo as i32);
+        self.wake_waiting_parent();
+    }
+
+    /// Wake the parent if it waits in `wait4`, which waits on the exit
+    /// queue of the main thread.
+    fn wake_waiting_parent(&self) {
+        if let Some(leader) = self.leader() {
+            leader.exit_wq.wake_all();
+        }
+    }
+
+    /// Kill the process with `signo`.
+    pub fn exit_by_signal(&self, signo: usize) {
+        self.pcb.lock().term_signal = Some(signo);
+        self.exit_group(128 + signo);
+    }
+}
+
+/// Every process, by its main thread.
+pub fn user_processes() -> Vec<Arc<UserTask>> {
+    TASK_MAP
+        .lock()
+        .values()
+        .filter_map(|task| task.clone().downcast_arc::<UserTask>().ok())
+        .filter(|task| task.task_id == task.process_id)
+        .collect()
+}
@@ -1,48 +1,264 @@
-
 use crate::executor::id_alloc::TaskId;
 use crate::executor::thread::UserTask;
 use alloc::sync::Arc;
-use timer::get_time;
+use alloc::vec::Vec;
+use filesystem::vfs::Inode;
+use struct_define::poll_event::PollEvent;
 use core::future::{Future};
 use core::pin::Pin;
 use core::task::{Context, Poll};
-use core::time::Duration;
 use crate::executor::error::TaskError;
-use crate::executor::task::AsyncTask;
+use crate::executor::task::{AsyncTask, WaitOptions};
+use crate::executor::jobctl::JobEvent;
+use struct_define::signal::SIGCHLD;
+
+/// What `wait4` and `waitid` found about a child.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitEvent {
+    Exited,
+    Job(JobEvent),
+    /// A thread of a traced process stopped for us, with the signal of the
+    /// stop.
+    Traced(TaskId, i32),
+}
+
+/// Wait for a child of `task` to exit, stop or continue, as `options` asks.
+/// The processes `task` traces count as children, their trace stops are
+/// reported whatever the options.
+///
+/// `pid` selects the children like `wait4` does: -1 is any child, 0 the
+/// children in the caller's process group, `-pgid` the ones in group `pgid`.
+/// Fails with `ECHILD` once no child matches.
+pub struct WaitPid {
+    pub task: Arc<UserTask>,
+    pub pid: isize,
+    pub options: WaitOptions,
+}
+
+impl WaitPid {
+    fn matches(&self, child: &UserTask) -> bool {
+        let selected = match self.pid {
+            -1 => true,
+            0 => child.pgid() == self.task.pgid(),
+            pid if pid < 0 => self.task.pid_of(TaskId(child.pgid())) == pid.unsigned_abs(),
+            pid => self.task.pid_of(child.task_id) == pid as usize,
+        };
+        // Clone children report their exit with another signal than
+        // `SIGCHLD`, `__WCLONE` waits for them only and `__WALL` for both.
+        let clone_child = child.pcb.lock().exit_signal != SIGCHLD;
+        selected
+            && (self.options.contains(WaitOptions::ALL)
+                || self.options.contains(WaitOptions::CLONE) == clone_child)
+    }
+
+    /// The event of `child` we wait for, if it has one.
+    fn event(&self, child: &UserTask) -> Option<WaitEvent> {
+        if child.traced_by(&self.task) {
+            if let Some((thread, sig)) = child.unreported_trace_stop() {
+                return Some(WaitEvent::Traced(thread.task_id, sig));
+            }
+        }
+        if child.is_zombie() {
+            // The tracer collects the exit first.
+            if child.exit_held() && !child.traced_by(&self.task) {
+                return None;
+            }
+            return self.options.contains(WaitOptions::EXITED).then_some(WaitEvent::Exited);
+        }
+        match child.pcb.lock().job_event {
+            Some(event @ JobEvent::Stopped(_)) if self.options.contains(WaitOptions::UNTRACED) => {
+                Some(WaitEvent::Job(event))
+            }
+            Some(JobEvent::Continued) if self.options.contains(WaitOptions::CONTINUED) => {
+                Some(WaitEvent::Job(JobEvent::Continued))
+            }
+            _ => None,
+        }
+    }
 
-pub struct WaitPid(pub Arc<UserTask>, pub isize);
+    fn matching_children(&self) -> Vec<Arc<UserTask>> {
+        let (mut children, tracees) = {
+            let pcb = self.task.pcb.lock();
+            (pcb.children.clone(), pcb.tracees.clone())
+        };
+        for tracee in tracees {
+            if !children.iter().any(|x| x.task_id == tracee.task_id) {
+                children.push(tracee);
+            }
+        }
+        children.into_iter().filter(|x| self.matches(x)).collect()
+    }
+
+    /// Look for a child with an event without waiting, for `WNOHANG`.
+    pub fn poll_now(&self) -> Result<Option<(Arc<UserTask>, WaitEvent)>, TaskError> {
+        let children = self.matching_children();
+        if children.is_empty() {
+            return Err(TaskError::ECHILD);
+        }
+        Ok(children
+            .into_iter()
+            .find_map(|x| self.event(&x).map(|event| (x, event))))
+    }
+}
 
 impl Future for WaitPid {
-    type Output = Result<Arc<UserTask>, TaskError>;
+    type Output = Result<(Arc<UserTask>, WaitEvent), TaskError>;
 
-    fn poll(self: Pin<&mut Self>, _cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
-        let inner = self.0.pcb.lock();
-        let res = inner
-            .children
+    fn poll(self: Pin<&mut Self>, cx: &mut core::task::Context<'_>) -> Poll<Self::Output> {
+        if let Some(res) = self.poll_now()? {
+            return Poll::Ready(Ok(res));
+        }
+        // Every child we may be waiting for wakes us when it exits, stops or
+        // continues.
+        self.matching_children()
             .iter()
-            .find(|x| (self.1 == -1 || x.task_id == TaskId(self.1 as usize)) && x.exit_code().is_some())
-            .cloned();
-        drop(inner);
-        match res {
-            Some(task) => Poll::Ready(Ok(task.clone())),
+            .for_each(|x| x.exit_wq.register(cx.waker()));
+        // So does an orphan we are given.
+        if let Some(leader) = self.task.leader() {
+            leader.exit_wq.register(cx.waker());
+        }
+        match self.poll_now()? {
+            Some(res) => Poll::Ready(Ok(res)),
             None => Poll::Pending,
         }
     }
 }
 
-pub struct Sleep {
-    pub time:Duration,
+/// Wait until the process has exited.
+pub struct WaitExit(pub Arc<UserTask>);
+
+impl Future for WaitExit {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.0.is_zombie() {
+            return Poll::Ready(());
+        }
+        self.0.exit_wq.register(cx.waker());
+        // The process may have exited between the check and the registration.
+        match self.0.is_zombie() {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// Wait until `inode` reports one of `events`.
+pub struct PollWait {
+    pub inode: Arc<dyn Inode>,
+    pub events: PollEvent,
 }
 
-impl Future for Sleep {
+impl Future for PollWait {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let events = self.events;
+        let ready = |inode: &Arc<dyn Inode>| {
+            inode
+                .poll(events)
+                .map_or(true, |res| !res.is_empty())
+        };
+        if ready(&self.inode) {
+            return Poll::Ready(());
+        }
+        match self.inode.register_waker(events, cx.waker()) {
+            Ok(()) => match ready(&self.inode) {
+                true => Poll::Ready(()),
+                false => Poll::Pending,
+            },
+            // The inode can't wake us, so poll it again on the next round.
+            Err(_) => {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Wait until one of several inodes reports a requested event.
+///
+/// Resolves to the returned events, in the same order as the inodes.
+pub struct PollFiles(pub Vec<(Arc<dyn Inode>, PollEvent)>);
+
+impl PollFiles {
+    /// Poll every inode once without waiting.
+    pub fn poll_now(&self) -> Vec<PollEvent> {
+        self.0
+            .iter()
+            .map(|(inode, events)| {
+                // Errors and hangups are always reported.
+                let wanted = events.clone() | PollEvent::ERR | PollEvent::HUP;
+                inode.poll(events.clone()).unwrap_or(PollEvent::ERR) & wanted
+            })
+            .collect()
+    }
+
+    fn any_ready(revents: &[PollEvent]) -> bool {
+        revents.iter().any(|events| !events.is_empty())
+    }
+}
+
+impl Future for PollFiles {
+    type Output = Vec<PollEvent>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let revents = self.poll_now();
+        if Self::any_ready(&revents) {
+            return Poll::Ready(revents);
+        }
+        let mut registered = true;
+        for (inode, events) in self.0.iter() {
+            registered &= inode.register_waker(events.clone(), cx.waker()).is_ok();
+        }
+        if !registered {
+            // Some inode can't wake us, poll again on the next round.
+            cx.waker().wake_by_ref();
+        }
+        let revents = self.poll_now();
+        match Self::any_ready(&revents) {
+            true => Poll::Ready(revents),
+            false => Poll::Pending,
+        }
+    }
+}
+
+/// Wait until the stopped process of the task is continued.
+pub struct WaitContinue(pub Arc<UserTask>);
+
+impl Future for WaitContinue {
     type Output = ();
 
     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
-        let ns = get_time();
+        // `SIGCONT` and `SIGKILL` wake every thread of the process.
+        match self.0.pcb.lock().stopped {
+            Some(_) => Poll::Pending,
+            None => Poll::Ready(()),
+        }
+    }
+}
 
-        match ns > self.time {
-            true => Poll::Ready(()),
+/// Run a blocking system call until it completes, or fail it with `EINTR`
+/// once a signal the task doesn't block is pending.
+pub struct Interruptible<F> {
+    pub task: Arc<UserTask>,
+    pub future: F,
+}
+
+impl<F: Future> Future for Interruptible<F> {
+    type Output = Result<F::Output, TaskError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is never moved out of `self`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        // Senders wake the task, which polls us again.
+        match this.task.signal_pending() {
+            true => Poll::Ready(Err(TaskError::EINTR)),
             false => Poll::Pending,
         }
     }
-}
\ No newline at end of file
+}
@@ -6,6 +6,8 @@ use core::pin::Pin;
 use downcast_rs::{impl_downcast, DowncastSync};
 use core::fmt::Debug;
 use arch::flush_tlb;
+use spin::Mutex;
+use crate::executor::sched::SchedEntity;
 /// A task type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TaskType {
@@ -19,12 +21,16 @@ pub trait AsyncTask: DowncastSync + Send + Sync + Debug {
     fn get_task_id(&self) -> TaskId;
     /// Run before the kernel
     fn before_run(&self);
+    /// Run after the task's future returned from `poll`.
+    fn after_run(&self) {}
     /// Get task type.
     fn get_task_type(&self) -> TaskType;
     /// Exit a task with exit code.
     fn exit(&self, exit_code: usize);
     /// Check if the task was exited successfully
     fn exit_code(&self) -> Option<usize>;
+    /// Get the scheduling state of the task.
+    fn sched(&self) -> &Mutex<SchedEntity>;
 }
 
 
@@ -32,7 +38,7 @@ pub type PinedFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
 use bitflags::bitflags;
 
 bitflags! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct CloneFlags: usize {
         const CSIGNAL      = 0x000000ff;
         const VM           = 0x00000100;
@@ -60,6 +66,23 @@ bitflags! {
         const IO           = 0x80000000;
     }
 }
+
+bitflags! {
+    /// `options` of `wait4` and `waitid`.
+    #[derive(Debug, Clone, Copy)]
+    pub struct WaitOptions: usize {
+        const NOHANG    = 0x00000001;
+        /// `WSTOPPED` of `waitid`.
+        const UNTRACED  = 0x00000002;
+        const EXITED    = 0x00000004;
+        const CONTINUED = 0x00000008;
+        const NOWAIT    = 0x01000000;
+        /// Children are per process, every thread waits for all of them.
+        const NOTHREAD  = 0x20000000;
+        const ALL       = 0x40000000;
+        const CLONE     = 0x80000000;
+    }
+}
 pub struct AsyncTaskItem {
     pub future: PinedFuture,
     pub task: Arc<dyn AsyncTask>,
@@ -83,11 +106,15 @@ impl core::fmt::Debug for AsyncTaskItem {
 #[derive(Debug)]
 pub struct KernelTask {
     id: TaskId,
+    sched: Mutex<SchedEntity>,
 }
 
 impl KernelTask {
     pub fn new() -> Self {
-        Self { id: alloc_tid() }
+        Self {
+            id: alloc_tid(),
+            sched: Mutex::new(SchedEntity::new()),
+        }
     }
 }
 
@@ -118,6 +145,10 @@ impl AsyncTask for KernelTask {
     fn exit_code(&self) -> Option<usize> {
         unreachable!("can't exit kernel task")
     }
+
+    fn sched(&self) -> &Mutex<SchedEntity> {
+        &self.sched
+    }
 }
 
 
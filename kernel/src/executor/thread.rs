@@ -1,9 +1,19 @@
 use super::id_alloc::TaskId;
 use crate::alloc::string::ToString;
+use crate::executor::cred::Credentials;
+use crate::executor::error::TaskError;
 use crate::executor::executor::get_cur_usr_task;
-use crate::executor::executor::{GLOBLE_EXECUTOR, release_task};
+use crate::executor::executor::{GLOBLE_EXECUTOR, cancel_task, is_task_running, release_task, task_cpu, tid2task};
+use crate::executor::futex::{exit_robust_list, futex_wake_any};
 use crate::executor::id_alloc::alloc_tid;
-use crate::executor::task::{AsyncTask, AsyncTaskItem};
+use crate::executor::itimer::{IntervalTimer, default_itimers};
+use crate::executor::jobctl::{JobEvent, claim_console};
+use crate::executor::namespace::Namespaces;
+use crate::executor::ptrace::{ThreadTrace, Tracing};
+use crate::executor::rlimit::default_rlimits;
+use crate::executor::sched::SchedEntity;
+use crate::executor::signal::{SigPending, map_sigreturn_trampoline};
+use crate::executor::task::{AsyncTask, AsyncTaskItem, CloneFlags};
 use crate::user_handler::entry::user_entry;
 use alloc::borrow::ToOwned;
 use alloc::collections::btree_map::BTreeMap;
@@ -11,24 +21,38 @@ use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
-use config::target::plat::{PAGE_SIZE, USER_STACK_TOP};
+use arch::flush_tlb_harts;
+use config::target::plat::{CLK_TCK, HWCAP, PAGE_SIZE, PLATFORM, USER_STACK_INIT_SIZE};
+use crate::executor::coredump::WCOREFLAG;
 use core::mem::size_of;
 use core::task;
 use core::time::Duration;
+use elf_ext::binfmt::resolve_exec;
 use elf_ext::{LoadElfReturn, load_elf_frame};
 use filesystem::fd_table::FdTable;
-use filesystem::file::{self, File};
+use filesystem::file::{self, File, OpenFlags};
 use filesystem::path::{self, Path};
+use filesystem::perm::{S_ISGID, S_ISUID};
 use heap::HeapUser;
 use log::{debug, error, info};
+use mem::memregion::MemRegionType;
 use mem::memset::MemSet;
 use mem::pagetable::PageTable;
-use mem::stack::StackRegion;
+use mem::stack::{InitStack, InitStackInfo, StackRegion};
+use crate::random::fill_random;
 use memory_addr::{PhysAddr, PhysAddrRange, VirtAddr, VirtAddrRange, align_up, align_up_4k};
+use page_table_multiarch::MappingFlags;
 use spin::{Mutex, MutexGuard, RwLock};
 use struct_define::aux::aux_type;
+use struct_define::resource::{RLIM_NLIMITS, RLimit};
+use struct_define::rusage::RUsage;
+use struct_define::signal::{
+    CLD_DUMPED, CLD_EXITED, CLD_KILLED, SIG_IGN, SIGCHLD, SIGNAL_COUNT, SigAction, SigActionFlags, SigInfo, SigSet,
+};
 use trap::trapframe::TrapFrame;
 use trap::trapframe::TrapFrameArgs;
+use wait_queue::WaitQueue;
+use timer::get_time;
 
 #[derive(Debug, Clone)]
 pub struct Shm {
@@ -37,6 +61,36 @@ pub struct Shm {
     pub shm_size: usize,
 }
 
+/// CPU time charged to a thread, a process or its reaped children.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuTimes {
+    pub utime: Duration,
+    pub stime: Duration,
+}
+
+impl CpuTimes {
+    pub fn total(&self) -> Duration {
+        self.utime + self.stime
+    }
+}
+
+impl core::ops::AddAssign for CpuTimes {
+    fn add_assign(&mut self, rhs: Self) {
+        self.utime += rhs.utime;
+        self.stime += rhs.stime;
+    }
+}
+
+impl From<CpuTimes> for RUsage {
+    fn from(times: CpuTimes) -> Self {
+        RUsage {
+            utime: times.utime.into(),
+            stime: times.stime.into(),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ProcessControlBlock {
     pub fd_table: FdTable,
@@ -48,7 +102,70 @@ pub struct ProcessControlBlock {
     pub children: Vec<Arc<UserTask>>,
     pub shms: BTreeMap<usize, Arc<Shm>>,
     pub exit_code: Option<usize>,
-    pub time: Option<Duration>,
+    /// The `setitimer` timers.
+    pub itimers: [IntervalTimer; 3],
+    /// The `timer_create` timers by id.
+    pub posix_timers: BTreeMap<usize, IntervalTimer>,
+    /// CPU time of the threads that already exited.
+    pub exited_times: CpuTimes,
+    /// CPU time of the reaped children and their reaped children.
+    pub children_times: CpuTimes,
+    pub sig_actions: [SigAction; SIGNAL_COUNT],
+    /// Signals sent to the process, taken by whichever thread gets there first.
+    pub sig_pending: SigPending,
+    /// The signal that stopped the process, if it is stopped.
+    pub stopped: Option<usize>,
+    /// The signal that killed the process.
+    pub term_signal: Option<usize>,
+    /// A core file was written when `term_signal` killed it.
+    pub core_dumped: bool,
+    /// Process group and session.
+    pub pgid: usize,
+    pub sid: usize,
+    /// Stop or continue the parent hasn't collected with `wait4` yet.
+    pub job_event: Option<JobEvent>,
+    /// Signal sent to the parent on exit, `SIGCHLD` unless `clone` asked
+    /// for another one. 0 sends none.
+    pub exit_signal: usize,
+    /// Every thread exited, the process waits for its parent to reap it.
+    pub zombie: bool,
+    pub rlimits: [RLimit; RLIM_NLIMITS],
+    /// Seconds of CPU time at which the next `SIGXCPU` is due.
+    pub xcpu_next: u64,
+    /// The auxiliary vector the program was started with, for core files.
+    pub auxv: Vec<(usize, usize)>,
+    /// The tracer, if the process is traced.
+    pub ptrace: Option<Tracing>,
+    /// The processes this one traces.
+    pub tracees: Vec<Arc<UserTask>>,
+    /// The thread stopping the others for `execve` or `exit_group`.
+    pub killing_threads: Option<TaskId>,
+}
+
+impl ProcessControlBlock {
+    /// Ranges holding user pages, and whether they are writable: the memory
+    /// set, the heap and the stack.
+    pub fn user_ranges(&self, stack_region: &StackRegion) -> Vec<(VirtAddrRange, bool)> {
+        let mut ranges: Vec<(VirtAddrRange, bool)> = self
+            .mem_set
+            .regions
+            .iter()
+            .map(|region| {
+                (region.vaddr_range, region.pte_flags.contains(MappingFlags::WRITE))
+            })
+            .collect();
+        ranges.push((self.heap.virt_range, true));
+        ranges.push((stack_region.vaddr_range, true));
+        ranges
+    }
+
+    /// Whether `range` is a mapping shared with other processes rather
+    /// than private to this one.
+    pub fn is_shared(&self, range: VirtAddrRange) -> bool {
+        self.mem_set.regions.iter().any(|region| {
+            region.vaddr_range == range && matches!(region.region_type, MemRegionType::SHARED)
+        })
+    }
 }
 
 #[derive(Clone)]
@@ -56,7 +173,19 @@ pub struct ThreadControlBlock {
     pub stack_region: StackRegion,
     pub cx: TrapFrame,
     pub clear_child_tid: Option<usize>,
+    /// Head of the robust futex list, 0 if none was set.
+    pub robust_list: usize,
     pub thread_exit_code: Option<usize>,
+    /// User time consumed since the task was last scheduled.
+    pub slice_used: Duration,
+    pub times: CpuTimes,
+    /// When `times` was last charged.
+    pub time_stamp: Duration,
+    pub sig_pending: SigPending,
+    pub sig_mask: SigSet,
+    /// `a0` of a system call cut short by a signal, to restart it with.
+    pub syscall_restart: Option<usize>,
+    pub trace: ThreadTrace,
 }
 
 #[allow(dead_code)]
@@ -67,6 +196,13 @@ pub struct UserTask {
     pub pcb: Arc<Mutex<ProcessControlBlock>>,
     pub parent: RwLock<Weak<UserTask>>,
     pub tcb: RwLock<ThreadControlBlock>,
+    /// Shared by the threads of the process.
+    pub cred: Arc<RwLock<Credentials>>,
+    /// Shared by the threads of the process.
+    pub ns: Arc<RwLock<Namespaces>>,
+    /// Woken when this task exits.
+    pub exit_wq: WaitQueue,
+    pub sched: Mutex<SchedEntity>,
 }
 
 impl core::fmt::Debug for UserTask {
@@ -106,7 +242,26 @@ impl UserTask {
             children: vec![],
             shms: BTreeMap::new(),
             exit_code: None,
-            time: None,
+            itimers: default_itimers(),
+            posix_timers: BTreeMap::new(),
+            exited_times: CpuTimes::default(),
+            children_times: CpuTimes::default(),
+            sig_actions: [SigAction::default(); SIGNAL_COUNT],
+            sig_pending: SigPending::default(),
+            stopped: None,
+            term_signal: None,
+            core_dumped: false,
+            pgid: 0,
+            sid: 0,
+            job_event: None,
+            exit_signal: SIGCHLD,
+            zombie: false,
+            rlimits: default_rlimits(),
+            xcpu_next: 0,
+            auxv: vec![],
+            ptrace: None,
+            tracees: vec![],
+            killing_threads: None,
         }));
         let parent = RwLock::new(parent);
         let tcb = RwLock::new(ThreadControlBlock {
@@ -114,6 +269,14 @@ impl UserTask {
             cx: TrapFrame::new(),
             thread_exit_code: None,
             clear_child_tid: None,
+            robust_list: 0,
+            slice_used: Duration::ZERO,
+            times: CpuTimes::default(),
+            time_stamp: Duration::ZERO,
+            sig_pending: SigPending::default(),
+            sig_mask: SigSet::empty(),
+            syscall_restart: None,
+            trace: ThreadTrace::default(),
         });
         Arc::new(UserTask {
             task_id,
@@ -122,6 +285,10 @@ impl UserTask {
             pcb,
             parent,
             tcb,
+            cred: Arc::new(RwLock::new(Credentials::default())),
+            ns: Arc::new(RwLock::new(Namespaces::default())),
+            exit_wq: WaitQueue::new(),
+            sched: Mutex::new(SchedEntity::new()),
         })
     }
 
@@ -130,28 +297,216 @@ impl UserTask {
     }
 
     pub fn init_cx(load_elf_return: LoadElfReturn) -> TrapFrame {
-        let base = load_elf_return.base;
-        let entry_point = load_elf_return.entry_point;
         let sp = load_elf_return.stack_region.get_top();
         let mut cx = TrapFrame::new();
-        cx.set_sepc(base + entry_point);
+        cx.set_sepc(load_elf_return.start_pc());
         cx.set_sp(sp);
+        if let Some(tls) = load_elf_return.tls {
+            cx[TrapFrameArgs::TLS] = tls.tp;
+        }
         cx
     }
 
-    pub fn push_arr(&self, buffer: &[u8]) -> usize {
-        self.tcb.write().stack_region.push_bytes(buffer)
-    }
-
     pub fn exit_group(&self, exit_code: usize) {
-        self.tcb.write().thread_exit_code = Some(exit_code);
         self.pcb.lock().exit_code = Some(exit_code);
+        // The thread that won tears the process down, this one with it.
+        if self.kill_other_threads() {
+            self.thread_exit(exit_code);
+        }
+    }
+
+    /// `exit_group` for a signal that dumps core. The core is written once
+    /// the other threads are off the harts, so the memory and registers in
+    /// it don't change under the dump.
+    pub fn exit_with_core(self: &Arc<Self>, signo: usize) {
+        let exit_code = 128 + signo;
+        {
+            let mut pcb = self.pcb.lock();
+            pcb.term_signal = Some(signo);
+            pcb.exit_code = Some(exit_code);
+        }
+        if let Some(threads) = self.stop_other_threads() {
+            self.dump_core(signo);
+            self.reap_other_threads(threads);
+            self.thread_exit(exit_code);
+        }
+    }
+
+    /// The main thread of the process, which stands for the process until
+    /// it is reaped.
+    pub fn leader(&self) -> Option<Arc<UserTask>> {
+        tid2task(self.process_id).and_then(|task| task.downcast_arc::<UserTask>().ok())
+    }
+
+    pub fn is_zombie(&self) -> bool {
+        self.pcb.lock().zombie
+    }
+
+    /// The last thread is gone: close the files, hand the children to init
+    /// and leave a zombie for the parent to reap.
+    fn exit_process(&self) {
+        let (fd_table, children) = {
+            let mut pcb = self.pcb.lock();
+            if pcb.zombie {
+                return;
+            }
+            pcb.zombie = true;
+            (core::mem::take(&mut pcb.fd_table), core::mem::take(&mut pcb.children))
+        };
+        drop(fd_table);
+        // Nothing writes to the pages any more, the processes still sharing
+        // them may write in place.
+        let ranges = self.pcb.lock().user_ranges(&self.tcb.read().stack_region);
+        let page_table = self.page_table.lock();
+        for (range, _) in ranges {
+            page_table.unshare(range);
+        }
+        drop(page_table);
+        let reaper = self.child_reaper();
+        children.iter().for_each(|child| child.reparent_to(reaper.as_ref()));
+        self.exit_pid_ns();
+        self.detach_tracees();
+        if let Some(leader) = self.leader() {
+            // A tracer sees the exit before the parent does.
+            if !leader.notify_tracer_exit() {
+                leader.detach_tracer();
+                leader.report_exit();
+            }
+            leader.exit_wq.wake_all();
+        }
+    }
+
+    /// Tell the parent the process exited, and release it if the parent
+    /// won't wait for it.
+    pub fn report_exit(&self) {
+        self.notify_exit();
+        self.reap_if_unwaited();
+    }
+
+    /// Send the exit signal to the parent once the process is gone.
+    fn notify_exit(&self) {
+        let (code, status) = self.exit_info();
+        let exit_signal = self.pcb.lock().exit_signal;
+        let Some(parent) = self.parent.read().upgrade() else {
+            return;
+        };
+        if exit_signal != 0 {
+            let mut info = SigInfo::child(code, parent.pid_of(self.process_id), self.cred.read().uid, status);
+            info.signo = exit_signal as i32;
+            parent.signal_process(info);
+        }
+    }
+
+    /// Release a zombie nobody will wait for: an orphan, which init reaps,
+    /// or the child of a parent that ignores `SIGCHLD` or set
+    /// `SA_NOCLDWAIT`.
+    fn reap_if_unwaited(&self) {
+        let parent = self.parent.read().upgrade();
+        if let Some(parent) = parent {
+            let action = parent.pcb.lock().sig_actions[SIGCHLD];
+            if action.handler != SIG_IGN && !action.flags.contains(SigActionFlags::NOCLDWAIT) {
+                return;
+            }
+            parent.pcb.lock().children.retain(|x| x.task_id != self.task_id);
+        }
+        self.release();
+    }
+
+    /// Where the children of this process go when it exits: the init of
+    /// its PID namespace, or else init, the process the kernel started at
+    /// the root of its tree. `None` if that is this process, the kernel
+    /// takes them then.
+    fn child_reaper(&self) -> Option<Arc<UserTask>> {
+        let alive = |init: &Arc<UserTask>| init.process_id != self.process_id && !init.is_zombie();
+        if let Some(init) = self.ns_init().filter(alive) {
+            return Some(init);
+        }
+        let mut root = self.leader()?;
+        loop {
+            let parent = root.parent.read().upgrade();
+            match parent {
+                Some(parent) => root = parent,
+                None => break,
+            }
+        }
+        Some(root).filter(alive)
+    }
+
+    /// Make `reaper` the parent of this orphan, or the kernel if `None`.
+    fn reparent_to(self: &Arc<Self>, reaper: Option<&Arc<UserTask>>) {
+        let parent = reaper.map_or_else(Weak::new, Arc::downgrade);
+        *self.parent.write() = parent.clone();
+        self.threads()
+            .iter()
+            .for_each(|thread| *thread.parent.write() = parent.clone());
+        let Some(reaper) = reaper else {
+            // It may have exited before it lost its parent.
+            if self.is_zombie() {
+                self.release();
+            }
+            return;
+        };
+        reaper.pcb.lock().children.push(self.clone());
+        // A zombie tells its new parent, which may be waiting already.
+        if self.is_zombie() {
+            self.notify_exit();
+            self.reap_if_unwaited();
+        }
+        reaper.exit_wq.wake_all();
+    }
+
+    /// Exit code of the process, `exit_group` sets it for every thread.
+    fn process_exit_code(&self) -> i32 {
+        let code = self.pcb.lock().exit_code.or(self.exit_code());
+        (code.unwrap_or(0) & 0xff) as i32
+    }
+
+    /// The signal that killed the process, and whether it dumped core.
+    fn term_signal(&self) -> (Option<usize>, bool) {
+        let pcb = self.pcb.lock();
+        (pcb.term_signal, pcb.core_dumped)
+    }
+
+    /// `si_code` and `si_status` of the exited process.
+    pub fn exit_info(&self) -> (i32, i32) {
+        let (term_signal, core_dumped) = self.term_signal();
+        match term_signal {
+            Some(signo) if core_dumped => (CLD_DUMPED, signo as i32),
+            Some(signo) => (CLD_KILLED, signo as i32),
+            None => (CLD_EXITED, self.process_exit_code()),
+        }
+    }
+
+    /// Wait status of the exited process, as `wait4` reports it.
+    pub fn exit_status(&self) -> i32 {
+        let (term_signal, core_dumped) = self.term_signal();
+        match term_signal {
+            Some(signo) if core_dumped => signo as i32 | WCOREFLAG,
+            Some(signo) => signo as i32,
+            None => self.process_exit_code() << 8,
+        }
+    }
+
+    /// Map a loaded ELF and its stack into a new page table.
+    fn map_elf(load_elf_return: &mut LoadElfReturn) -> PageTable {
+        let mut pagetable = PageTable::new();
+        let _ = pagetable.restore();
+        for region in load_elf_return.memset.regions.iter_mut() {
+            info!("region: {:?}", region);
+            let _ = pagetable.map_region_user(region);
+            region.is_mapped = true;
+        }
+
+        load_elf_return.stack_region.map(&mut pagetable);
+        map_sigreturn_trampoline(&mut pagetable);
+
+        pagetable
     }
 
     pub fn new_from_file(
         parent: Option<Weak<UserTask>>,
         path: Path,
-    ) -> Option<(Arc<Self>, LoadElfReturn)> {
+    ) -> Result<(Arc<Self>, LoadElfReturn), TaskError> {
         let curr_dir = if let Some(parent_weak) = &parent {
             if let Some(parent_arc) = parent_weak.upgrade() {
                 parent_arc.pcb.lock().curr_dir.clone()
@@ -162,29 +517,19 @@ impl UserTask {
             Arc::new(Path::new("/".to_owned()))
         };
 
-        let mut load_elf_return: LoadElfReturn = load_elf_frame(path.clone());
-        if load_elf_return.entry_point == 0 {
-            // Not a valid ELF file
-            return None;
-        }
+        let mut load_elf_return: LoadElfReturn = load_elf_frame(path.clone(), USER_STACK_INIT_SIZE)?;
         let cx = UserTask::init_cx(load_elf_return.clone());
         info!("load_elf_return: {:?}", load_elf_return);
-        let mut pagetable = PageTable::new();
-        let _ = pagetable.restore();
-        for region in load_elf_return.memset.regions.iter_mut() {
-            info!("region: {:?}", region);
-            let _ = pagetable.map_region_user(region);
-            region.is_mapped = true;
-        }
-
-        load_elf_return.stack_region.map(&mut pagetable);
+        let pagetable = UserTask::map_elf(&mut load_elf_return);
 
         // Clone the return value before the original is partially moved.
         let return_elf_data = load_elf_return.clone();
         // Initialize task based on load_elf_return information
+        let task_id = alloc_tid();
         let task = Arc::new(Self {
-            task_id: alloc_tid(),
-            process_id: alloc_tid(),
+            task_id,
+            // The main thread's id is the pid.
+            process_id: task_id,
             page_table: Arc::new(Mutex::new(pagetable)),
             pcb: Arc::new(Mutex::new(ProcessControlBlock {
                 fd_table: FdTable::new(),
@@ -199,7 +544,27 @@ impl UserTask {
                 children: vec![],
                 shms: BTreeMap::new(),
                 exit_code: None,
-                time: None,
+                itimers: default_itimers(),
+                posix_timers: BTreeMap::new(),
+                exited_times: CpuTimes::default(),
+                children_times: CpuTimes::default(),
+                sig_actions: [SigAction::default(); SIGNAL_COUNT],
+                sig_pending: SigPending::default(),
+                stopped: None,
+                term_signal: None,
+                core_dumped: false,
+                // Processes the kernel starts lead their own session.
+                pgid: task_id.0,
+                sid: task_id.0,
+                job_event: None,
+                exit_signal: SIGCHLD,
+                zombie: false,
+                rlimits: default_rlimits(),
+                xcpu_next: 0,
+                auxv: vec![],
+                ptrace: None,
+                tracees: vec![],
+                killing_threads: None,
             })),
             parent: RwLock::new(parent.unwrap_or_else(|| Weak::new())),
             tcb: RwLock::new(ThreadControlBlock {
@@ -207,172 +572,125 @@ impl UserTask {
                 cx: cx,
                 thread_exit_code: None,
                 clear_child_tid: None,
+                robust_list: 0,
+                slice_used: Duration::ZERO,
+                times: CpuTimes::default(),
+                time_stamp: Duration::ZERO,
+                sig_pending: SigPending::default(),
+                sig_mask: SigSet::empty(),
+                syscall_restart: None,
+                trace: ThreadTrace::default(),
             }),
+            // Processes the kernel starts run as root.
+            cred: Arc::new(RwLock::new(Credentials::default())),
+            // And live in the initial namespaces.
+            ns: Arc::new(RwLock::new(Namespaces::default())),
+            exit_wq: WaitQueue::new(),
+            sched: Mutex::new(SchedEntity::new()),
         });
+        task.pcb.lock().threads.push(Arc::downgrade(&task));
 
-        let sbss_start = load_elf_return.sbss_start;
-        let sbss_size = load_elf_return.sbss_size;
-        let bss_start = load_elf_return.bss_start;
-        let bss_size = load_elf_return.bss_size;
-
-        let pagetable = task.page_table.lock().clone();
-
-        for cur_vaddr in (sbss_start..sbss_start + sbss_size).step_by(4096) {
-            let paddr = pagetable.translate(VirtAddr::from_usize(cur_vaddr)).expect("translate failed");
-            let paddr_usize = paddr.as_usize();
-            unsafe {
-                core::ptr::write_bytes(paddr_usize as *mut u8, 0, PAGE_SIZE);
-            }
-        }
-
-        for cur_vaddr in (bss_start..bss_start + bss_size).step_by(4096) {
-            let paddr = pagetable.translate(VirtAddr::from_usize(cur_vaddr)).expect("translate failed");
-            let paddr_usize = paddr.as_usize();
-            unsafe {
-                core::ptr::write_bytes(paddr_usize as *mut u8, 0, PAGE_SIZE);
-            }
-        }
-
-        Some((task, return_elf_data))
-    }
-
-    pub fn push_num(&self, num: usize) -> usize {
-        self.tcb.write().stack_region.push_num(num);
-        let sp = self.tcb.read().stack_region.get_sp();
-        self.tcb.write().cx.set_sp(sp);
-        sp
-    }
-
-    fn push_array(&self, array: &[u8]) -> usize {
-        self.tcb.write().stack_region.push_bytes(array);
-        let sp = self.tcb.read().stack_region.get_sp();
-        self.tcb.write().cx.set_sp(sp);
-        sp
-    }
-
-    pub fn push_str(&self, str: &str) -> usize {
-        self.push_arr(str.as_bytes());
-        let sp = self.tcb.read().stack_region.get_sp();
-        self.tcb.write().cx.set_sp(sp);
-        sp
+        Ok((task, return_elf_data))
     }
 
+    /// Lay out `args`, `envp` and the auxv of the program `path` just loaded
+    /// on `stack_region`, for it to run with `cred`. Returns the auxv, fails
+    /// with `E2BIG` if they don't fit on the stack.
     pub fn init_task_stack(
-        user_task: &Arc<UserTask>,
-        args: Vec<String>,
-        base: usize,
+        stack_region: &mut StackRegion,
+        args: &[String],
+        envp: &[String],
         path: &str,
-        entry_point: usize,
-        ph_count: usize,
-        ph_entry_size: usize,
-        ph_addr: usize,
-        heap_bottom: usize,
-    ) {
-        log::info!(
-            "[init_task_stack] args: {:?}, base: {:#x}, path: {}, entry_point: {:#x}, ph_count: {}, ph_entry_size: {}, ph_addr: {:#x}, heap_bottom: {:#x}",
-            args,
-            base,
-            path,
-            entry_point,
-            ph_count,
-            ph_entry_size,
-            ph_addr,
-            heap_bottom
-        );
-
-        user_task.tcb.write().cx[TrapFrameArgs::SP] = USER_STACK_TOP;
-        user_task.tcb.write().cx[TrapFrameArgs::SEPC] = base + entry_point;
-        let envp = vec![
-            "LD_LIBRARY_PATH=/",
-            "PS1=\x1b[1m\x1b[32mByteOS\x1b[0m:\x1b[1m\x1b[34m\\w\x1b[0m\\$ \0",
-            "PATH=/:/bin:/usr/bin",
-            "UB_BINDIR=./",
+        cred: &Credentials,
+        elf: &LoadElfReturn,
+    ) -> Result<Vec<(usize, usize)>, TaskError> {
+        debug!("[init_task_stack] path: {}, args: {:?}, envp: {:?}", path, args, envp);
+        let entry = elf.base + elf.entry_point;
+        let auxv = [
+            (aux_type::AT_PHDR, elf.base + elf.ph_addr),
+            (aux_type::AT_PHENT, elf.ph_entry_size),
+            (aux_type::AT_PHNUM, elf.ph_count),
+            (aux_type::AT_PAGESZ, PAGE_SIZE),
+            (aux_type::AT_BASE, elf.interp.map_or(0, |interp| interp.base)),
+            (aux_type::AT_FLAGS, 0),
+            (aux_type::AT_ENTRY, entry),
+            (aux_type::AT_UID, cred.uid as usize),
+            (aux_type::AT_EUID, cred.euid as usize),
+            (aux_type::AT_GID, cred.gid as usize),
+            (aux_type::AT_EGID, cred.egid as usize),
+            (aux_type::AT_HWCAP, HWCAP),
+            (aux_type::AT_CLKTCK, CLK_TCK),
+            (aux_type::AT_SECURE, cred.is_secure() as usize),
+            // There is no vDSO, 0 tells libc to do without.
+            (aux_type::AT_SYSINFO_EHDR, 0),
         ];
-        let envp: Vec<usize> = envp
-            .into_iter()
-            .rev()
-            .map(|x| {
-                let ptr = user_task.push_str(x);
-                log::error!("ENV: {:#x} -> {} (SP: {:#x})", ptr, x, user_task.get_sp());
-                ptr
-            })
-            .collect();
-        let args: Vec<usize> = args
-            .into_iter()
-            .rev()
-            .map(|x| {
-                let ptr = user_task.push_str(&x);
-                log::error!("ARG: {:#x} -> {} (SP: {:#x})", ptr, x, user_task.get_sp());
-                ptr
-            })
-            .collect();
-        let random_ptr = user_task.push_arr(&[0u8; 16]);
-        log::error!("Random bytes at {:#x}", random_ptr);
-        log::error!("Building auxiliary vector");
-        let mut auxv = BTreeMap::new();
-        auxv.insert(aux_type::AT_PLATFORM, user_task.push_str("riscv"));
-        log::error!("SP after AT_PLATFORM: {:#x}", user_task.get_sp());
-        auxv.insert(aux_type::AT_EXECFN, user_task.push_str(path));
-        log::error!("SP after AT_EXECFN: {:#x}", user_task.get_sp());
-        auxv.insert(aux_type::AT_PHNUM, ph_count);
-        auxv.insert(aux_type::AT_PAGESZ, PAGE_SIZE);
-        auxv.insert(aux_type::AT_ENTRY, base + entry_point);
-        auxv.insert(aux_type::AT_PHENT, ph_entry_size);
-        auxv.insert(aux_type::AT_PHDR, base + ph_addr);
-        auxv.insert(aux_type::AT_GID, 0);
-        auxv.insert(aux_type::AT_EGID, 0);
-        auxv.insert(aux_type::AT_UID, 0);
-        auxv.insert(aux_type::AT_EUID, 0);
-        auxv.insert(aux_type::AT_SECURE, 0);
-        auxv.insert(aux_type::AT_RANDOM, random_ptr);
-
-        user_task.push_num(0);
-        log::error!("SP after null terminator: {:#x}", user_task.get_sp());
-        auxv.iter().for_each(|(key, v)| {
-            log::error!("AUXV: {:#x} -> {:#x}", key, v);
-            user_task.push_num(*v);
-            log::error!("SP after value: {:#x}", user_task.get_sp());
-            user_task.push_num(*key);
-            log::error!("SP after key: {:#x}", user_task.get_sp());
-        });
-        user_task.push_num(0);
-        log::error!("SP after env null terminator: {:#x}", user_task.get_sp());
+        let mut random = [0; 16];
+        fill_random(&mut random);
+        let info = InitStackInfo {
+            args,
+            envp,
+            auxv: &auxv,
+            execfn: path,
+            platform: PLATFORM,
+            random,
+        };
+        let stack = InitStack::build(stack_region.get_top(), &info);
+        stack_region.push_init_stack(&stack).ok_or(TaskError::E2BIG)?;
+        Ok(stack.auxv().to_vec())
+    }
 
-        envp.iter().for_each(|x| {
-            log::error!("ENV ptr: {:#x}", x);
-            user_task.push_num(*x);
-            log::error!("SP after env ptr: {:#x}", user_task.get_sp());
-        });
-        user_task.push_num(0);
-        log::error!("SP after args null terminator: {:#x}", user_task.get_sp());
-        args.iter().for_each(|x| {
-            log::error!("ARG ptr: {:#x}", x);
-            user_task.push_num(*x);
-            log::error!("SP after arg ptr: {:#x}", user_task.get_sp());
-        });
+    /// Charge the kernel time since the last stamp, called right before
+    /// returning to user mode.
+    pub fn enter_user(&self) {
+        let now = get_time();
+        let mut tcb = self.tcb.write();
+        let delta = now.saturating_sub(tcb.time_stamp);
+        tcb.times.stime += delta;
+        tcb.time_stamp = now;
+    }
 
-        let argc = args.len();
-        log::error!("Argc: {}", argc);
-        user_task.push_num(argc);
-        log::error!("Final SP after argc: {:#x}", user_task.get_sp());
+    /// Charge the user time since [`Self::enter_user`], called right after
+    /// trapping back into the kernel. Returns the user time charged.
+    pub fn leave_user(&self) -> Duration {
+        let now = get_time();
+        let mut tcb = self.tcb.write();
+        let delta = now.saturating_sub(tcb.time_stamp);
+        tcb.times.utime += delta;
+        tcb.time_stamp = now;
+        delta
+    }
 
-    // Print memory contents from stack top to USER_STACK_TOP - 0x1D1
-    let dump_end = USER_STACK_TOP - 0x1D2;
-    let mut vaddr = USER_STACK_TOP; // Align to 4 bytes
+    /// CPU time of this thread.
+    pub fn thread_times(&self) -> CpuTimes {
+        self.tcb.read().times
+    }
 
-    log::info!("Dumping stack from vaddr {:#x} down to {:#x}", USER_STACK_TOP, dump_end);
-    while vaddr >= dump_end {
-        if let Some(paddr) = user_task.page_table.lock().translate(vaddr.into()) {
-            let value = unsafe { *(paddr.as_usize() as *const u32) };
-            log::info!("vaddr: {:#x} paddr: {:#x} => {:#010x}", vaddr, paddr, value);
-        } else {
-            log::warn!("Failed to translate vaddr {:#x}", vaddr);
-        }
-        if vaddr < 4 {
-            break;
+    /// CPU time of the whole process, exited threads included.
+    pub fn process_times(&self) -> CpuTimes {
+        let pcb = self.pcb.lock();
+        let mut times = pcb.exited_times;
+        for thread in pcb.threads.iter().filter_map(Weak::upgrade) {
+            times += thread.tcb.read().times;
         }
-        vaddr -= 4;
+        times
+    }
+
+    /// CPU time of the reaped children.
+    pub fn children_times(&self) -> CpuTimes {
+        self.pcb.lock().children_times
     }
+
+    /// Resolve a store fault at `vaddr`. Returns false if it isn't a write
+    /// to a copy-on-write page.
+    pub fn handle_cow_fault(&self, vaddr: VirtAddr) -> bool {
+        let writable = {
+            let pcb = self.pcb.lock();
+            let tcb = self.tcb.read();
+            pcb.user_ranges(&tcb.stack_region)
+                .iter()
+                .any(|(range, writable)| *writable && range.contains(vaddr))
+        };
+        writable && self.page_table.lock().copy_on_write(vaddr).is_ok()
     }
 
     pub fn force_cx_ref(&self) -> &'static mut TrapFrame {
@@ -399,16 +717,65 @@ impl UserTask {
     pub fn release(&self) {
         // Ensure that the task was exited successfully.
         assert!(self.exit_code().is_some() || self.tcb.read().thread_exit_code.is_some());
+        self.pid_ns().detach(self.task_id);
         release_task(self.task_id);
     }
 
+    /// Release the futexes of the thread before it dies: the robust futexes
+    /// it still holds and `clear_child_tid`, which gets zeroed and woken.
+    /// Runs in the address space of the process.
+    pub fn futex_exit(&self) {
+        let (robust_list, clear_child_tid) = {
+            let mut tcb = self.tcb.write();
+            (core::mem::take(&mut tcb.robust_list), tcb.clear_child_tid.take())
+        };
+        if robust_list != 0 {
+            exit_robust_list(self, robust_list);
+        }
+        if let Some(tid_addr) = clear_child_tid {
+            if self.write_user_u32(tid_addr, 0) {
+                futex_wake_any(self, tid_addr, 1);
+            }
+        }
+    }
+
+    /// Write `value` to `vaddr` of this task's address space, which doesn't
+    /// have to be the current one. Returns false if `vaddr` isn't mapped.
+    pub fn write_user_u32(&self, vaddr: usize, value: u32) -> bool {
+        let vaddr = VirtAddr::from_usize(vaddr);
+        let mut page_table = self.page_table.lock();
+        // Break copy-on-write sharing first, the write must not reach the
+        // other side.
+        if page_table.copy_on_write(vaddr).is_err() {
+            return false;
+        }
+        match page_table.query_page(vaddr) {
+            Some((paddr, _)) => {
+                let paddr = paddr.as_usize() + vaddr.as_usize() % PAGE_SIZE;
+                unsafe { (paddr as *mut u32).write_volatile(value) };
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn thread_exit(&self, exit_code: usize) {
+        self.futex_exit();
         self.tcb.write().thread_exit_code = Some(exit_code);
-        if self.task_id != self.process_id {
-            self.pcb
-                .lock()
-                .threads
+        let last = {
+            let times = self.tcb.read().times;
+            let mut pcb = self.pcb.lock();
+            pcb.exited_times += times;
+            pcb.threads
                 .retain(|x| x.upgrade().map_or(false, |x| x.task_id != self.task_id));
+            pcb.threads.is_empty()
+        };
+        if last {
+            self.exit_process();
+        }
+        self.exit_wq.wake_all();
+        // The main thread stays until the process is reaped.
+        if self.task_id != self.process_id {
             self.release();
         }
     }
@@ -422,26 +789,42 @@ impl UserTask {
             cx: parent.cx.clone(),
             thread_exit_code: None,
             clear_child_tid: None,
+            robust_list: 0,
+            slice_used: Duration::ZERO,
+            times: CpuTimes::default(),
+            time_stamp: Duration::ZERO,
+            sig_pending: SigPending::default(),
+            sig_mask: parent.sig_mask,
+            syscall_restart: None,
+            trace: ThreadTrace::default(),
         });
         cur_tcb.write().cx[TrapFrameArgs::RET] = 0;
 
         let new_task = Arc::new(Self {
             page_table: self.page_table.clone(),
             task_id,
-            process_id: self.task_id,
+            process_id: self.process_id,
             parent: RwLock::new(self.parent.read().clone()),
             pcb: self.pcb.clone(),
             tcb: cur_tcb,
+            cred: self.cred.clone(),
+            ns: self.ns.clone(),
+            exit_wq: WaitQueue::new(),
+            sched: Mutex::new(SchedEntity::new()),
         });
         self.pcb.lock().threads.push(Arc::downgrade(&new_task));
+        self.pid_ns().attach(task_id);
         new_task
     }
 
-    pub fn process_clone(self: Arc<Self>) -> Arc<Self> {
+    /// Fork the process, the child gets the new namespaces `flags` ask for.
+    pub fn process_clone(self: Arc<Self>, flags: CloneFlags) -> Arc<Self> {
         let parent_tcb = self.tcb.read();
         let mut parent_pcb = self.pcb.lock();
 
         let task_id = alloc_tid();
+        let ns = self.ns.read().for_child(flags);
+        ns.pid.attach(task_id);
 
         // Clone the PCB. This requires ProcessControlBlock to be Clone.
         let mut new_pcb = (*parent_pcb).clone();
@@ -450,25 +833,68 @@ impl UserTask {
         // And it has no children yet.
         new_pcb.threads = vec![];
         new_pcb.children = vec![];
+        new_pcb.exited_times = CpuTimes::default();
+        new_pcb.children_times = CpuTimes::default();
+        // Handlers are inherited, pending signals are not.
+        new_pcb.sig_pending = SigPending::default();
+        new_pcb.stopped = None;
+        new_pcb.term_signal = None;
+        new_pcb.core_dumped = false;
+        new_pcb.job_event = None;
+        new_pcb.xcpu_next = 0;
+        // Timers aren't inherited.
+        new_pcb.itimers = default_itimers();
+        new_pcb.posix_timers.clear();
+        // Nor is tracing.
+        new_pcb.ptrace = None;
+        new_pcb.tracees = vec![];
+        new_pcb.killing_threads = None;
 
         let new_tcb = RwLock::new(ThreadControlBlock {
             stack_region: self.tcb.read().stack_region.clone(),
             cx: parent_tcb.cx.clone(),
             thread_exit_code: None,
             clear_child_tid: None,
+            robust_list: 0,
+            slice_used: Duration::ZERO,
+            times: CpuTimes::default(),
+            time_stamp: Duration::ZERO,
+            sig_pending: SigPending::default(),
+            sig_mask: parent_tcb.sig_mask,
+            syscall_restart: None,
+            trace: ThreadTrace::default(),
         });
         new_tcb.write().cx[TrapFrameArgs::RET] = 0; // Return 0 for child process
 
+        // Each process has its own page table, the user pages are shared
+        // copy-on-write, shared mappings stay shared.
+        let mut page_table = PageTable::new();
+        let _ = page_table.restore();
+        map_sigreturn_trampoline(&mut page_table);
+        {
+            let mut parent_page_table = self.page_table.lock();
+            for (range, _) in parent_pcb.user_ranges(&parent_tcb.stack_region) {
+                let _ = match parent_pcb.is_shared(range) {
+                    true => parent_page_table.share(&mut page_table, range),
+                    false => parent_page_table.share_cow(&mut page_table, range),
+                };
+            }
+            // The other threads may still write through the rights they had.
+            flush_tlb_harts(self.other_threads_harts(&parent_pcb));
+        }
+
         let new_task = Arc::new(Self {
-            // Each process has its own page table.
-            // A real implementation would create a new page table and copy mappings (CoW).
-            // For now, we create a new PageTable by cloning the inner part of the parent's.
-            page_table: Arc::new(Mutex::new(self.page_table.lock().clone())),
+            page_table: Arc::new(Mutex::new(page_table)),
             task_id,
             process_id: task_id, // For a new process, process_id is same as task_id
-            parent: RwLock::new(Arc::downgrade(&self)), // The parent is the current task
+            // The parent is the process, which outlives the calling thread.
+            parent: RwLock::new(Arc::downgrade(&self.leader().unwrap_or(self.clone()))),
             pcb: Arc::new(Mutex::new(new_pcb)),
             tcb: new_tcb,
+            cred: Arc::new(RwLock::new(self.cred.read().clone())),
+            ns: Arc::new(RwLock::new(ns)),
+            exit_wq: WaitQueue::new(),
+            sched: Mutex::new(SchedEntity::new()),
         });
 
         // Add the new task to the parent's children list.
@@ -489,15 +915,181 @@ impl UserTask {
         f(&mut self.pcb.lock())
     }
 
-    // pub fn execve(&self, filename: &str, args: Vec<&str>, envp: Vec<&str>)
-    // {
-    //     add_user_task(filename, args, envp)
-    // }
+    /// The harts running the other threads of the process, a mask.
+    fn other_threads_harts(&self, pcb: &ProcessControlBlock) -> usize {
+        pcb.threads
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|thread| thread.task_id != self.task_id)
+            .filter_map(|thread| task_cpu(thread.task_id))
+            .fold(0, |mask, cpu| mask | 1 << cpu)
+    }
+
+    /// Stop every other thread of the process, done by `execve` and
+    /// `exit_group`.
+    ///
+    /// Returns once none of them runs on a hart any more. Fails if another
+    /// thread is already stopping the others, this one included.
+    fn kill_other_threads(&self) -> bool {
+        match self.stop_other_threads() {
+            Some(threads) => {
+                self.reap_other_threads(threads);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// First half of `kill_other_threads`: make the other threads exit and
+    /// wait until none of them runs on a hart. They stay in the thread list
+    /// until `reap_other_threads`.
+    fn stop_other_threads(&self) -> Option<Vec<Arc<UserTask>>> {
+        let threads: Vec<Arc<UserTask>> = {
+            let mut pcb = self.pcb.lock();
+            if pcb.killing_threads.is_some_and(|killer| killer != self.task_id) {
+                return None;
+            }
+            pcb.killing_threads = Some(self.task_id);
+            pcb.threads
+                .iter()
+                .filter_map(Weak::upgrade)
+                .filter(|thread| thread.task_id != self.task_id)
+                .collect()
+        };
+        let exit_code = self.pcb.lock().exit_code.unwrap_or(0);
+        for thread in threads.iter() {
+            thread.tcb.write().thread_exit_code = Some(exit_code);
+        }
+        // A thread in user mode comes back on its next timer tick, one in
+        // the kernel at its next await, and sees its exit code.
+        for thread in threads.iter() {
+            while is_task_running(thread.task_id) {
+                core::hint::spin_loop();
+            }
+        }
+        Some(threads)
+    }
+
+    /// Second half of `kill_other_threads`: release the threads it stopped.
+    fn reap_other_threads(&self, threads: Vec<Arc<UserTask>>) {
+        for thread in threads {
+            thread.futex_exit();
+            thread.exit_wq.wake_all();
+            // The main thread stands for the process until it is reaped.
+            match thread.task_id == thread.process_id {
+                true => cancel_task(thread.task_id),
+                false => thread.release(),
+            }
+            self.pcb.lock().exited_times += thread.thread_times();
+        }
+        self.pcb
+            .lock()
+            .threads
+            .retain(|x| x.upgrade().map_or(false, |x| x.task_id == self.task_id));
+    }
+
+    /// Replace the program of this process in place.
+    ///
+    /// The pid, the parent, the cwd and the fd table are kept, fds opened
+    /// with `O_CLOEXEC` are closed and the other threads are stopped.
+    /// Fails, leaving the process as it was, if `path` cannot be loaded.
+    pub fn exec(self: &Arc<Self>, path: Path, args: Vec<String>, envp: Vec<String>) -> Result<(), TaskError> {
+        let mut load_elf_return: LoadElfReturn = load_elf_frame(path.clone(), self.initial_stack_size())?;
+        // Another thread is exiting or execing the process, this one is
+        // stopped by it.
+        if !self.kill_other_threads() {
+            load_elf_return.release();
+            return Err(TaskError::EINTR);
+        }
+        // A set-user-ID or set-group-ID program runs as its owner, unless an
+        // unprivileged tracer would get hold of it that way.
+        let traced = self.tracer().is_some_and(|tracer| !tracer.cred.read().privileged());
+        let attr = File::open(&path.to_string(), OpenFlags::O_RDONLY).and_then(|file| file.inner.getattr());
+        let mut cred = self.cred.read().clone();
+        if let Some(attr) = attr.ok().filter(|_| !traced) {
+            let setuid = (attr.mode & S_ISUID != 0).then_some(attr.uid);
+            // Set-group-ID without group execute marks mandatory locking.
+            let setgid = (attr.mode & S_ISGID != 0 && attr.mode & 0o010 != 0).then_some(attr.gid);
+            cred.exec_setid(setuid, setgid);
+        }
+        // The arguments go on the new stack while the old program can still
+        // get the error.
+        let filename = path.to_string();
+        let mut stack_region = load_elf_return.stack_region.clone();
+        let auxv = match UserTask::init_task_stack(&mut stack_region, &args, &envp, &filename, &cred, &load_elf_return) {
+            Ok(auxv) => auxv,
+            Err(err) => {
+                load_elf_return.release();
+                return Err(err);
+            }
+        };
+        *self.cred.write() = cred;
+        info!("exec load_elf_return: {:?}", load_elf_return);
+        let pagetable = UserTask::map_elf(&mut load_elf_return);
+        let old_ranges = self.pcb.lock().user_ranges(&self.tcb.read().stack_region);
+
+        {
+            let mut pcb = self.pcb.lock();
+            pcb.mem_set = load_elf_return.memset.clone();
+            pcb.heap = HeapUser::new(VirtAddrRange::new(
+                VirtAddr::from_usize(load_elf_return.heap_bottom),
+                VirtAddr::from_usize(load_elf_return.heap_bottom),
+            ));
+            pcb.entry = load_elf_return.entry_point;
+            pcb.auxv = auxv;
+            pcb.shms.clear();
+            // The setitimer timers survive exec, POSIX timers don't.
+            pcb.posix_timers.values_mut().for_each(IntervalTimer::wake_driver);
+            pcb.posix_timers.clear();
+            pcb.fd_table.close_on_exec();
+            pcb.killing_threads = None;
+            // The handlers are gone with the old program, ignored signals
+            // stay ignored.
+            for action in pcb.sig_actions.iter_mut() {
+                if action.handler != SIG_IGN {
+                    *action = SigAction::default();
+                }
+            }
+        }
+        {
+            let mut tcb = self.tcb.write();
+            tcb.cx = UserTask::init_cx(load_elf_return.clone());
+            tcb.cx[TrapFrameArgs::SP] = stack_region.get_sp();
+            tcb.stack_region = stack_region;
+            tcb.clear_child_tid = None;
+            tcb.robust_list = 0;
+            tcb.syscall_restart = None;
+            // They were in the old program.
+            tcb.trace.breakpoints.clear();
+        }
+        // The old address space is gone from here on.
+        let mut page_table = self.page_table.lock();
+        for (range, _) in old_ranges {
+            page_table.unshare(range);
+        }
+        *page_table = pagetable;
+        page_table.change_pagetable();
+        drop(page_table);
+        Ok(())
+    }
 }
 
 impl AsyncTask for UserTask {
     fn before_run(&self) {
         self.page_table.lock().change_pagetable();
+        // Every time the task is picked it gets a fresh time slice.
+        let mut tcb = self.tcb.write();
+        tcb.slice_used = Duration::ZERO;
+        // Time spent off the CPU is not charged.
+        tcb.time_stamp = get_time();
+    }
+
+    fn after_run(&self) {
+        let now = get_time();
+        let mut tcb = self.tcb.write();
+        let delta = now.saturating_sub(tcb.time_stamp);
+        tcb.times.stime += delta;
+        tcb.time_stamp = now;
     }
 
     fn get_task_id(&self) -> TaskId {
@@ -515,9 +1107,15 @@ impl AsyncTask for UserTask {
     fn exit_code(&self) -> Option<usize> {
         self.tcb.read().thread_exit_code
     }
+
+    fn sched(&self) -> &Mutex<SchedEntity> {
+        &self.sched
+    }
 }
 
-pub fn add_user_task(filename: &str, args: Vec<&str>, envp: Vec<&str>) -> TaskId {
+/// Start the program `filename` as a new process, scripts run with their
+/// interpreter.
+pub fn add_user_task(filename: &str, args: Vec<&str>, envp: Vec<&str>) -> Result<TaskId, TaskError> {
     info!("Adding user task: {}", filename);
     let parent = get_cur_usr_task();
     if let Some(p) = &parent {
@@ -529,35 +1127,28 @@ pub fn add_user_task(filename: &str, args: Vec<&str>, envp: Vec<&str>) -> TaskId
     let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
     let envp: Vec<String> = envp.iter().map(|s| s.to_string()).collect();
 
-    info!("Creating task from file: {}", filename);
-    let (task, load_elf_return) = UserTask::new_from_file(None, Path::new(filename.to_owned()))
-        .expect("Failed to create task from file");
+    let (path, args) = resolve_exec(Path::new(filename.to_owned()), args)?;
+    info!("Creating task from file: {}", path.to_string());
+    let (task, load_elf_return) = UserTask::new_from_file(None, path.clone())?;
 
     info!(
         "Initializing task stack with {} args and {} env vars",
         args.len(),
         envp.len()
     );
-    let mut filename = filename.to_owned();
-    if !filename.starts_with('/') {
-        let mut path = "/".to_owned();
-        path.push_str(&filename);
-        filename = path;
-    }
-    UserTask::init_task_stack(
-        &task,
-        args,
-        load_elf_return.base,
-        filename.as_str(),
-        load_elf_return.entry_point,
-        load_elf_return.ph_count,
-        load_elf_return.ph_entry_size,
-        load_elf_return.ph_addr,
-        load_elf_return.heap_bottom,
-    );
+    let cred = task.cred.read().clone();
+    let auxv = {
+        let mut tcb = task.tcb.write();
+        let stack_region = &mut tcb.stack_region;
+        let auxv = UserTask::init_task_stack(stack_region, &args, &envp, &path.to_string(), &cred, &load_elf_return)?;
+        tcb.cx[TrapFrameArgs::SP] = tcb.stack_region.get_sp();
+        auxv
+    };
+    task.pcb.lock().auxv = auxv;
     if let Some(p) = &parent {
         p.before_run();
     }
+    claim_console(task.process_id.0);
     let task_id = task.get_task_id();
     info!("New task created with ID: {:?}", task_id);
 
@@ -570,5 +1161,5 @@ pub fn add_user_task(filename: &str, args: Vec<&str>, envp: Vec<&str>) -> TaskId
     info!("User task {:?} added successfully", task_id);
 
     // test_addr_load
-    task_id
+    Ok(task_id)
 }
@@ -4,7 +4,8 @@ use arch::os_shut_down;
 use console::println;
 use core::panic::PanicInfo;
 use core::sync::atomic::{AtomicBool, Ordering};
-use device::init_dt;
+use config::target::plat::MAX_CPUS;
+use device::{bootargs, hart_ids, init_dt};
 use filesystem::file::File;
 use filesystem::init_fs;
 use filesystem::file::OpenFlags;
@@ -24,6 +25,9 @@ use crate::executor::executor::{GLOBLE_EXECUTOR, info_task_queue, spawn_blank};
 use crate::executor::initproc::initproc;
 use boot::boot_page_table;
 pub mod backtrace;
+pub mod cmdline;
+pub mod random;
+use cmdline::Cmdline;
 use backtrace::backtrace;
 
 #[panic_handler]
@@ -51,17 +55,35 @@ pub extern "C" fn kernel_main(hartid: usize, dtb: usize) -> ! {
     heap::init();
     trap::trap::init();
     init_dt(dtb);
-    init_fs();
+    let cmdline = Cmdline::parse(&bootargs());
+    if let Some(level) = cmdline.loglevel {
+        console::set_log_level(level);
+    }
+    info!("cmdline: {:?}", cmdline);
+    let hart_ids = hart_ids();
+    let cpu_num = hart_ids.iter().max().map_or(1, |id| id + 1).min(MAX_CPUS);
+    arch::set_cpu_num(cpu_num);
+    info!("hart ids: {:?}, cpu num: {}", hart_ids, cpu_num);
+    init_fs(cmdline.root);
 
     info!("\n\n\n\n\n\n");
     // test_ls();
-    spawn_blank(initproc());
+    spawn_blank(initproc(cmdline));
     info_task_queue();
+    boot::start_secondary_harts(hartid, &hart_ids);
     GLOBLE_EXECUTOR.run();
     info!("kernel_end");
     arch::os_shut_down();
 }
 
+#[unsafe(no_mangle)]
+pub extern "C" fn kernel_secondary_main(hartid: usize) -> ! {
+    trap::trap::init();
+    info!("hart {} started", hartid);
+    GLOBLE_EXECUTOR.run();
+    arch::os_shut_down();
+}
+
 pub fn test_ls() {
     let file = File::open(&"/".to_string(), OpenFlags::O_DIRECTORY | OpenFlags::O_RDWR).unwrap();
     let mut buffer = Vec::<DirEntry>::new();
@@ -0,0 +1,28 @@
+//! Kernel random numbers, for `AT_RANDOM` and the like.
+//!
+//! A splitmix64 generator that mixes the time of every draw into its state,
+//! so the values differ across boots and calls. It is not meant for
+//! cryptography.
+
+use spin::Mutex;
+use timer::get_time;
+
+static STATE: Mutex<u64> = Mutex::new(0x9e37_79b9_7f4a_7c15);
+
+fn next_u64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Fill `buf` with random bytes.
+pub fn fill_random(buf: &mut [u8]) {
+    let mut state = STATE.lock();
+    *state ^= get_time().as_nanos() as u64;
+    for chunk in buf.chunks_mut(8) {
+        let bytes = next_u64(&mut state).to_ne_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
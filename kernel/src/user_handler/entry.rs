@@ -26,11 +26,8 @@ impl UserHandler {
         loop {
             self.check_timer();
 
-            let res = self.handle_syscall(cx_ref).await;
-
-            if let UserTaskControlFlow::Break = res {
-                break;
-            }
+            // Signals are delivered on the way back to user mode.
+            self.handle_signals(cx_ref).await;
 
             if let Some(exit_code) = self.check_thread_exit() {
                 debug!(
@@ -40,6 +37,12 @@ impl UserHandler {
                 );
                 break;
             }
+
+            let res = self.handle_syscall(cx_ref).await;
+
+            if let UserTaskControlFlow::Break = res {
+                break;
+            }
             
             // yield_now().await;
         }
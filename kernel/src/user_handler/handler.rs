@@ -2,9 +2,12 @@ use crate::executor::thread::UserTask;
 use crate::executor::id_alloc::TaskId;
 use crate::executor::task::AsyncTask;
 
+use crate::executor::executor::need_resched;
+use crate::executor::ops::yield_now;
+use crate::executor::sched::SchedPolicy;
 use alloc::sync::Arc;
-use timer::get_time;
-use log::info;
+use config::target::plat::TIME_SLICE_MS;
+use core::time::Duration;
 
 pub enum UserTaskControlFlow {
     Continue,
@@ -23,15 +26,24 @@ impl UserHandler {
             .or(self.task.tcb.read().thread_exit_code.map(|x| x as usize))
     }
 
-    pub fn check_timer(&self) {
-        let pcb = self.task.pcb.lock();
-        if let Some(timeout) = pcb.time {
-            let now = get_time();
-            if now >= timeout {
-                info!("timer expired");
-                loop{};
-            }
+    /// Yield to the executor once the task has used up its time slice, or
+    /// when a real-time task with a higher priority is waiting.
+    ///
+    /// `SCHED_FIFO` tasks have no time slice.
+    pub async fn check_time_slice(&self) {
+        let used = self.task.tcb.read().slice_used;
+        let entity = self.task.sched.lock().clone();
+        let expired = entity.policy != SchedPolicy::Fifo
+            && used >= Duration::from_millis(TIME_SLICE_MS);
+        if expired || need_resched(&entity) {
+            yield_now().await;
         }
     }
-}
 
+    /// Act on the CPU time used so far: fire the timers on the CPU clocks and
+    /// enforce `RLIMIT_CPU`. Wall clock timers fire by themselves.
+    pub fn check_timer(&self) {
+        self.task.check_cpu_timers();
+        self.task.check_cpu_limit();
+    }
+}
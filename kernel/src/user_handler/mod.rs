@@ -1,5 +1,7 @@
 pub mod handler;
 pub mod entry;
+pub mod signal;
+pub mod ptrace;
 
 
 pub mod syscall;
@@ -0,0 +1,70 @@
+//! Stops of a traced thread.
+
+use super::handler::UserHandler;
+use crate::executor::ptrace::{PTRACE_O_TRACESYSGOOD, TraceMode, TraceStop, TraceWait};
+use log::debug;
+use struct_define::signal::*;
+
+impl UserHandler {
+    /// Stop for the tracer with `sig` until it resumes the thread. Returns
+    /// the signal it resumed with, `None` if the process isn't traced.
+    pub async fn trace_stop(&self, sig: i32, info: SigInfo) -> Option<usize> {
+        let tracer = self.task.tracer()?;
+        debug!("[task {:?}] trace stop, signal {:#x}", self.tid, sig);
+        self.task.tcb.write().trace.stop = Some(TraceStop {
+            sig,
+            info,
+            reported: false,
+        });
+        let uid = self.task.cred.read().uid;
+        tracer.signal_process(SigInfo::child(CLD_TRAPPED, tracer.pid_of(self.tid), uid, sig));
+        // The tracer waits in `wait4` on the main thread.
+        if let Some(leader) = self.task.leader() {
+            leader.exit_wq.wake_all();
+        }
+        TraceWait(self.task.clone()).await;
+        // The tracer may have moved the pc, a step starts over from there.
+        self.task.remove_step_breakpoints();
+        let mut tcb = self.task.tcb.write();
+        tcb.trace.stop = None;
+        Some(core::mem::take(&mut tcb.trace.resume_signal))
+    }
+
+    /// Signal-delivery stop of `info`. Returns the signal to deliver in its
+    /// place, `None` if the tracer suppressed it.
+    pub async fn trace_signal(&self, info: SigInfo) -> Option<SigInfo> {
+        let signo = info.signo as usize;
+        if signo == SIGKILL {
+            return Some(info);
+        }
+        match self.trace_stop(signo as i32, info).await {
+            None => Some(info),
+            Some(0) => None,
+            Some(resumed) if resumed == signo => Some(info),
+            Some(resumed) => Some(SigInfo::new(resumed, SI_USER)),
+        }
+    }
+
+    /// System call entry or exit stop, if `PTRACE_SYSCALL` asked for it.
+    pub async fn trace_syscall(&self) {
+        if self.task.tcb.read().trace.mode != TraceMode::Syscall || self.check_thread_exit().is_some() {
+            return;
+        }
+        let sig = match self.task.trace_options() & PTRACE_O_TRACESYSGOOD != 0 {
+            true => SIGTRAP as i32 | 0x80,
+            false => SIGTRAP as i32,
+        };
+        let resumed = self.trace_stop(sig, SigInfo::new(SIGTRAP, SI_KERNEL)).await;
+        if let Some(signo) = resumed.filter(|x| *x != 0) {
+            self.task.signal_thread(SigInfo::new(signo, SI_USER));
+        }
+    }
+
+    /// A single step ended in a system call, which completes the step.
+    pub fn trace_step_syscall(&self) {
+        if self.task.tcb.read().trace.mode == TraceMode::Step && self.task.remove_step_breakpoints() {
+            let pc = self.task.tcb.read().cx.sepc;
+            self.task.force_signal(SigInfo::fault(SIGTRAP, TRAP_TRACE, pc));
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! Signal delivery on the way back to user mode.
+
+use super::handler::UserHandler;
+use super::syscall::sysnum;
+use crate::executor::signal::{DefaultAction, UNBLOCKABLE};
+use crate::executor::sync::{Interruptible, WaitContinue};
+use crate::user_handler::userbuf::UserBuf;
+use config::target::plat::{PAGE_SIZE, SIGRETURN_TRAMPOLINE};
+use core::future::Future;
+use core::mem::size_of;
+use log::debug;
+use memory_addr::VirtAddr;
+use page_table_multiarch::MappingFlags;
+use struct_define::signal::*;
+use trap::trap::TrapType;
+use trap::trapframe::{TrapFrame, TrapFrameArgs};
+
+/// Whether `sysno` starts over after a signal that didn't run a handler, or
+/// ran one with `SA_RESTART`. Sleeps and waits with a timeout report `EINTR`.
+pub fn restartable(sysno: usize) -> bool {
+    !matches!(
+        sysno,
+        sysnum::SYS_NANOSLEEP
+            | sysnum::SYS_CLOCK_NANOSLEEP
+            | sysnum::SYS_PPOLL
+            | sysnum::SYS_SIGTIMEDWAIT
+    )
+}
+
+/// Point `cx` back at the `ecall` of an interrupted system call.
+fn restart_syscall(cx: &mut TrapFrame, a0: usize) {
+    cx.sepc -= 4;
+    cx[TrapFrameArgs::RET] = a0;
+}
+
+impl UserHandler {
+    /// Fail `future` with `EINTR` once a signal is pending, for system calls
+    /// that may block for long.
+    pub fn interruptible<F: Future>(&self, future: F) -> Interruptible<F> {
+        Interruptible {
+            task: self.task.clone(),
+            future,
+        }
+    }
+
+    /// Whether `[start, end)` is mapped to user pages.
+    pub fn user_range_mapped(&self, start: usize, end: usize) -> bool {
+        let page_table = self.task.page_table.lock();
+        (start & !(PAGE_SIZE - 1)..end)
+            .step_by(PAGE_SIZE)
+            .all(|vaddr| {
+                page_table
+                    .query_page(VirtAddr::from_usize(vaddr))
+                    .is_some_and(|(_, flags)| flags.contains(MappingFlags::USER))
+            })
+    }
+
+    /// Turn a fault of the user code into a signal for the thread. RISC-V
+    /// doesn't trap on division by zero, so nothing raises `SIGFPE` here.
+    pub fn handle_user_fault(&self, trap: TrapType, cx: &TrapFrame) {
+        let stepped = self.task.remove_step_breakpoints();
+        let info = match trap {
+            // A write to a page shared by fork just copies it.
+            TrapType::StorePageFault(addr)
+                if self.task.handle_cow_fault(VirtAddr::from_usize(addr)) =>
+            {
+                return;
+            }
+            // The main stack grows down on demand, up to RLIMIT_STACK.
+            TrapType::StorePageFault(addr) | TrapType::LoadPageFault(addr)
+                if !self.user_range_mapped(addr, addr + 1) && self.task.grow_stack(addr) =>
+            {
+                return;
+            }
+            TrapType::StorePageFault(addr)
+            | TrapType::LoadPageFault(addr)
+            | TrapType::InstructionPageFault(addr) => {
+                let mapped = self.user_range_mapped(addr, addr + 1);
+                let code = match mapped {
+                    true => SEGV_ACCERR,
+                    false => SEGV_MAPERR,
+                };
+                SigInfo::fault(SIGSEGV, code, addr)
+            }
+            TrapType::AccessFault(addr) => SigInfo::fault(SIGBUS, BUS_ADRERR, addr),
+            TrapType::MisalignedAccess(addr) => SigInfo::fault(SIGBUS, BUS_ADRALN, addr),
+            TrapType::IllegalInstruction(_) => SigInfo::fault(SIGILL, ILL_ILLOPC, cx.sepc),
+            // A single step ran into one of its breakpoints.
+            TrapType::Breakpoint if stepped => SigInfo::fault(SIGTRAP, TRAP_TRACE, cx.sepc),
+            // Another thread is being stepped, the instruction runs again.
+            TrapType::Breakpoint if self.task.sibling_step_trap(cx.sepc) => return,
+            TrapType::Breakpoint => SigInfo::fault(SIGTRAP, TRAP_BRKPT, cx.sepc),
+            _ => return,
+        };
+        debug!(
+            "[task {:?}] {:?} at pc {:#x}, signal {}",
+            self.tid, trap, cx.sepc, info.signo
+        );
+        self.task.force_signal(info);
+    }
+
+    /// Act on the pending signals before returning to user mode.
+    ///
+    /// Default actions run here. A handled signal gets a frame on the user
+    /// stack and `cx` is pointed at its handler, the other pending signals
+    /// wait for the next return to user mode.
+    pub async fn handle_signals(&self, cx: &mut TrapFrame) {
+        let mut restart = self.task.tcb.write().syscall_restart.take();
+        loop {
+            if self.task.pcb.lock().stopped.is_some() {
+                WaitContinue(self.task.clone()).await;
+            }
+            if self.check_thread_exit().is_some() {
+                return;
+            }
+            let mask = self.task.tcb.read().sig_mask;
+            let Some(info) = self.task.dequeue_signal(SigSet(!mask.0)) else {
+                break;
+            };
+            // A tracer sees the signal first, and may change or drop it.
+            let Some(info) = self.trace_signal(info).await else {
+                continue;
+            };
+            if self.check_thread_exit().is_some() {
+                return;
+            }
+            let signo = info.signo as usize;
+            let action = self.task.pcb.lock().sig_actions[signo];
+            debug!("[task {:?}] signal {} handler: {:#x}", self.tid, signo, action.handler);
+            match action.handler {
+                SIG_IGN => continue,
+                SIG_DFL => match DefaultAction::of(signo) {
+                    DefaultAction::Ignore | DefaultAction::Continue => continue,
+                    DefaultAction::Stop => self.task.stop(signo),
+                    DefaultAction::Terminate => {
+                        self.task.exit_by_signal(signo);
+                        return;
+                    }
+                    DefaultAction::Core => {
+                        self.task.exit_with_core(signo);
+                        return;
+                    }
+                },
+                _ => {
+                    // Without `SA_RESTART` the call keeps its `EINTR`.
+                    if let Some(a0) = restart.take() {
+                        if action.flags.contains(SigActionFlags::RESTART) {
+                            restart_syscall(cx, a0);
+                        }
+                    }
+                    self.setup_frame(cx, info, action);
+                    return;
+                }
+            }
+        }
+        if let Some(a0) = restart {
+            restart_syscall(cx, a0);
+        }
+    }
+
+    /// Push the `siginfo_t` and the `ucontext_t` of the interrupted context
+    /// and enter the handler, which returns to the sigreturn trampoline.
+    fn setup_frame(&self, cx: &mut TrapFrame, info: SigInfo, action: SigAction) {
+        let signo = info.signo as usize;
+        let sp = cx[TrapFrameArgs::SP];
+        let uc_ptr = sp.wrapping_sub(size_of::<UContext>()) & !0xf;
+        let info_ptr = uc_ptr.wrapping_sub(size_of::<SigInfo>()) & !0xf;
+        if info_ptr > sp || !self.user_range_mapped(info_ptr, sp) {
+            // No room for the frame, the process can't go on.
+            self.task.exit_by_signal(SIGSEGV);
+            return;
+        }
+
+        let mut tcb = self.task.tcb.write();
+        let mut gregs = cx.x;
+        gregs[0] = cx.sepc;
+        UserBuf::new(uc_ptr as *mut UContext).write(UContext::new(tcb.sig_mask, gregs));
+        UserBuf::new(info_ptr as *mut SigInfo).write(info);
+
+        let mut mask = tcb.sig_mask.union(action.mask);
+        if !action.flags.contains(SigActionFlags::NODEFER) {
+            mask.insert(signo);
+        }
+        tcb.sig_mask = mask.difference(UNBLOCKABLE);
+        drop(tcb);
+        if action.flags.contains(SigActionFlags::RESETHAND) {
+            self.task.pcb.lock().sig_actions[signo] = SigAction::default();
+        }
+
+        cx.sepc = action.handler;
+        cx[TrapFrameArgs::RA] = SIGRETURN_TRAMPOLINE;
+        cx[TrapFrameArgs::SP] = info_ptr;
+        cx[TrapFrameArgs::ARG0] = signo;
+        cx[TrapFrameArgs::ARG1] = info_ptr;
+        cx[TrapFrameArgs::ARG2] = uc_ptr;
+    }
+}
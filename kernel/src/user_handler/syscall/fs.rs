@@ -1,9 +1,13 @@
 use crate::executor::error::TaskError;
-use crate::executor::ops::yield_now;
+use crate::executor::sync::{PollFiles, PollWait};
+use crate::executor::id_alloc::TaskId;
 use filesystem::vfs::VfsError;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 use struct_define::fd::FcntlCmd;
+use struct_define::poll_event::{PollEvent, PollFd};
+use struct_define::timespec::TimeSpec;
+use timer::{TimedOut, timeout};
 use crate::user_handler::handler::UserHandler;
 use crate::user_handler::userbuf::UserBuf;
 use alloc::string::ToString;
@@ -11,16 +15,29 @@ use alloc::sync::{self, Arc};
 use alloc::vec::Vec;
 
 use filesystem::devfs::{DevFsDirInode, DevType};
+use filesystem::devfs::uart::UartDev;
+use struct_define::signal::SIGTTIN;
 use filesystem::file::OpenFlags;
 use filesystem::file::{File, Stat};
 use filesystem::mount::{mount_inode, umount_fs};
 use filesystem::path::{self, Path};
+use filesystem::perm::{Access, check_access_with};
 use filesystem::pipe::create_pipe;
 use filesystem::vfs::{DirEntry, FileType};
 use log::debug;
 
 use memory_addr::VirtAddr;
 const AT_FDCWD: isize = -100;
+/// `faccessat` flag to check with the effective ids.
+const AT_EACCESS: usize = 0x200;
+/// `faccessat` mode asking only for existence.
+const F_OK: usize = 0;
+/// `FD_CLOEXEC` of `F_GETFD`/`F_SETFD`.
+const FD_CLOEXEC: usize = 1;
+/// Terminal `ioctl`s for job control.
+const TIOCGPGRP: u32 = 0x540f;
+const TIOCSPGRP: u32 = 0x5410;
+const TIOCGSID: u32 = 0x5429;
 
 impl UserHandler {
     pub async fn sys_write(
@@ -44,11 +61,11 @@ impl UserHandler {
         &mut self,
         dirfd: isize,
         path_str: &str,
-        _mode: usize,
+        mode: usize,
     ) -> Result<usize, TaskError> {
         debug!(
-            "sys_mkdirat @ dirfd: {}, path: {}, mode: {}",
-            dirfd, path_str, _mode
+            "sys_mkdirat @ dirfd: {}, path: {}, mode: {:#o}",
+            dirfd, path_str, mode
         );
 
         let task = self.task.clone();
@@ -59,7 +76,7 @@ impl UserHandler {
         } else {
             cwd = self.task.get_fd(dirfd as usize).expect("invalid dirfd");
         }
-        cwd.mkdir_at(path_str)?;
+        cwd.mkdir_at(path_str, mode as u32)?;
         //test_ls();
         Ok(0)
     }
@@ -130,23 +147,36 @@ impl UserHandler {
             filename
         };
 
-        let file = cwd.open_at(&filename, flags)?;
-        let fd = self.task.pcb.lock().fd_table.alloc(file);
+        let file = cwd.open_at_mode(&filename, flags, mode)?;
+        let fd = self.task.pcb.lock().fd_table.alloc(file)?;
         // test_ls();
         Ok(fd as isize)
     }
 
-    pub async fn sys_dup3(&self, fd_src: usize, fd_dst: usize) -> Result<usize, TaskError> {
-        debug!("sys_dup3 @ fd_src: {}, fd_dst: {}", fd_src, fd_dst);
-        let file = self.task.get_fd(fd_src).ok_or(TaskError::EBADF)?;
-        self.task.pcb.lock().fd_table.set(fd_dst, file);
+    pub async fn sys_dup3(
+        &self,
+        fd_src: usize,
+        fd_dst: usize,
+        flags: usize,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_dup3 @ fd_src: {}, fd_dst: {}, flags: {:#x}", fd_src, fd_dst, flags);
+        let flags = OpenFlags::from_bits_truncate(flags);
+        let mut file = self.task.get_fd(fd_src).ok_or(TaskError::EBADF)?;
+        // Close-on-exec belongs to the fd, the new one only has it if asked.
+        file.openflags.set(OpenFlags::O_CLOEXEC, flags.contains(OpenFlags::O_CLOEXEC));
+        let mut pcb = self.task.pcb.lock();
+        if fd_dst >= pcb.fd_table.limit {
+            return Err(TaskError::EBADF);
+        }
+        pcb.fd_table.set(fd_dst, file);
         Ok(fd_dst)
     }
 
     pub async fn sys_dup(&self, fd: usize) -> Result<usize, TaskError> {
         debug!("sys_dup @ fd: {}", fd);
-        let file = self.task.get_fd(fd).ok_or(TaskError::EBADF)?;
-        let fd_dst = self.task.pcb.lock().fd_table.alloc(file);
+        let mut file = self.task.get_fd(fd).ok_or(TaskError::EBADF)?;
+        file.openflags.remove(OpenFlags::O_CLOEXEC);
+        let fd_dst = self.task.pcb.lock().fd_table.alloc(file)?;
         Ok(fd_dst)
     }
 
@@ -249,12 +279,19 @@ impl UserHandler {
         count: usize,
     ) -> Result<usize, TaskError> {
         let mut file = self.task.get_fd(fd).ok_or(TaskError::EBADF)?;
+        if file.inner.is::<UartDev>() {
+            self.task.tty_job_check(SIGTTIN)?;
+        }
         let mut buffer = unsafe { core::slice::from_raw_parts_mut(buf_ptr.ptr, count) };
         loop {
             match file.read(&mut buffer) {
                 Ok(read_len) => return Ok(read_len),
                 Err(VfsError::Again) => {
-                    yield_now().await;
+                    self.interruptible(PollWait {
+                        inode: file.inner.clone(),
+                        events: PollEvent::IN,
+                    })
+                    .await?;
                     continue;
                 }
                 Err(err) => return Err(err.into()),
@@ -262,19 +299,87 @@ impl UserHandler {
         }
     }
 
+    pub async fn sys_ppoll(
+        &self,
+        fds_ptr: UserBuf<PollFd>,
+        nfds: usize,
+        timeout_ptr: UserBuf<TimeSpec>,
+        _sigmask: usize,
+    ) -> Result<usize, TaskError> {
+        debug!(
+            "sys_ppoll @ fds_ptr: {}, nfds: {}, timeout_ptr: {}",
+            fds_ptr, nfds, timeout_ptr
+        );
+        let poll_fds = fds_ptr.slice_mut_with_len(nfds);
+        let mut indices = Vec::new();
+        let mut targets = Vec::new();
+        let mut invalid = 0;
+        for (index, poll_fd) in poll_fds.iter_mut().enumerate() {
+            poll_fd.revents = PollEvent::NONE;
+            // Negative fds are ignored.
+            if (poll_fd.fd as i32) < 0 {
+                continue;
+            }
+            match self.task.get_fd(poll_fd.fd as usize) {
+                Some(file) => {
+                    indices.push(index);
+                    targets.push((file.inner.clone(), poll_fd.events.clone()));
+                }
+                None => {
+                    poll_fd.revents = PollEvent::NVAL;
+                    invalid += 1;
+                }
+            }
+        }
+
+        let future = PollFiles(targets);
+        let revents = match (invalid, timeout_ptr.is_valid()) {
+            // An invalid fd is an event, don't wait.
+            (1.., _) => future.poll_now(),
+            (0, false) => self.interruptible(future).await?,
+            (0, true) => {
+                let timeout_dur = timeout_ptr.read().to_duration().ok_or(TaskError::EINVAL)?;
+                match timeout(self.interruptible(future), timeout_dur).await {
+                    Ok(revents) => revents?,
+                    Err(TimedOut) => return Ok(0),
+                }
+            }
+        };
+        let mut ready = invalid;
+        for (index, events) in indices.into_iter().zip(revents) {
+            if !events.is_empty() {
+                ready += 1;
+            }
+            poll_fds[index].revents = events;
+        }
+        Ok(ready)
+    }
+
     pub async fn sys_pipe2(
         &self,
         fds_ptr: UserBuf<u32>,
-        _unknown: usize,
+        flags: usize,
     ) -> Result<usize, TaskError> {
-        debug!("sys_pipe2 @ fds_ptr: {}, _unknown: {}", fds_ptr, _unknown);
+        debug!("sys_pipe2 @ fds_ptr: {}, flags: {:#x}", fds_ptr, flags);
         let fds = fds_ptr.slice_mut_with_len(2);
+        let cloexec = OpenFlags::from_bits_truncate(flags).contains(OpenFlags::O_CLOEXEC);
 
         let (rx, tx) = create_pipe();
-        let rx_file = File::new_dev(rx);
-        let tx_file = File::new_dev(tx);
-        let rx_fd = self.task.pcb.lock().fd_table.alloc(rx_file);
-        let tx_fd = self.task.pcb.lock().fd_table.alloc(tx_file);
+        let mut rx_file = File::new_dev(rx);
+        let mut tx_file = File::new_dev(tx);
+        rx_file.openflags.set(OpenFlags::O_CLOEXEC, cloexec);
+        tx_file.openflags.set(OpenFlags::O_CLOEXEC, cloexec);
+        let (rx_fd, tx_fd) = {
+            let mut pcb = self.task.pcb.lock();
+            let rx_fd = pcb.fd_table.alloc(rx_file)?;
+            match pcb.fd_table.alloc(tx_file) {
+                Ok(tx_fd) => (rx_fd, tx_fd),
+                Err(e) => {
+                    pcb.fd_table.remove(rx_fd);
+                    return Err(e.into());
+                }
+            }
+        };
         fds[0] = rx_fd as u32;
         fds[1] = tx_fd as u32;
         // );
@@ -355,27 +460,73 @@ impl UserHandler {
         Ok(0)
     }
 
+    /// Check that the caller may access `pathname` as `mode` asks. The real
+    /// ids are used unless `AT_EACCESS` is given.
     pub async fn sys_faccessat(&self, dirfd: isize, pathname: UserBuf<u8>, mode: usize, flags: usize) -> Result<usize, TaskError>
     {
-        // let cwd;
-        // if dirfd == -100
-        // {
-        //     cwd = File::open(self.task.pcb.lock().curr_dir.to_string().as_str(),OpenFlags::O_DIRECTORY | OpenFlags::O_RDWR)?;
-        // } else {
-        //     cwd = self.task.get_fd(dirfd as usize).expect("invalid dirfd");
-        // }
-        // let inode = cwd.open_relative(pathname.read_string().as_str(), OpenFlags::from_bits_truncate(mode))?;
+        let path = pathname.read_string();
+        debug!("sys_faccessat @ dirfd: {}, path: {}, mode: {:#o}, flags: {:#x}", dirfd, path, mode, flags);
+        if mode & !0o7 != 0 {
+            return Err(TaskError::EINVAL);
+        }
+        let file = if path.starts_with('/') {
+            File::open(&path, OpenFlags::O_PATH)?
+        } else {
+            let cwd = if dirfd == AT_FDCWD {
+                File::open(
+                    &self.task.pcb.lock().curr_dir.to_string(),
+                    OpenFlags::O_DIRECTORY | OpenFlags::O_PATH,
+                )?
+            } else {
+                self.task.get_fd(dirfd as usize).ok_or(TaskError::EBADF)?
+            };
+            cwd.open_at(&path, OpenFlags::O_PATH)?
+        };
+        // F_OK only asks whether the file is there.
+        if mode == F_OK {
+            return Ok(0);
+        }
+        let cred = match flags & AT_EACCESS != 0 {
+            true => self.task.cred.read().fs_cred(),
+            false => self.task.cred.read().real_cred(),
+        };
+        check_access_with(&cred, &file.inner, Access::from_bits_truncate(mode as u32))?;
         Ok(0)
     }
 
     pub async fn sys_ioctl(
         &self,
-        _fd: u32,          // 文件描述符
-        _cmd: u32,         // 控制命令（如 Linux 的 `TIOCGWINSZ` 获取终端大小）
-        _arg: usize,       // 可选参数（可能是用户态缓冲区地址或直接值）
+        fd: u32,          // 文件描述符
+        cmd: u32,         // 控制命令（如 Linux 的 `TIOCGWINSZ` 获取终端大小）
+        arg: usize,       // 可选参数（可能是用户态缓冲区地址或直接值）
     ) -> Result<usize, TaskError>
     {
-        Ok(0)
+        debug!("sys_ioctl @ fd: {}, cmd: {:#x}, arg: {:#x}", fd, cmd, arg);
+        let file = self.task.get_fd(fd as usize).ok_or(TaskError::EBADF)?;
+        let is_tty = file.inner.is::<UartDev>();
+        match cmd {
+            TIOCGPGRP | TIOCSPGRP | TIOCGSID if !is_tty => Err(TaskError::ENOTTY),
+            TIOCGPGRP => {
+                let pgrp = self.task.pid_of(TaskId(self.task.console_foreground()?));
+                UserBuf::new(arg as *mut i32).write(pgrp as i32);
+                Ok(0)
+            }
+            TIOCSPGRP => {
+                let pgrp = UserBuf::new(arg as *mut i32).read();
+                if pgrp < 0 {
+                    return Err(TaskError::EINVAL);
+                }
+                let pgrp = self.task.pid_ns().task_id(pgrp as usize).ok_or(TaskError::EPERM)?;
+                self.task.set_console_foreground(pgrp.0)?;
+                Ok(0)
+            }
+            TIOCGSID => {
+                let sid = self.task.pid_of(TaskId(self.task.console_session()?));
+                UserBuf::new(arg as *mut i32).write(sid as i32);
+                Ok(0)
+            }
+            _ => Ok(0),
+        }
     }
 
     pub async fn sys_fstatat(&self, dirfd: isize, pathname: UserBuf<u8>, statbuf: UserBuf<u8>,flags: usize) -> Result<usize, TaskError>
@@ -408,20 +559,25 @@ impl UserHandler {
             "[task {:?}] fcntl: fd: {}, cmd: {:#x}, arg: {}",
             self.tid, fd, cmd, arg
         );
-        // let cmd = FromPrimitive::from_usize(cmd).ok_or(TaskError::EINVAL)?;
-        // let mut file = self.task.get_fd(fd).ok_or(TaskError::EBADF)?;
-        // debug!("[task {:?}] fcntl: {:?}", self.tid, cmd);
-        // match cmd {
-        //     FcntlCmd::DUPFD | FcntlCmd::DUPFDCLOEXEC => self.sys_dup(fd).await,
-        //     FcntlCmd::GETFD => Ok(1),
-        //     FcntlCmd::GETFL => Ok(file.openflags.bits()),
-        //     FcntlCmd::SETFL => {
-        //         file.openflags = OpenFlags::from_bits_truncate(arg);
-        //         self.task.pcb.lock().fd_table.set(fd, file);
-        //         Ok(0)
-        //     }
-        //     _ => Ok(0),
-        // }
-        Ok(0)
+        let cmd = FcntlCmd::from_usize(cmd).ok_or(TaskError::EINVAL)?;
+        let mut file = self.task.get_fd(fd).ok_or(TaskError::EBADF)?;
+        match cmd {
+            FcntlCmd::DUPFD | FcntlCmd::DUPFDCLOEXEC => {
+                file.openflags.set(OpenFlags::O_CLOEXEC, cmd == FcntlCmd::DUPFDCLOEXEC);
+                let mut pcb = self.task.pcb.lock();
+                if arg >= pcb.fd_table.limit {
+                    return Err(TaskError::EINVAL);
+                }
+                Ok(pcb.fd_table.alloc_from(arg.max(3), file)?)
+            }
+            FcntlCmd::GETFD => Ok(file.openflags.contains(OpenFlags::O_CLOEXEC) as usize),
+            FcntlCmd::SETFD => {
+                file.openflags.set(OpenFlags::O_CLOEXEC, arg & FD_CLOEXEC != 0);
+                self.task.pcb.lock().fd_table.set(fd, file);
+                Ok(0)
+            }
+            FcntlCmd::GETFL => Ok((file.openflags - OpenFlags::O_CLOEXEC).bits()),
+            _ => Ok(0),
+        }
     }
 }
@@ -0,0 +1,207 @@
+use crate::executor::error::TaskError;
+use crate::executor::futex::{FUTEX_BITSET_MATCH_ANY, FUTEX_TABLE, FutexKey, futex_key};
+use crate::executor::thread::UserTask;
+use crate::user_handler::handler::UserHandler;
+use crate::user_handler::userbuf::UserBuf;
+use alloc::sync::Arc;
+use core::mem::size_of;
+use log::debug;
+use num_traits::FromPrimitive;
+use struct_define::fd::{FutexFlags, RobustListHead};
+use struct_define::timespec::TimeSpec;
+use timer::{get_time, timeout_at};
+
+const FUTEX_PRIVATE_FLAG: usize = 128;
+const FUTEX_CLOCK_REALTIME: usize = 256;
+const FUTEX_CMD_MASK: usize = !(FUTEX_PRIVATE_FLAG | FUTEX_CLOCK_REALTIME);
+
+/// Sign extend the 12 bit fields of a `FUTEX_WAKE_OP` argument.
+fn sign_extend_12(value: u32) -> i32 {
+    ((value << 20) as i32) >> 20
+}
+
+/// Apply the operation encoded in `encoded` to `old`, returns the new value.
+fn futex_op(old: u32, encoded: u32) -> Result<u32, TaskError> {
+    let op = (encoded >> 28) & 0xf;
+    let mut oparg = sign_extend_12((encoded >> 12) & 0xfff) as u32;
+    // FUTEX_OP_OPARG_SHIFT
+    if op & 8 != 0 {
+        oparg = 1u32.checked_shl(oparg).unwrap_or(0);
+    }
+    match op & 7 {
+        0 => Ok(oparg),
+        1 => Ok(old.wrapping_add(oparg)),
+        2 => Ok(old | oparg),
+        3 => Ok(old & !oparg),
+        4 => Ok(old ^ oparg),
+        _ => Err(TaskError::ENOSYS),
+    }
+}
+
+/// Compare `old` as encoded in `encoded`.
+fn futex_cmp(old: u32, encoded: u32) -> Result<bool, TaskError> {
+    let old = old as i32;
+    let cmparg = sign_extend_12(encoded & 0xfff);
+    match (encoded >> 24) & 0xf {
+        0 => Ok(old == cmparg),
+        1 => Ok(old != cmparg),
+        2 => Ok(old < cmparg),
+        3 => Ok(old <= cmparg),
+        4 => Ok(old > cmparg),
+        5 => Ok(old >= cmparg),
+        _ => Err(TaskError::ENOSYS),
+    }
+}
+
+impl UserHandler {
+    fn futex_key(&self, uaddr: usize, private: bool) -> Result<FutexKey, TaskError> {
+        if uaddr % size_of::<u32>() != 0 {
+            return Err(TaskError::EINVAL);
+        }
+        futex_key(&self.task, uaddr, private).ok_or(TaskError::EFAULT)
+    }
+
+    /// Sleep on `uaddr` while it holds `val`. `deadline` is time since boot.
+    async fn futex_wait(
+        &self,
+        uaddr: usize,
+        private: bool,
+        val: u32,
+        deadline: Option<core::time::Duration>,
+        bitset: u32,
+    ) -> Result<usize, TaskError> {
+        let key = self.futex_key(uaddr, private)?;
+        let wait = {
+            let mut table = FUTEX_TABLE.lock();
+            if UserBuf::new(uaddr as *mut u32).read() != val {
+                return Err(TaskError::EAGAIN);
+            }
+            table.wait(key, bitset)
+        };
+        let wait = self.interruptible(wait);
+        match deadline {
+            Some(deadline) => timeout_at(wait, deadline)
+                .await
+                .map_err(|_| TaskError::ETIMEDOUT)??,
+            None => wait.await?,
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_futex(
+        &self,
+        uaddr: usize,
+        op: usize,
+        val: usize,
+        timeout: usize,
+        uaddr2: usize,
+        val3: usize,
+    ) -> Result<usize, TaskError> {
+        debug!(
+            "sys_futex @ uaddr: {:#x}, op: {:#x}, val: {:#x}, timeout: {:#x}, uaddr2: {:#x}, val3: {:#x}",
+            uaddr, op, val, timeout, uaddr2, val3
+        );
+        let private = op & FUTEX_PRIVATE_FLAG != 0;
+        let cmd = FutexFlags::from_usize(op & FUTEX_CMD_MASK).ok_or(TaskError::ENOSYS)?;
+        let val3 = val3 as u32;
+        // `timeout` is the second count of the requeue operations.
+        let val2 = timeout;
+        let timeout = UserBuf::<TimeSpec>::new(timeout as *mut TimeSpec);
+        match cmd {
+            FutexFlags::Wait => {
+                let deadline = match timeout.is_valid() {
+                    true => {
+                        let duration = timeout.read().to_duration().ok_or(TaskError::EINVAL)?;
+                        Some(get_time().saturating_add(duration))
+                    }
+                    false => None,
+                };
+                self.futex_wait(uaddr, private, val as u32, deadline, FUTEX_BITSET_MATCH_ANY)
+                    .await
+            }
+            FutexFlags::WaitBitset => {
+                if val3 == 0 {
+                    return Err(TaskError::EINVAL);
+                }
+                // Absolute, the realtime and monotonic clocks are the same here.
+                let deadline = match timeout.is_valid() {
+                    true => Some(timeout.read().to_duration().ok_or(TaskError::EINVAL)?),
+                    false => None,
+                };
+                self.futex_wait(uaddr, private, val as u32, deadline, val3).await
+            }
+            FutexFlags::Wake => {
+                let key = self.futex_key(uaddr, private)?;
+                Ok(FUTEX_TABLE.lock().wake(key, val, FUTEX_BITSET_MATCH_ANY))
+            }
+            FutexFlags::WakeBitset => {
+                if val3 == 0 {
+                    return Err(TaskError::EINVAL);
+                }
+                let key = self.futex_key(uaddr, private)?;
+                Ok(FUTEX_TABLE.lock().wake(key, val, val3))
+            }
+            FutexFlags::Requeue | FutexFlags::CmpRequeue => {
+                let from = self.futex_key(uaddr, private)?;
+                let to = self.futex_key(uaddr2, private)?;
+                let mut table = FUTEX_TABLE.lock();
+                let cmp = matches!(cmd, FutexFlags::CmpRequeue);
+                if cmp && UserBuf::new(uaddr as *mut u32).read() != val3 {
+                    return Err(TaskError::EAGAIN);
+                }
+                Ok(table.requeue(from, to, val, val2))
+            }
+            FutexFlags::WakeOp => {
+                if uaddr2 % size_of::<u32>() != 0 {
+                    return Err(TaskError::EINVAL);
+                }
+                // A bad `uaddr2` is an error, not a fault in the kernel.
+                if !self.user_range_mapped(uaddr2, uaddr2 + size_of::<u32>()) {
+                    return Err(TaskError::EFAULT);
+                }
+                let key = self.futex_key(uaddr, private)?;
+                let mut table = FUTEX_TABLE.lock();
+                let old = UserBuf::new(uaddr2 as *mut u32).read();
+                if !self.task.write_user_u32(uaddr2, futex_op(old, val3)?) {
+                    return Err(TaskError::EFAULT);
+                }
+                // The write may have copied the page, take the key after it.
+                let key2 = self.futex_key(uaddr2, private)?;
+                let mut woken = table.wake(key, val, FUTEX_BITSET_MATCH_ANY);
+                if futex_cmp(old, val3)? {
+                    woken += table.wake(key2, val2, FUTEX_BITSET_MATCH_ANY);
+                }
+                Ok(woken)
+            }
+            _ => Err(TaskError::ENOSYS),
+        }
+    }
+
+    pub async fn sys_set_robust_list(&self, head: usize, len: usize) -> Result<usize, TaskError> {
+        debug!("sys_set_robust_list @ head: {:#x}, len: {}", head, len);
+        if len != size_of::<RobustListHead>() {
+            return Err(TaskError::EINVAL);
+        }
+        self.task.tcb.write().robust_list = head;
+        Ok(0)
+    }
+
+    pub async fn sys_get_robust_list(
+        &self,
+        pid: usize,
+        head_ptr: UserBuf<usize>,
+        len_ptr: UserBuf<usize>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_get_robust_list @ pid: {}, head_ptr: {}, len_ptr: {}", pid, head_ptr, len_ptr);
+        let task: Arc<UserTask> = match pid {
+            0 => self.task.clone(),
+            pid => self.task.task_by_pid(pid).ok_or(TaskError::ESRCH)?,
+        };
+        if !head_ptr.is_valid() || !len_ptr.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        head_ptr.write(task.tcb.read().robust_list);
+        len_ptr.write(size_of::<RobustListHead>());
+        Ok(0)
+    }
+}
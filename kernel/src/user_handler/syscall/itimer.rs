@@ -0,0 +1,211 @@
+use crate::executor::error::TaskError;
+use crate::executor::itimer::{IntervalTimer, TimerClock, TimerKey, TimerNotify};
+use crate::executor::signal::valid_signal;
+use crate::user_handler::handler::UserHandler;
+use crate::user_handler::userbuf::UserBuf;
+use core::time::Duration;
+use log::debug;
+use struct_define::signal::{SIGALRM, SIGEV_NONE, SIGEV_SIGNAL, SIGEV_THREAD_ID, SigEvent};
+use struct_define::timespec::{ITimerSpec, ITimerVal, TimeVal};
+
+/// `flags` of `timer_settime`.
+const TIMER_ABSTIME: usize = 1;
+
+/// An armed timer never reads back as 0, which would look disarmed.
+fn to_timeval(left: Duration, armed: bool) -> TimeVal {
+    match armed && left < Duration::from_micros(1) {
+        true => Duration::from_micros(1).into(),
+        false => left.into(),
+    }
+}
+
+impl UserHandler {
+    /// The clock of a timer on `clock_id`, CPU clocks only of the caller.
+    fn timer_clock(&self, clock_id: usize) -> Result<TimerClock, TaskError> {
+        match clock_id as i32 {
+            // CLOCK_REALTIME, CLOCK_MONOTONIC, CLOCK_BOOTTIME and the alarm
+            // clocks all run on the time since boot.
+            0 | 1 | 7 | 8 | 9 => Ok(TimerClock::Real),
+            // CLOCK_PROCESS_CPUTIME_ID
+            2 => Ok(TimerClock::Prof),
+            // A process clock of `clock_getcpuclockid`, see `clock_gettime`.
+            id if id < 0 && id & 4 == 0 => {
+                let pid = !(id >> 3) as usize;
+                if pid != 0 && pid != self.task.pid_of(self.task.process_id) {
+                    return Err(TaskError::EINVAL);
+                }
+                match id & 3 {
+                    1 => Ok(TimerClock::Virtual),
+                    0 | 2 => Ok(TimerClock::Prof),
+                    _ => Err(TaskError::EINVAL),
+                }
+            }
+            _ => Err(TaskError::EINVAL),
+        }
+    }
+
+    pub async fn sys_getitimer(&self, which: usize, curr_value: UserBuf<ITimerVal>) -> Result<usize, TaskError> {
+        debug!("sys_getitimer @ which: {}", which);
+        if which > 2 {
+            return Err(TaskError::EINVAL);
+        }
+        if !curr_value.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let (left, interval) = self.task.get_timer(TimerKey::ITimer(which))?;
+        let armed = self.task.pcb.lock().itimers[which].expires.is_some();
+        curr_value.write(ITimerVal {
+            interval: interval.into(),
+            value: to_timeval(left, armed),
+        });
+        Ok(0)
+    }
+
+    /// `alarm` is this on `ITIMER_REAL`.
+    pub async fn sys_setitimer(
+        &self,
+        which: usize,
+        new_value: UserBuf<ITimerVal>,
+        old_value: UserBuf<ITimerVal>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_setitimer @ which: {}", which);
+        if which > 2 {
+            return Err(TaskError::EINVAL);
+        }
+        if !new_value.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let new = new_value.read();
+        let value = new.value.to_duration().ok_or(TaskError::EINVAL)?;
+        let interval = new.interval.to_duration().ok_or(TaskError::EINVAL)?;
+        let key = TimerKey::ITimer(which);
+        let armed = self.task.pcb.lock().itimers[which].expires.is_some();
+        let (left, old_interval) = self.task.set_timer(key, value, interval, false)?;
+        if old_value.is_valid() {
+            old_value.write(ITimerVal {
+                interval: old_interval.into(),
+                value: to_timeval(left, armed),
+            });
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_timer_create(
+        &self,
+        clock_id: usize,
+        sevp: UserBuf<SigEvent>,
+        timerid: UserBuf<i32>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_timer_create @ clock_id: {}", clock_id as i32);
+        let clock = self.timer_clock(clock_id)?;
+        if !timerid.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let event = match sevp.is_valid() {
+            true => Some(sevp.read()),
+            false => None,
+        };
+        let notify = match event {
+            // No sigevent is SIGEV_SIGNAL with SIGALRM, the value is the
+            // timer id filled in below.
+            None => Some(TimerNotify {
+                signo: SIGALRM,
+                tid: None,
+                value: 0,
+            }),
+            Some(event) if event.notify == SIGEV_NONE => None,
+            Some(event) => {
+                let signo = event.signo as usize;
+                if !valid_signal(signo) {
+                    return Err(TaskError::EINVAL);
+                }
+                let tid = match event.notify {
+                    SIGEV_SIGNAL => None,
+                    SIGEV_THREAD_ID => {
+                        let tid = self.task.pid_ns().task_id(event.tid as usize).ok_or(TaskError::EINVAL)?;
+                        let ours = self.task.threads().iter().any(|thread| thread.task_id == tid);
+                        if !ours {
+                            return Err(TaskError::EINVAL);
+                        }
+                        Some(tid)
+                    }
+                    // SIGEV_THREAD is done by libc on top of SIGEV_THREAD_ID.
+                    _ => return Err(TaskError::EINVAL),
+                };
+                Some(TimerNotify {
+                    signo,
+                    tid,
+                    value: event.value,
+                })
+            }
+        };
+        let id = {
+            let mut pcb = self.task.pcb.lock();
+            let id = pcb.add_posix_timer(IntervalTimer::new(clock, notify))?;
+            if event.is_none() {
+                let timer = pcb.timer_mut(TimerKey::Posix(id)).unwrap();
+                timer.notify = timer.notify.map(|x| TimerNotify { value: id, ..x });
+            }
+            id
+        };
+        timerid.write(id as i32);
+        Ok(0)
+    }
+
+    pub async fn sys_timer_settime(
+        &self,
+        timerid: usize,
+        flags: usize,
+        new_value: UserBuf<ITimerSpec>,
+        old_value: UserBuf<ITimerSpec>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_timer_settime @ timerid: {}, flags: {:#x}", timerid, flags);
+        if !new_value.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let new = new_value.read();
+        let value = new.value.to_duration().ok_or(TaskError::EINVAL)?;
+        let interval = new.interval.to_duration().ok_or(TaskError::EINVAL)?;
+        let key = TimerKey::Posix(timerid);
+        let absolute = flags & TIMER_ABSTIME != 0;
+        let (left, old_interval) = self.task.set_timer(key, value, interval, absolute)?;
+        if old_value.is_valid() {
+            old_value.write(ITimerSpec {
+                interval: old_interval.into(),
+                value: left.into(),
+            });
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_timer_gettime(&self, timerid: usize, curr_value: UserBuf<ITimerSpec>) -> Result<usize, TaskError> {
+        debug!("sys_timer_gettime @ timerid: {}", timerid);
+        let (left, interval) = self.task.get_timer(TimerKey::Posix(timerid))?;
+        if !curr_value.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        curr_value.write(ITimerSpec {
+            interval: interval.into(),
+            value: left.into(),
+        });
+        Ok(0)
+    }
+
+    pub async fn sys_timer_getoverrun(&self, timerid: usize) -> Result<usize, TaskError> {
+        self.task.timer_overrun(timerid)
+    }
+
+    /// The kernel task of an armed timer is woken, notices it is gone and
+    /// stops.
+    pub async fn sys_timer_delete(&self, timerid: usize) -> Result<usize, TaskError> {
+        debug!("sys_timer_delete @ timerid: {}", timerid);
+        self.task
+            .pcb
+            .lock()
+            .posix_timers
+            .remove(&timerid)
+            .ok_or(TaskError::EINVAL)?
+            .wake_driver();
+        Ok(0)
+    }
+}
@@ -11,6 +11,12 @@ use crate::executor::task::AsyncTask;
 use memory_addr::align_up;
 use memory_addr::VirtAddr;
 use page_table_multiarch::MappingFlags;
+use struct_define::resource::RLIMIT_DATA;
+
+/// `MAP_SHARED` of `mmap`.
+const MAP_SHARED: usize = 0x01;
+/// `PROT_WRITE` of `mmap`.
+const PROT_WRITE: usize = 0x2;
 
 impl UserHandler {
     pub async fn sys_brk(&mut self, addr: usize) -> Result<usize, TaskError> {
@@ -19,8 +25,18 @@ impl UserHandler {
             let res = self.task.get_heap().get_top();
             return Ok(res);
         }
-        self.task.get_heap().brk(addr, &mut self.task.page_table.lock());
-        let res = self.task.get_heap().get_top();
+        let mut heap = self.task.get_heap();
+        // A brk that can't be done leaves the break where it was.
+        let grow = addr.saturating_sub(heap.get_top());
+        if addr < heap.get_bottom()
+            || self.task.rlimit(RLIMIT_DATA).exceeded_by((addr - heap.get_bottom()) as u64)
+            || !self.task.may_grow(grow)
+        {
+            return Ok(heap.get_top());
+        }
+        heap.brk(addr, &mut self.task.page_table.lock());
+        self.task.set_heap(heap);
+        let res = heap.get_top();
         Ok(res)
     }
 
@@ -41,6 +57,13 @@ impl UserHandler {
         let file_size = file.get_file_size()?;
         let map_len = if len == 0 { file_size } else { len };
         let aligned_len = align_up(map_len, PAGE_SIZE);
+        if !self.task.may_grow(aligned_len) {
+            return Err(TaskError::ENOMEM);
+        }
+        let private_writable = flags & MAP_SHARED == 0 && prot & PROT_WRITE != 0;
+        if private_writable && !self.task.may_grow_data(aligned_len) {
+            return Err(TaskError::ENOMEM);
+        }
         let frame_tracers = alloc_continues(aligned_len / PAGE_SIZE);
         let start_vaddr = if addr == 0 {
             let start = self.task.pcb.lock().mem_set.find_free_area(aligned_len);
@@ -55,6 +78,10 @@ impl UserHandler {
         file.read_at(offset, buffer)?;
         let start_paddr = frame_tracers[0].paddr;
         let end_paddr = start_paddr + aligned_len;
+        let region_type = match flags & MAP_SHARED != 0 {
+            true => MemRegionType::SHARED,
+            false => MemRegionType::MMAP,
+        };
         let mut mem_region = MemRegion::new_mapped(
             start_vaddr.into(),
             end_vaddr.into(),
@@ -62,7 +89,7 @@ impl UserHandler {
             end_paddr,
             MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER,
             "mmap".to_string(),
-            MemRegionType::MMAP,
+            region_type,
         );
         self.task
             .page_table
@@ -9,6 +9,7 @@ use trap::trapframe::{TrapFrame, TrapFrameArgs};
 pub mod sysnum;
 use crate::executor::error::TaskError;
 use crate::executor::task::AsyncTask;
+use crate::executor::ptrace::TraceMode;
 use crate::user_handler::syscall::other::TimeVal;
 use crate::user_handler::syscall::sysnum::sysnum_to_string;
 use crate::user_handler::userbuf::UserBuf;
@@ -19,13 +20,41 @@ pub mod fs;
 pub mod mem;
 pub mod proc;
 pub mod other;
-use struct_define::timespec::TimeSpec;
+pub mod sched;
+pub mod futex;
+pub mod signal;
+pub mod itimer;
+pub mod ptrace;
+pub mod ns;
+use struct_define::timespec::{ITimerSpec, ITimerVal, TimeSpec};
+use struct_define::sched::SchedParam;
+use struct_define::poll_event::PollFd;
+use struct_define::rusage::RUsage;
+use struct_define::resource::RLimit;
+use struct_define::signal::{SigAction, SigEvent, SigInfo, SigSet};
+use crate::user_handler::signal::restartable;
 
 impl UserHandler {
     pub async fn handle_syscall(&mut self, cx_ref: &mut TrapFrame) -> UserTaskControlFlow {
         //debug!("TrapFrame: {:?}", cx_ref);
 
-        if matches!(run_user_task(cx_ref), EscapeReason::SysCall) {
+        if self.task.tcb.read().trace.mode == TraceMode::Step {
+            self.task.insert_step_breakpoints();
+        }
+        self.task.enter_user();
+        let reason = run_user_task(cx_ref);
+        let user_time = self.task.leave_user();
+        self.task.tcb.write().slice_used += user_time;
+
+        if matches!(reason, EscapeReason::Timer) {
+            self.check_time_slice().await;
+        }
+
+        if let EscapeReason::Fault(trap) = reason {
+            self.handle_user_fault(trap, cx_ref);
+        }
+
+        if matches!(reason, EscapeReason::SysCall) {
             info!(
                 "[task {:?}] syscall: {} at sepc: {:#x}",
                 self.task.get_task_id(),
@@ -34,11 +63,16 @@ impl UserHandler {
             );
 
             cx_ref.syscall_ok();
-            let result = self
-                .syscall(cx_ref.get_sysno(), cx_ref.args())
-                .await
-                .map_or_else(|e| -e.into_raw() as isize, |x| x as isize)
-                as usize;
+            // The tracer may change the call at its entry stop.
+            self.trace_syscall().await;
+            let sysno = cx_ref.get_sysno();
+            let args = cx_ref.args();
+            let result = self.syscall(sysno, args).await;
+            if result == Err(TaskError::EINTR) && restartable(sysno) {
+                // Signal delivery decides whether it starts over.
+                self.task.tcb.write().syscall_restart = Some(args[0]);
+            }
+            let result = result.map_or_else(|e| -e.into_raw() as isize, |x| x as isize) as usize;
 
             info!(
                 "[task {:?}] syscall result: {} \n\n",
@@ -47,6 +81,8 @@ impl UserHandler {
             );
 
             cx_ref[TrapFrameArgs::RET] = result;
+            self.trace_syscall().await;
+            self.trace_step_syscall();
         }
         UserTaskControlFlow::Continue
     }
@@ -90,7 +126,8 @@ impl UserHandler {
             sysnum::SYS_DUP3 => {
                 let oldfd = _args[0];
                 let newfd = _args[1];
-                self.sys_dup3(oldfd, newfd).await
+                let flags = _args[2];
+                self.sys_dup3(oldfd, newfd, flags).await
             }
             sysnum::SYS_CLONE => {
                 let flags = _args[0];
@@ -103,8 +140,17 @@ impl UserHandler {
             sysnum::SYS_WAIT4 => {
                 let pid = _args[0] as isize;
                 let status = UserBuf::new(_args[1] as *mut i32);
-                let options = _args[2];
-                self.sys_wait4(pid, status, options).await
+                // An int, `__WCLONE` must not get sign extended.
+                let options = _args[2] as u32 as usize;
+                let rusage = UserBuf::new(_args[3] as *mut RUsage);
+                self.sys_wait4(pid, status, options, rusage).await
+            }
+            sysnum::SYS_WAITID => {
+                let id = _args[1] as i32 as isize;
+                let infop = UserBuf::new(_args[2] as *mut SigInfo);
+                let options = _args[3] as u32 as usize;
+                let rusage = UserBuf::new(_args[4] as *mut RUsage);
+                self.sys_waitid(_args[0], id, infop, options, rusage).await
             }
             sysnum::SYS_EXECVE => {
                 let filename = UserBuf::new(_args[0] as *mut u8);
@@ -127,9 +173,17 @@ impl UserHandler {
             sysnum::SYS_GETPID => {
                 self.sys_getpid().await
             }
+            sysnum::SYS_GETTID => self.sys_gettid().await,
             sysnum::SYS_GETPPID => {
                 self.sys_getppid().await
             }
+            sysnum::SYS_SETPGID => self.sys_setpgid(_args[0] as isize, _args[1] as isize).await,
+            sysnum::SYS_GETPGID => match _args[0] {
+                0 => self.sys_getpgrp().await,
+                pid => self.sys_getpgid(pid as isize).await,
+            },
+            sysnum::SYS_SETSID => self.sys_setsid().await,
+            sysnum::SYS_GETSID => self.sys_getsid(_args[0] as isize).await,
             sysnum::SYS_GETTIMEOFDAY => {
                 let tv_ptr = UserBuf::new(_args[0] as *mut TimeVal);
                 let timezone_ptr = _args[1];
@@ -169,14 +223,73 @@ impl UserHandler {
             }
             sysnum::SYS_PIPE2 => {
                 let fds_ptr = UserBuf::new(_args[0] as *mut u32);
-                let unknown = _args[1];
-                self.sys_pipe2(fds_ptr, unknown).await
+                let flags = _args[1];
+                self.sys_pipe2(fds_ptr, flags).await
             }
             sysnum::SYS_NANOSLEEP => {
                 let req = UserBuf::new(_args[0] as *mut TimeSpec);
                 let _rem = UserBuf::new(_args[1] as *mut TimeSpec);
                 self.sys_nanosleep(req, _rem).await
             }
+            sysnum::SYS_CLOCK_NANOSLEEP => {
+                let clock_id = _args[0];
+                let flags = _args[1];
+                let req = UserBuf::new(_args[2] as *mut TimeSpec);
+                let rem = UserBuf::new(_args[3] as *mut TimeSpec);
+                self.sys_clock_nanosleep(clock_id, flags, req, rem).await
+            }
+            sysnum::SYS_PTRACE => self.sys_ptrace(_args[0], _args[1] as u32 as usize, _args[2], _args[3]).await,
+            sysnum::SYS_GETITIMER => {
+                let curr_value = UserBuf::new(_args[1] as *mut ITimerVal);
+                self.sys_getitimer(_args[0] as u32 as usize, curr_value).await
+            }
+            sysnum::SYS_SETITIMER => {
+                let new_value = UserBuf::new(_args[1] as *mut ITimerVal);
+                let old_value = UserBuf::new(_args[2] as *mut ITimerVal);
+                self.sys_setitimer(_args[0] as u32 as usize, new_value, old_value).await
+            }
+            sysnum::SYS_TIMER_CREATE => {
+                let sevp = UserBuf::new(_args[1] as *mut SigEvent);
+                let timerid = UserBuf::new(_args[2] as *mut i32);
+                self.sys_timer_create(_args[0], sevp, timerid).await
+            }
+            sysnum::SYS_TIMER_SETTIME => {
+                let new_value = UserBuf::new(_args[2] as *mut ITimerSpec);
+                let old_value = UserBuf::new(_args[3] as *mut ITimerSpec);
+                self.sys_timer_settime(_args[0] as u32 as usize, _args[1], new_value, old_value).await
+            }
+            sysnum::SYS_TIMER_GETTIME => {
+                let curr_value = UserBuf::new(_args[1] as *mut ITimerSpec);
+                self.sys_timer_gettime(_args[0] as u32 as usize, curr_value).await
+            }
+            sysnum::SYS_TIMER_GETOVERRUN => self.sys_timer_getoverrun(_args[0] as u32 as usize).await,
+            sysnum::SYS_TIMER_DELETE => self.sys_timer_delete(_args[0] as u32 as usize).await,
+            sysnum::SYS_PPOLL => {
+                let fds = UserBuf::new(_args[0] as *mut PollFd);
+                let nfds = _args[1];
+                let timeout = UserBuf::new(_args[2] as *mut TimeSpec);
+                let sigmask = _args[3];
+                self.sys_ppoll(fds, nfds, timeout, sigmask).await
+            }
+            sysnum::SYS_PRLIMIT64 => {
+                let new_limit = UserBuf::new(_args[2] as *mut RLimit);
+                let old_limit = UserBuf::new(_args[3] as *mut RLimit);
+                self.sys_prlimit64(_args[0] as i32 as isize, _args[1] as u32 as usize, new_limit, old_limit).await
+            }
+            sysnum::SYS_GETRLIMIT => {
+                let limit = UserBuf::new(_args[1] as *mut RLimit);
+                self.sys_getrlimit(_args[0] as u32 as usize, limit).await
+            }
+            sysnum::SYS_SETRLIMIT => {
+                let limit = UserBuf::new(_args[1] as *mut RLimit);
+                self.sys_setrlimit(_args[0] as u32 as usize, limit).await
+            }
+            sysnum::SYS_GETRUSAGE => {
+                let who = _args[0] as isize;
+                let usage = UserBuf::new(_args[1] as *mut RUsage);
+                self.sys_getrusage(who, usage).await
+            }
+            sysnum::SYS_UMASK => self.sys_umask(_args[0] as u32).await,
             sysnum::SYS_TIMES => {
                 let tms_ptr = UserBuf::new(_args[0] as *mut TMS);
                 self.sys_times(tms_ptr).await
@@ -206,8 +319,94 @@ impl UserHandler {
                 self.sys_ioctl(fd, request, argp).await
             }
             sysnum::SYS_GETUID => self.sys_getuid().await,
+            sysnum::SYS_GETEUID => self.sys_geteuid().await,
+            sysnum::SYS_GETGID => self.sys_getgid().await,
+            sysnum::SYS_GETEGID => self.sys_getegid().await,
+            sysnum::SYS_SETUID => self.sys_setuid(_args[0] as u32).await,
+            sysnum::SYS_SETGID => self.sys_setgid(_args[0] as u32).await,
+            sysnum::SYS_SETREUID => self.sys_setreuid(_args[0] as u32, _args[1] as u32).await,
+            sysnum::SYS_SETREGID => self.sys_setregid(_args[0] as u32, _args[1] as u32).await,
+            sysnum::SYS_SETRESUID => {
+                self.sys_setresuid(_args[0] as u32, _args[1] as u32, _args[2] as u32).await
+            }
+            sysnum::SYS_SETRESGID => {
+                self.sys_setresgid(_args[0] as u32, _args[1] as u32, _args[2] as u32).await
+            }
+            sysnum::SYS_GETRESUID => {
+                let ruid = UserBuf::new(_args[0] as *mut u32);
+                let euid = UserBuf::new(_args[1] as *mut u32);
+                let suid = UserBuf::new(_args[2] as *mut u32);
+                self.sys_getresuid(ruid, euid, suid).await
+            }
+            sysnum::SYS_GETRESGID => {
+                let rgid = UserBuf::new(_args[0] as *mut u32);
+                let egid = UserBuf::new(_args[1] as *mut u32);
+                let sgid = UserBuf::new(_args[2] as *mut u32);
+                self.sys_getresgid(rgid, egid, sgid).await
+            }
+            sysnum::SYS_GETGROUPS => {
+                let list = UserBuf::new(_args[1] as *mut u32);
+                self.sys_getgroups(_args[0] as u32 as usize, list).await
+            }
+            sysnum::SYS_SETGROUPS => {
+                let list = UserBuf::new(_args[1] as *mut u32);
+                self.sys_setgroups(_args[0] as u32 as usize, list).await
+            }
             sysnum::SYS_UNAME => self.sys_uname(UserBuf::new(_args[0] as *mut UTSname)).await,
+            sysnum::SYS_SETHOSTNAME => self.sys_sethostname(UserBuf::new(_args[0] as *mut u8), _args[1]).await,
+            sysnum::SYS_SETDOMAINNAME => self.sys_setdomainname(UserBuf::new(_args[0] as *mut u8), _args[1]).await,
+            sysnum::SYS_UNSHARE => self.sys_unshare(_args[0] as u32 as usize).await,
+            sysnum::SYS_SETNS => self.sys_setns(_args[0] as u32 as usize, _args[1] as u32 as usize).await,
+            sysnum::SYS_PIDFD_OPEN => self.sys_pidfd_open(_args[0] as u32 as usize, _args[1] as u32 as usize).await,
             sysnum::SYS_SCHED_YIELD => self.sys_sched_yield().await,
+            sysnum::SYS_SCHED_SETSCHEDULER => {
+                let pid = _args[0] as isize;
+                let policy = _args[1];
+                let param = UserBuf::new(_args[2] as *mut SchedParam);
+                self.sys_sched_setscheduler(pid, policy, param).await
+            }
+            sysnum::SYS_SCHED_GETSCHEDULER => self.sys_sched_getscheduler(_args[0] as isize).await,
+            sysnum::SYS_SCHED_SETPARAM => {
+                let pid = _args[0] as isize;
+                let param = UserBuf::new(_args[1] as *mut SchedParam);
+                self.sys_sched_setparam(pid, param).await
+            }
+            sysnum::SYS_SCHED_GETPARAM => {
+                let pid = _args[0] as isize;
+                let param = UserBuf::new(_args[1] as *mut SchedParam);
+                self.sys_sched_getparam(pid, param).await
+            }
+            sysnum::SYS_SCHED_GET_PRIORITY_MAX => self.sys_sched_get_priority_max(_args[0]).await,
+            sysnum::SYS_SCHED_GET_PRIORITY_MIN => self.sys_sched_get_priority_min(_args[0]).await,
+            sysnum::SYS_SETPRIORITY => {
+                let which = _args[0];
+                let who = _args[1] as isize;
+                let prio = _args[2] as isize;
+                self.sys_setpriority(which, who, prio).await
+            }
+            sysnum::SYS_GETPRIORITY => self.sys_getpriority(_args[0], _args[1] as isize).await,
+            sysnum::SYS_SCHED_SETAFFINITY => {
+                let pid = _args[0] as isize;
+                let len = _args[1];
+                let mask = UserBuf::new(_args[2] as *mut u8);
+                self.sys_sched_setaffinity(pid, len, mask).await
+            }
+            sysnum::SYS_SCHED_GETAFFINITY => {
+                let pid = _args[0] as isize;
+                let len = _args[1];
+                let mask = UserBuf::new(_args[2] as *mut u8);
+                self.sys_sched_getaffinity(pid, len, mask).await
+            }
+            sysnum::SYS_FUTEX => {
+                self.sys_futex(_args[0], _args[1], _args[2], _args[3], _args[4], _args[5])
+                    .await
+            }
+            sysnum::SYS_SET_ROBUST_LIST => self.sys_set_robust_list(_args[0], _args[1]).await,
+            sysnum::SYS_GET_ROBUST_LIST => {
+                let head_ptr = UserBuf::new(_args[1] as *mut usize);
+                let len_ptr = UserBuf::new(_args[2] as *mut usize);
+                self.sys_get_robust_list(_args[0], head_ptr, len_ptr).await
+            }
             sysnum::SYS_SET_TID_ADDRESS => self.sys_set_tid_address(UserBuf::new(_args[0] as *mut u32)).await,
             sysnum::SYS_FSTATAT => {
                 let dir_fd = _args[0] as isize;
@@ -216,6 +415,26 @@ impl UserHandler {
                 let flags = _args[3];
                 self.sys_fstatat(dir_fd, path, statbuf, flags).await
             }
+            sysnum::SYS_SIGACTION => {
+                let act = UserBuf::new(_args[1] as *mut SigAction);
+                let oldact = UserBuf::new(_args[2] as *mut SigAction);
+                self.sys_rt_sigaction(_args[0], act, oldact, _args[3]).await
+            }
+            sysnum::SYS_SIGPROCMASK => {
+                let set = UserBuf::new(_args[1] as *mut SigSet);
+                let oldset = UserBuf::new(_args[2] as *mut SigSet);
+                self.sys_rt_sigprocmask(_args[0], set, oldset, _args[3]).await
+            }
+            sysnum::SYS_KILL => self.sys_kill(_args[0] as isize, _args[1]).await,
+            sysnum::SYS_TKILL => self.sys_tkill(_args[0], _args[1]).await,
+            sysnum::SYS_TGKILL => self.sys_tgkill(_args[0], _args[1], _args[2]).await,
+            sysnum::SYS_SIGTIMEDWAIT => {
+                let set = UserBuf::new(_args[0] as *mut SigSet);
+                let info = UserBuf::new(_args[1] as *mut SigInfo);
+                let timeout = UserBuf::new(_args[2] as *mut TimeSpec);
+                self.sys_rt_sigtimedwait(set, info, timeout, _args[3]).await
+            }
+            sysnum::SYS_SIGRETURN => self.sys_rt_sigreturn().await,
             sysnum::SYS_EXIT_GROUP => {
                 let exit_code = _args[0];
                 self.sys_exit_group(exit_code).await
@@ -0,0 +1,129 @@
+use crate::executor::error::TaskError;
+use crate::executor::namespace::{HOST_NAME_MAX, NS_FLAGS, Namespaces, PidFd};
+use crate::executor::task::CloneFlags;
+use crate::user_handler::handler::UserHandler;
+use crate::user_handler::userbuf::UserBuf;
+use alloc::string::String;
+use alloc::sync::Arc;
+use filesystem::file::{File, OpenFlags};
+use log::debug;
+
+impl UserHandler {
+    /// Threads share the namespaces, the fd table and the rest of what
+    /// `unshare` and `setns` could split off, so only a process running on
+    /// one thread may change them.
+    fn check_single_thread(&self) -> Result<(), TaskError> {
+        match self.task.threads().len() > 1 {
+            true => Err(TaskError::EINVAL),
+            false => Ok(()),
+        }
+    }
+
+    pub async fn sys_unshare(&self, flags: usize) -> Result<usize, TaskError> {
+        debug!("sys_unshare @ flags: {:#x}", flags);
+        let allowed = NS_FLAGS
+            | CloneFlags::NEWUSER
+            | CloneFlags::NEWNET
+            | CloneFlags::NEWCGROUP
+            | CloneFlags::FILES
+            | CloneFlags::FS
+            | CloneFlags::SYSVSEM
+            | CloneFlags::THREAD
+            | CloneFlags::SIGHAND
+            | CloneFlags::VM;
+        let flags = CloneFlags::from_bits(flags)
+            .filter(|x| allowed.contains(*x))
+            .ok_or(TaskError::EINVAL)?;
+        Namespaces::check_flags(flags, self.task.cred.read().euid)?;
+        if !flags.is_empty() {
+            self.check_single_thread()?;
+        }
+        self.task.ns.write().unshare(flags);
+        Ok(0)
+    }
+
+    /// Join the namespaces `nstype` picks of the process behind pidfd `fd`.
+    /// A PID namespace is joined by the children, and has to be ours or
+    /// one nested in it.
+    pub async fn sys_setns(&self, fd: usize, nstype: usize) -> Result<usize, TaskError> {
+        debug!("sys_setns @ fd: {}, nstype: {:#x}", fd, nstype);
+        let file = self.task.get_fd(fd).ok_or(TaskError::EBADF)?;
+        let target = file.inner.downcast_ref::<PidFd>().ok_or(TaskError::EINVAL)?.0.clone();
+        let flags = CloneFlags::from_bits(nstype)
+            .filter(|x| !x.is_empty() && NS_FLAGS.contains(*x))
+            .ok_or(TaskError::EINVAL)?;
+        Namespaces::check_flags(flags, self.task.cred.read().euid)?;
+        self.check_single_thread()?;
+        if target.is_zombie() {
+            return Err(TaskError::ESRCH);
+        }
+        let theirs = target.ns.read().clone();
+        if flags.contains(CloneFlags::NEWPID) && !self.task.pid_ns().contains(&theirs.pid) {
+            return Err(TaskError::EINVAL);
+        }
+        let mut ns = self.task.ns.write();
+        if flags.contains(CloneFlags::NEWNS) {
+            ns.mnt = theirs.mnt;
+        }
+        if flags.contains(CloneFlags::NEWUTS) {
+            ns.uts = theirs.uts;
+        }
+        if flags.contains(CloneFlags::NEWPID) {
+            ns.pid_for_children = theirs.pid;
+        }
+        Ok(0)
+    }
+
+    /// A pidfd of process `pid`, for `setns`.
+    pub async fn sys_pidfd_open(&self, pid: usize, flags: usize) -> Result<usize, TaskError> {
+        debug!("sys_pidfd_open @ pid: {}, flags: {:#x}", pid, flags);
+        let flags = OpenFlags::from_bits(flags)
+            .filter(|x| OpenFlags::O_NONBLOCK.contains(*x))
+            .ok_or(TaskError::EINVAL)?;
+        if (pid as isize) <= 0 {
+            return Err(TaskError::EINVAL);
+        }
+        let target = self.task.task_by_pid(pid).ok_or(TaskError::ESRCH)?;
+        if target.task_id != target.process_id {
+            return Err(TaskError::EINVAL);
+        }
+        let mut file = File::new_dev(Arc::new(PidFd(target)));
+        file.openflags |= flags | OpenFlags::O_CLOEXEC;
+        Ok(self.task.pcb.lock().fd_table.alloc(file)?)
+    }
+
+    /// The name `sethostname` and `setdomainname` set, `len` bytes with no
+    /// NUL needed.
+    fn read_uts_name(&self, name: UserBuf<u8>, len: usize) -> Result<String, TaskError> {
+        if self.task.cred.read().euid != 0 {
+            return Err(TaskError::EPERM);
+        }
+        if len > HOST_NAME_MAX {
+            return Err(TaskError::EINVAL);
+        }
+        if len > 0 && !name.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let bytes = match len {
+            0 => &[][..],
+            _ => &*name.slice_mut_with_len(len),
+        };
+        String::from_utf8(bytes.to_vec()).map_err(|_| TaskError::EINVAL)
+    }
+
+    pub async fn sys_sethostname(&self, name: UserBuf<u8>, len: usize) -> Result<usize, TaskError> {
+        debug!("sys_sethostname @ name: {}, len: {}", name, len);
+        let hostname = self.read_uts_name(name, len)?;
+        let uts = self.task.ns.read().uts.clone();
+        *uts.hostname.write() = hostname;
+        Ok(0)
+    }
+
+    pub async fn sys_setdomainname(&self, name: UserBuf<u8>, len: usize) -> Result<usize, TaskError> {
+        debug!("sys_setdomainname @ name: {}, len: {}", name, len);
+        let domainname = self.read_uts_name(name, len)?;
+        let uts = self.task.ns.read().uts.clone();
+        *uts.domainname.write() = domainname;
+        Ok(0)
+    }
+}
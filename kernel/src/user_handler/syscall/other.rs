@@ -1,33 +1,18 @@
-use crate::executor::sync::Sleep;
 use crate::user_handler::handler::UserHandler;
 use crate::executor::error::TaskError;
 use crate::user_handler::userbuf::UserBuf;
 
 use log::debug;
 use struct_define::tms::TMS;
+use struct_define::rusage::RUsage;
 use core::time::Duration;
-use timer::get_time;
+use timer::{get_time, sleep_until};
 use struct_define::timespec::TimeSpec;
 use struct_define::uname::UTSname;
+use config::target::plat::CLK_TCK;
 
 
-#[repr(C)]
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
-pub struct TimeVal {
-    /// seconds, range in 0~999999999
-    pub sec: usize,
-    /// microseconds, range in 0~999999
-    pub usec: usize,
-}
-
-impl From<Duration> for TimeVal {
-    fn from(duration: Duration) -> Self {
-        TimeVal {
-            sec: duration.as_secs() as usize,
-            usec: (duration.subsec_micros()) as usize,
-        }
-    }
-}
+pub use struct_define::timespec::TimeVal;
 
 
 
@@ -42,31 +27,80 @@ impl UserHandler {
         Ok(0)
     }
 
-    pub async fn sys_nanosleep(&self, req: UserBuf<TimeSpec>, _rem: UserBuf<TimeSpec>) -> Result<usize, TaskError> {
-
-        let req = req.read();
-        let _rem = _rem.read();
+    pub async fn sys_nanosleep(&self, req: UserBuf<TimeSpec>, rem: UserBuf<TimeSpec>) -> Result<usize, TaskError> {
+        debug!("sys_nanosleep @ req: {}, rem: {}", req, rem);
+        let duration = req.read().to_duration().ok_or(TaskError::EINVAL)?;
+        let deadline = get_time().saturating_add(duration);
+        let res = self.interruptible(sleep_until(deadline)).await;
+        if rem.is_valid() {
+            rem.write(deadline.saturating_sub(get_time()).into());
+        }
+        res.map(|_| 0)
+    }
 
-        let duration = Duration::from_secs(req.sec as u64) + Duration::from_nanos(req.nsec as u64);
-        let sleep = Sleep { time: duration };
-        sleep.await;
+    pub async fn sys_clock_nanosleep(
+        &self,
+        clock_id: usize,
+        flags: usize,
+        req: UserBuf<TimeSpec>,
+        rem: UserBuf<TimeSpec>,
+    ) -> Result<usize, TaskError> {
+        const TIMER_ABSTIME: usize = 1;
+        debug!(
+            "sys_clock_nanosleep @ clock_id: {}, flags: {:#x}, req: {}, rem: {}",
+            clock_id, flags, req, rem
+        );
+        // CLOCK_REALTIME, CLOCK_MONOTONIC and CLOCK_BOOTTIME all count from boot.
+        if !matches!(clock_id, 0 | 1 | 7) {
+            return Err(TaskError::EINVAL);
+        }
+        let time = req.read().to_duration().ok_or(TaskError::EINVAL)?;
+        if flags & TIMER_ABSTIME != 0 {
+            self.interruptible(sleep_until(time)).await?;
+        } else {
+            let deadline = get_time().saturating_add(time);
+            let res = self.interruptible(sleep_until(deadline)).await;
+            if rem.is_valid() {
+                rem.write(deadline.saturating_sub(get_time()).into());
+            }
+            res?;
+        }
         Ok(0)
     }
 
     pub async fn sys_times(&self, tms_ptr: UserBuf<TMS>) -> Result<usize, TaskError> {
-        const CLK_TCK: u128 = 100;
         debug!("sys_times @ tms_ptr: {}", tms_ptr);
-        let duration = get_time();
-        let total_ticks = duration.as_nanos() * CLK_TCK / 1_000_000_000;
+        let to_ticks = |duration: Duration| (duration.as_nanos() * CLK_TCK as u128 / 1_000_000_000) as u64;
+        let times = self.task.process_times();
+        let children = self.task.children_times();
         let tms = TMS {
-            utime: 0, // TODO: track user time
-            stime: total_ticks as u64,
-            cutime: 0,
-            cstime: 0,
+            utime: to_ticks(times.utime),
+            stime: to_ticks(times.stime),
+            cutime: to_ticks(children.utime),
+            cstime: to_ticks(children.stime),
         };
-        // println!("duration: {:?}", duration);
-        // println!("sys_times @ tms: {:#?}", tms);
-        tms_ptr.write(tms);
+        if tms_ptr.is_valid() {
+            tms_ptr.write(tms);
+        }
+        // Clock ticks since boot.
+        Ok(to_ticks(get_time()) as usize)
+    }
+
+    pub async fn sys_getrusage(&self, who: isize, usage: UserBuf<RUsage>) -> Result<usize, TaskError> {
+        const RUSAGE_SELF: isize = 0;
+        const RUSAGE_CHILDREN: isize = -1;
+        const RUSAGE_THREAD: isize = 1;
+        debug!("sys_getrusage @ who: {}, usage: {}", who, usage);
+        let times = match who {
+            RUSAGE_SELF => self.task.process_times(),
+            RUSAGE_CHILDREN => self.task.children_times(),
+            RUSAGE_THREAD => self.task.thread_times(),
+            _ => return Err(TaskError::EINVAL),
+        };
+        if !usage.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        usage.write(times.into());
         Ok(0)
     }
 
@@ -78,8 +112,10 @@ impl UserHandler {
         let sys_name = b"Linux";
         uts.sysname[..sys_name.len()].copy_from_slice(sys_name);
 
-        let sys_nodename = b"debian";
-        uts.nodename[..sys_nodename.len()].copy_from_slice(sys_nodename);
+        // The names of our UTS namespace, no longer than HOST_NAME_MAX.
+        let names = self.task.ns.read().uts.clone();
+        let sys_nodename = names.hostname.read();
+        uts.nodename[..sys_nodename.len()].copy_from_slice(sys_nodename.as_bytes());
 
         let sys_release = b"5.10.0-7-riscv64";
         uts.release[..sys_release.len()].copy_from_slice(sys_release);
@@ -90,7 +126,8 @@ impl UserHandler {
         let sys_machine = b"riscv qemu";
         uts.machine[..sys_machine.len()].copy_from_slice(sys_machine);
 
-        // domainname is already all zeros from default(), which is a valid empty C string.
+        let sys_domainname = names.domainname.read();
+        uts.domainname[..sys_domainname.len()].copy_from_slice(sys_domainname.as_bytes());
 
         buf_ptr.write(uts);
 
@@ -1,21 +1,38 @@
 use crate::executor::error::TaskError;
 use crate::executor::ops::yield_now;
 use crate::user_handler::handler::UserHandler;
-use log::{debug, warn};
+use log::debug;
 use struct_define::timespec::TimeSpec;
 use timer::get_time;
-use crate::executor::task::{AsyncTask, CloneFlags};
+use crate::executor::task::{AsyncTask, CloneFlags, WaitOptions};
 use crate::user_handler::userbuf::UserBuf;
 use crate::executor::task::AsyncTaskItem;
-use crate::executor::executor::add_ready_task;
+use crate::executor::executor::GLOBLE_EXECUTOR;
 use crate::user_handler::entry::user_entry;
-use crate::executor::sync::WaitPid;
+use crate::executor::sync::{WaitEvent, WaitPid};
+use crate::executor::jobctl::JobEvent;
+use crate::executor::cred::NGROUPS_MAX;
+use crate::executor::namespace::{NS_FLAGS, Namespaces};
+use crate::executor::thread::CpuTimes;
+use struct_define::signal::{CLD_CONTINUED, CLD_STOPPED, CLD_TRAPPED, SI_USER, SIGCONT, SIGTRAP, SigInfo};
 use trap::trapframe::TrapFrameArgs;
 use crate::executor::id_alloc::TaskId;
-use alloc::string::String;
+use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use filesystem::file::{File, OpenFlags};
+use elf_ext::binfmt::resolve_exec;
 use filesystem::path::Path;
-use crate::executor::thread::add_user_task;
+use filesystem::perm::{Access, check_access};
+use crate::executor::thread::UserTask;
+use alloc::sync::Arc;
+use struct_define::rusage::RUsage;
+use struct_define::resource::{RLIM_NLIMITS, RLimit};
+
+/// `idtype` of `waitid`.
+const P_ALL: usize = 0;
+const P_PID: usize = 1;
+const P_PGID: usize = 2;
+
 impl UserHandler {
     pub async fn sys_exit(&self, exit_code: isize) -> Result<usize, TaskError> {
         debug!(
@@ -44,10 +61,18 @@ impl UserHandler {
             self.task.get_task_id(), flags, stack, ptid, tls, ctid
         );
         
+        // Threads share the namespaces of their process.
+        if flags.contains(CloneFlags::THREAD) && flags.intersects(NS_FLAGS) {
+            return Err(TaskError::EINVAL);
+        }
+        Namespaces::check_flags(flags, self.task.cred.read().euid)?;
+        if !flags.contains(CloneFlags::THREAD) && !self.task.may_fork() {
+            return Err(TaskError::EAGAIN);
+        }
         let new_task = if flags.contains(CloneFlags::THREAD) {
             self.task.thread_clone()
         } else {
-            self.task.clone().process_clone()
+            self.task.clone().process_clone(flags)
         };
 
         if stack != 0 {
@@ -58,11 +83,26 @@ impl UserHandler {
             new_task.tcb.write().cx[TrapFrameArgs::TLS] = tls;
         }
 
-        // TODO: handle ptid and ctid
-        
         let new_task_id = new_task.get_task_id();
-        add_ready_task(AsyncTaskItem::new(new_task, user_entry()));
-        Ok(new_task_id.0)
+        let pid = self.task.pid_of(new_task_id);
+        if flags.contains(CloneFlags::PARENT_SETTID) && ptid.is_valid() {
+            ptid.write(pid as u32);
+        }
+        // The child's memory, which is a copy-on-write copy after a fork.
+        // It gets the pid the child sees, 1 in a new PID namespace.
+        if flags.contains(CloneFlags::CHILD_SETTID) && ctid.is_valid() {
+            new_task.write_user_u32(ctid.ptr as usize, new_task.pid_of(new_task_id) as u32);
+        }
+        if flags.contains(CloneFlags::CHILD_CLEARTID) {
+            new_task.tcb.write().clear_child_tid = Some(ctid.ptr as usize);
+        }
+
+        if !flags.contains(CloneFlags::THREAD) {
+            new_task.pcb.lock().exit_signal = (flags & CloneFlags::CSIGNAL).bits();
+        }
+
+        GLOBLE_EXECUTOR.spawn(AsyncTaskItem::new(new_task, user_entry()));
+        Ok(pid)
     }
 
     pub async fn sys_wait4(
@@ -70,87 +110,146 @@ impl UserHandler {
         pid: isize,           // 指定进程ID，可为-1等待任何子进程；
         status: UserBuf<i32>, // 接收状态的指针；
         options: usize,       // WNOHANG，WUNTRACED，WCONTINUED；
+        rusage: UserBuf<RUsage>,
     ) -> Result<usize, TaskError> {
         debug!(
             "[task {:?}] sys_wait4 @ pid: {}, status: {:?}, options: {}",
             self.tid, pid, status, options
         );
 
-        // return LinuxError::ECHILD if there has no child process.
-        if self.task.inner_map(|inner| inner.children.len()) == 0 {
-            return Err(TaskError::ECHILD);
-        }
-
-        if pid != -1 {
-            self.task
-                .inner_map(|inner| {
-                    inner
-                        .children
-                        .iter()
-                        .find(|x| x.task_id == TaskId(pid as usize))
-                        .map(|x| x.clone())
-                })
-                .ok_or(TaskError::ECHILD)?;
-        }
-        if options == 0 || options == 2 || options == 3 || options == 10 {
-            debug!(
-                "children:{:?}",
-                self.task.pcb.lock().children.iter().count()
-            );
-            let child_task = WaitPid(self.task.clone(), pid).await?;
-
-            debug!(
-                "wait ok: {:?}  waiter: {:?}",
-                child_task.task_id, self.task.task_id
-            );
-            // release the task resources
-            self.task
-                .pcb
-                .lock()
-                .children
-                .retain(|x| x.task_id != child_task.task_id);
-            child_task.release();
-            debug!("wait pid: {}", child_task.exit_code().unwrap());
-
-            if status.is_valid() {
-                status.write((child_task.exit_code().unwrap() as i32) << 8);
+        let options = WaitOptions::from_bits(options)
+            .filter(|x| !x.intersects(WaitOptions::EXITED | WaitOptions::NOWAIT))
+            .ok_or(TaskError::EINVAL)?;
+        let wait = WaitPid {
+            task: self.task.clone(),
+            pid,
+            options: options | WaitOptions::EXITED,
+        };
+        let Some((child_task, event)) = self.wait_child(wait).await? else {
+            return Ok(0);
+        };
+        let (tid, wstatus) = match event {
+            WaitEvent::Exited => (child_task.task_id, child_task.exit_status()),
+            WaitEvent::Job(job_event) => (child_task.task_id, job_event.status()),
+            WaitEvent::Traced(tid, sig) => (tid, sig << 8 | 0x7f),
+        };
+        let times = self.collect_child(&child_task, event);
+        if status.is_valid() {
+            status.write(wstatus);
+        }
+        if rusage.is_valid() {
+            rusage.write(times.into());
+        }
+        Ok(self.task.pid_of(tid))
+    }
+
+    pub async fn sys_waitid(
+        &self,
+        idtype: usize,
+        id: isize,
+        infop: UserBuf<SigInfo>,
+        options: usize,
+        rusage: UserBuf<RUsage>,
+    ) -> Result<usize, TaskError> {
+        debug!(
+            "[task {:?}] sys_waitid @ idtype: {}, id: {}, infop: {}, options: {:#x}",
+            self.tid, idtype, id, infop, options
+        );
+        let options = WaitOptions::from_bits(options)
+            .filter(|x| x.intersects(WaitOptions::EXITED | WaitOptions::UNTRACED | WaitOptions::CONTINUED))
+            .ok_or(TaskError::EINVAL)?;
+        let pid = match idtype {
+            P_ALL => -1,
+            P_PID if id > 0 => id,
+            // Group 0 is the caller's.
+            P_PGID if id >= 0 => -id,
+            _ => return Err(TaskError::EINVAL),
+        };
+        let wait = WaitPid {
+            task: self.task.clone(),
+            pid,
+            options,
+        };
+        let Some((child_task, event)) = self.wait_child(wait).await? else {
+            // Nothing to report yet, which `si_pid` 0 tells.
+            if infop.is_valid() {
+                infop.write(SigInfo::new(0, 0));
             }
-            Ok(child_task.task_id.0)
-        } else if options == 1 {
-            let child_task = self
-                .task
-                .pcb
-                .lock()
-                .children
-                .iter()
-                .find(|x| x.task_id == TaskId(pid as usize) || pid == -1)
-                .cloned();
-            let exit = child_task.clone().map_or(None, |x| x.exit_code());
-            match exit {
-                Some(t1) => {
-                    let child_task = child_task.unwrap();
-                    // Release task.
-                    self.task
-                        .pcb
-                        .lock()
-                        .children
-                        .retain(|x| x.task_id != child_task.task_id);
-                    child_task.release();
-                    if status.is_valid() {
-                        status.write((t1 as i32) << 8);
+            return Ok(0);
+        };
+        let (code, status) = match event {
+            WaitEvent::Exited => child_task.exit_info(),
+            WaitEvent::Job(JobEvent::Stopped(signo)) => (CLD_STOPPED, signo as i32),
+            WaitEvent::Job(JobEvent::Continued) => (CLD_CONTINUED, SIGCONT as i32),
+            WaitEvent::Traced(_, sig) => (CLD_TRAPPED, sig),
+        };
+        let times = match options.contains(WaitOptions::NOWAIT) {
+            true => child_task.process_times(),
+            false => self.collect_child(&child_task, event),
+        };
+        if infop.is_valid() {
+            let pid = self.task.pid_of(child_task.task_id);
+            infop.write(SigInfo::child(code, pid, child_task.cred.read().uid, status));
+        }
+        if rusage.is_valid() {
+            rusage.write(times.into());
+        }
+        Ok(0)
+    }
+
+    /// Wait as `wait` says, `None` if `WNOHANG` found nothing.
+    async fn wait_child(&self, wait: WaitPid) -> Result<Option<(Arc<UserTask>, WaitEvent)>, TaskError> {
+        let res = match wait.options.contains(WaitOptions::NOHANG) {
+            true => wait.poll_now()?,
+            false => Some(self.interruptible(wait).await??),
+        };
+        if let Some((child, event)) = &res {
+            debug!("wait ok: {:?} {:?} waiter: {:?}", child.task_id, event, self.task.task_id);
+        }
+        Ok(res)
+    }
+
+    /// Consume the event of `child` once reported: reap it if it exited,
+    /// a stop or continue is reported once. Returns its CPU time.
+    fn collect_child(&self, child: &Arc<UserTask>, event: WaitEvent) -> CpuTimes {
+        match event {
+            WaitEvent::Exited => {
+                // A tracer that isn't the parent lets go of the process, the
+                // parent reaps it.
+                child.detach_tracer();
+                let is_child = self.task.pcb.lock().children.iter().any(|x| x.task_id == child.task_id);
+                match is_child {
+                    true => self.reap_child(child),
+                    false => child.process_times(),
+                }
+            }
+            WaitEvent::Job(_) => {
+                child.pcb.lock().job_event = None;
+                child.process_times()
+            }
+            WaitEvent::Traced(tid, _) => {
+                if let Some(thread) = child.threads().into_iter().find(|x| x.task_id == tid) {
+                    if let Some(stop) = thread.tcb.write().trace.stop.as_mut() {
+                        stop.reported = true;
                     }
-                    // TIPS: This is a small change.
-                    Ok(child_task.task_id.0)
-                    // Ok(0)
                 }
-                None => Ok(0),
+                child.process_times()
             }
-        } else {
-            warn!("wait4 unsupported options: {}", options);
-            Err(TaskError::EPERM)
         }
     }
 
+    /// Release an exited child and charge its CPU time to our children.
+    fn reap_child(&self, child: &Arc<UserTask>) -> CpuTimes {
+        let mut times = child.process_times();
+        times += child.children_times();
+        let mut pcb = self.task.pcb.lock();
+        pcb.children.retain(|x| x.task_id != child.task_id);
+        pcb.children_times += times;
+        drop(pcb);
+        child.release();
+        times
+    }
+
     pub async fn sys_execve(
         &self,
         filename: UserBuf<u8>,      // *mut i8
@@ -183,29 +282,103 @@ impl UserHandler {
         }
 
         debug!("sys_execve @ filename: {}, args: {:?}, envp: {:?}", file_name, args_vec, envp_vec);
-                let _path = Path::new(file_name.clone());
-        
-        // Convert Vec<String> to Vec<&str>
-        let args_str: Vec<&str> = args_vec.iter().map(|s| s.as_str()).collect();
-        let envp_str: Vec<&str> = envp_vec.iter().map(|s| s.as_str()).collect();
-        let id = add_user_task(&file_name, args_str, envp_str);
-        self.task.thread_exit(id.0);
-        Ok(id.0)
+        let path = match file_name.strip_prefix("./").unwrap_or(&file_name) {
+            name if name.starts_with('/') => Path::new(name.to_string()),
+            name => self.task.pcb.lock().curr_dir.join(name),
+        };
+        let file = File::open(&path.to_string(), OpenFlags::O_RDONLY)?;
+        check_access(&file.inner, Access::EXEC).map_err(|_| TaskError::EACCES)?;
+        // A script runs its interpreter, which has to be executable too.
+        let (program, args_vec) = resolve_exec(path.clone(), args_vec)?;
+        if program != path {
+            let file = File::open(&program.to_string(), OpenFlags::O_RDONLY)?;
+            check_access(&file.inner, Access::EXEC).map_err(|_| TaskError::EACCES)?;
+        }
+        self.task.exec(program, args_vec, envp_vec)?;
+        // A traced process stops in the new program before it runs.
+        if self.task.tracer().is_some() {
+            self.task.signal_thread(SigInfo::new(SIGTRAP, SI_USER));
+        }
+        Ok(0)
     }
 
 
     /// sys_getpid() 获取进程 id
     pub async fn sys_getpid(&self) -> Result<usize, TaskError> {
-        Ok(self.task.process_id.0)
+        Ok(self.task.pid_of(self.task.process_id))
+    }
+
+    pub async fn sys_gettid(&self) -> Result<usize, TaskError> {
+        Ok(self.task.pid_of(self.tid))
     }
 
+    /// A parent outside our PID namespace reads as 0.
     pub async fn sys_getppid(&self) -> Result<usize, TaskError> {
         match self.task.parent.read().upgrade() {
-            Some(parent) => Ok(parent.process_id.0),
+            Some(parent) => Ok(self.task.pid_of(parent.process_id)),
             None => Ok(-1_isize as usize),
         }
     }
 
+    /// The process `pid`, 0 is the caller.
+    fn find_process(&self, pid: isize) -> Result<Arc<UserTask>, TaskError> {
+        match pid {
+            0 => Ok(self.task.clone()),
+            1.. => self
+                .task
+                .task_by_pid(pid as usize)
+                .filter(|task| task.task_id == task.process_id && !task.is_zombie())
+                .ok_or(TaskError::ESRCH),
+            _ => Err(TaskError::EINVAL),
+        }
+    }
+
+    pub async fn sys_setpgid(&self, pid: isize, pgid: isize) -> Result<usize, TaskError> {
+        debug!("sys_setpgid @ pid: {}, pgid: {}", pid, pgid);
+        if pgid < 0 {
+            return Err(TaskError::EINVAL);
+        }
+        // Only the caller and its children can be moved.
+        let target = self.find_process(pid)?;
+        let is_child = target
+            .parent
+            .read()
+            .upgrade()
+            .is_some_and(|parent| parent.process_id == self.task.process_id);
+        if target.process_id != self.task.process_id && !is_child {
+            return Err(TaskError::ESRCH);
+        }
+        if target.sid() != self.task.sid() {
+            return Err(TaskError::EPERM);
+        }
+        let pgid = match pgid {
+            0 => target.process_id.0,
+            _ => self.task.pid_ns().task_id(pgid as usize).ok_or(TaskError::EPERM)?.0,
+        };
+        target.set_pgid(pgid)?;
+        Ok(0)
+    }
+
+    pub async fn sys_getpgid(&self, pid: isize) -> Result<usize, TaskError> {
+        debug!("sys_getpgid @ pid: {}", pid);
+        Ok(self.task.pid_of(TaskId(self.find_process(pid)?.pgid())))
+    }
+
+    /// `getpgrp`, which riscv64 libcs implement as `getpgid(0)`.
+    pub async fn sys_getpgrp(&self) -> Result<usize, TaskError> {
+        self.sys_getpgid(0).await
+    }
+
+    pub async fn sys_setsid(&self) -> Result<usize, TaskError> {
+        debug!("sys_setsid");
+        Ok(self.task.pid_of(TaskId(self.task.set_sid()?)))
+    }
+
+    pub async fn sys_getsid(&self, pid: isize) -> Result<usize, TaskError> {
+        debug!("sys_getsid @ pid: {}", pid);
+        Ok(self.task.pid_of(TaskId(self.find_process(pid)?.sid())))
+    }
+
     pub async fn sys_sched_yield(&self) -> Result<usize, TaskError> {
         debug!("sys_sched_yield @ ");
         yield_now().await;
@@ -214,15 +387,170 @@ impl UserHandler {
 
     pub async fn sys_set_tid_address(&self, tid_address: UserBuf<u32>) -> Result<usize, TaskError> {
         debug!("sys_set_tid_address @ tid_address: {:?}", tid_address);
-        let tid_address = tid_address.read();
-        self.task.tcb.write().clear_child_tid = Some(tid_address.try_into().unwrap());
-        Ok(self.tid.0)
+        self.task.tcb.write().clear_child_tid = match tid_address.is_valid() {
+            true => Some(tid_address.ptr as usize),
+            false => None,
+        };
+        Ok(self.task.pid_of(self.tid))
     }
 
     pub async fn sys_getuid(&self) -> Result<usize, TaskError> {
+        Ok(self.task.cred.read().uid as usize)
+    }
+
+    pub async fn sys_geteuid(&self) -> Result<usize, TaskError> {
+        Ok(self.task.cred.read().euid as usize)
+    }
+
+    pub async fn sys_getgid(&self) -> Result<usize, TaskError> {
+        Ok(self.task.cred.read().gid as usize)
+    }
+
+    pub async fn sys_getegid(&self) -> Result<usize, TaskError> {
+        Ok(self.task.cred.read().egid as usize)
+    }
+
+    pub async fn sys_setuid(&self, uid: u32) -> Result<usize, TaskError> {
+        debug!("sys_setuid @ uid: {}", uid);
+        self.task.cred.write().setuid(uid)?;
+        Ok(0)
+    }
+
+    /// Set the file mode creation mask, giving the old one.
+    pub async fn sys_umask(&self, mask: u32) -> Result<usize, TaskError> {
+        debug!("sys_umask @ mask: {:#o}", mask);
+        let old = core::mem::replace(&mut self.task.cred.write().umask, mask & 0o777);
+        Ok(old as usize)
+    }
+
+    pub async fn sys_setgid(&self, gid: u32) -> Result<usize, TaskError> {
+        debug!("sys_setgid @ gid: {}", gid);
+        self.task.cred.write().setgid(gid)?;
         Ok(0)
     }
 
+    pub async fn sys_setreuid(&self, ruid: u32, euid: u32) -> Result<usize, TaskError> {
+        debug!("sys_setreuid @ ruid: {}, euid: {}", ruid as i32, euid as i32);
+        self.task.cred.write().setreuid(ruid, euid)?;
+        Ok(0)
+    }
+
+    pub async fn sys_setregid(&self, rgid: u32, egid: u32) -> Result<usize, TaskError> {
+        debug!("sys_setregid @ rgid: {}, egid: {}", rgid as i32, egid as i32);
+        self.task.cred.write().setregid(rgid, egid)?;
+        Ok(0)
+    }
+
+    pub async fn sys_setresuid(&self, ruid: u32, euid: u32, suid: u32) -> Result<usize, TaskError> {
+        debug!("sys_setresuid @ ruid: {}, euid: {}, suid: {}", ruid as i32, euid as i32, suid as i32);
+        self.task.cred.write().setresuid(ruid, euid, suid)?;
+        Ok(0)
+    }
+
+    pub async fn sys_setresgid(&self, rgid: u32, egid: u32, sgid: u32) -> Result<usize, TaskError> {
+        debug!("sys_setresgid @ rgid: {}, egid: {}, sgid: {}", rgid as i32, egid as i32, sgid as i32);
+        self.task.cred.write().setresgid(rgid, egid, sgid)?;
+        Ok(0)
+    }
+
+    pub async fn sys_getresuid(
+        &self,
+        ruid: UserBuf<u32>,
+        euid: UserBuf<u32>,
+        suid: UserBuf<u32>,
+    ) -> Result<usize, TaskError> {
+        if !(ruid.is_valid() && euid.is_valid() && suid.is_valid()) {
+            return Err(TaskError::EFAULT);
+        }
+        let cred = self.task.cred.read().clone();
+        ruid.write(cred.uid);
+        euid.write(cred.euid);
+        suid.write(cred.suid);
+        Ok(0)
+    }
+
+    pub async fn sys_getresgid(
+        &self,
+        rgid: UserBuf<u32>,
+        egid: UserBuf<u32>,
+        sgid: UserBuf<u32>,
+    ) -> Result<usize, TaskError> {
+        if !(rgid.is_valid() && egid.is_valid() && sgid.is_valid()) {
+            return Err(TaskError::EFAULT);
+        }
+        let cred = self.task.cred.read().clone();
+        rgid.write(cred.gid);
+        egid.write(cred.egid);
+        sgid.write(cred.sgid);
+        Ok(0)
+    }
+
+    /// Copy the supplementary groups to `list`, or only count them if `size`
+    /// is 0.
+    pub async fn sys_getgroups(&self, size: usize, list: UserBuf<u32>) -> Result<usize, TaskError> {
+        let groups = self.task.cred.read().groups.clone();
+        if size == 0 {
+            return Ok(groups.len());
+        }
+        if size < groups.len() {
+            return Err(TaskError::EINVAL);
+        }
+        for (i, gid) in groups.iter().enumerate() {
+            list.offset(i as isize).write(*gid);
+        }
+        Ok(groups.len())
+    }
+
+    pub async fn sys_setgroups(&self, size: usize, list: UserBuf<u32>) -> Result<usize, TaskError> {
+        debug!("sys_setgroups @ size: {}", size);
+        if size > NGROUPS_MAX {
+            return Err(TaskError::EINVAL);
+        }
+        let groups = (0..size).map(|i| list.offset(i as isize).read()).collect();
+        self.task.cred.write().setgroups(groups)?;
+        Ok(0)
+    }
+
+    /// Get and set the limits of process `pid`, 0 is the caller.
+    pub async fn sys_prlimit64(
+        &self,
+        pid: isize,
+        resource: usize,
+        new_limit: UserBuf<RLimit>,
+        old_limit: UserBuf<RLimit>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_prlimit64 @ pid: {}, resource: {}", pid, resource);
+        if resource >= RLIM_NLIMITS {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.find_process(pid)?;
+        if pid != 0 && !self.task.cred.read().owns(&task.cred.read()) {
+            return Err(TaskError::EPERM);
+        }
+        let old = task.rlimit(resource);
+        if new_limit.is_valid() {
+            task.set_rlimit(resource, new_limit.read())?;
+        }
+        if old_limit.is_valid() {
+            old_limit.write(old);
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_getrlimit(&self, resource: usize, limit: UserBuf<RLimit>) -> Result<usize, TaskError> {
+        if !limit.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        self.sys_prlimit64(0, resource, UserBuf::new(core::ptr::null_mut()), limit).await
+    }
+
+    pub async fn sys_setrlimit(&self, resource: usize, limit: UserBuf<RLimit>) -> Result<usize, TaskError> {
+        if !limit.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        self.sys_prlimit64(0, resource, limit, UserBuf::new(core::ptr::null_mut())).await
+    }
+
     pub async fn sys_clock_gettime(
         &self,
         clock_id: usize,
@@ -233,14 +561,30 @@ impl UserHandler {
             self.task.get_task_id(), clock_id, times_ptr
         );
 
-        let ns = match clock_id {
+        let ns = match clock_id as i32 {
             0 => get_time(),        // CLOCK_REALTIME
             1 => get_time(), // CLOCK_MONOTONIC
-            2 => {
-                panic!("CLOCK_PROCESS_CPUTIME_ID not implemented");
-            }
-            3 => {
-                panic!("CLOCK_THREAD_CPUTIME_ID not implemented");
+            2 => self.task.process_times().total(), // CLOCK_PROCESS_CPUTIME_ID
+            3 => self.task.thread_times().total(),  // CLOCK_THREAD_CPUTIME_ID
+            4 | 7 => get_time(), // CLOCK_MONOTONIC_RAW, CLOCK_BOOTTIME
+            // clock_getcpuclockid() and pthread_getcpuclockid() encode the pid
+            // as `~pid << 3`, bit 2 selects a thread clock and the low bits
+            // pick PROF (0), VIRT (1) or SCHED (2).
+            id if id < 0 => {
+                let pid = !(id >> 3) as usize;
+                let task = match pid {
+                    0 => self.task.clone(),
+                    pid => self.task.task_by_pid(pid).ok_or(TaskError::EINVAL)?,
+                };
+                let times = match id & 4 != 0 {
+                    true => task.thread_times(),
+                    false => task.process_times(),
+                };
+                match id & 3 {
+                    1 => times.utime,
+                    0 | 2 => times.total(),
+                    _ => return Err(TaskError::EINVAL),
+                }
             }
             _ => return Err(TaskError::EINVAL),
         };
@@ -0,0 +1,190 @@
+use crate::executor::error::TaskError;
+use crate::executor::ptrace::TraceMode;
+use crate::executor::signal::valid_signal;
+use crate::executor::thread::UserTask;
+use crate::user_handler::handler::UserHandler;
+use crate::user_handler::userbuf::UserBuf;
+use alloc::sync::Arc;
+use core::mem::size_of;
+use log::debug;
+use struct_define::fd::IoVec;
+use struct_define::signal::{SI_USER, SIGSTOP, SigInfo};
+use trap::trapframe::TrapFrame;
+
+/// `request` of `ptrace`.
+const PTRACE_TRACEME: usize = 0;
+const PTRACE_PEEKTEXT: usize = 1;
+const PTRACE_PEEKDATA: usize = 2;
+const PTRACE_POKETEXT: usize = 4;
+const PTRACE_POKEDATA: usize = 5;
+const PTRACE_CONT: usize = 7;
+const PTRACE_SINGLESTEP: usize = 9;
+const PTRACE_GETREGS: usize = 12;
+const PTRACE_SETREGS: usize = 13;
+const PTRACE_ATTACH: usize = 16;
+const PTRACE_DETACH: usize = 17;
+const PTRACE_SYSCALL: usize = 24;
+const PTRACE_SETOPTIONS: usize = 0x4200;
+const PTRACE_GETSIGINFO: usize = 0x4202;
+const PTRACE_GETREGSET: usize = 0x4204;
+const PTRACE_SETREGSET: usize = 0x4205;
+
+/// The general registers in `PTRACE_GETREGSET`.
+const NT_PRSTATUS: usize = 1;
+
+/// `struct user_regs_struct`: `pc` in place of `x0`, then `x1` to `x31`.
+type UserRegs = [usize; 32];
+
+fn user_regs(cx: &TrapFrame) -> UserRegs {
+    let mut regs = cx.x;
+    regs[0] = cx.sepc;
+    regs
+}
+
+fn set_user_regs(cx: &mut TrapFrame, regs: &UserRegs) {
+    cx.sepc = regs[0];
+    cx.x[1..].copy_from_slice(&regs[1..]);
+}
+
+/// The signal a tracee is resumed with, 0 for none.
+fn resume_signal(data: usize) -> Result<usize, TaskError> {
+    match data == 0 || valid_signal(data) {
+        true => Ok(data),
+        false => Err(TaskError::EIO),
+    }
+}
+
+impl UserHandler {
+    /// Thread `pid` of a process we trace, stopped for us.
+    fn tracee(&self, pid: usize) -> Result<Arc<UserTask>, TaskError> {
+        self.task
+            .task_by_pid(pid)
+            .filter(|task| task.traced_by(&self.task) && task.tcb.read().trace.stop.is_some())
+            .ok_or(TaskError::ESRCH)
+    }
+
+    /// Trace process `pid`, which gets a `SIGSTOP` to stop it for us.
+    fn ptrace_attach(&self, pid: usize) -> Result<usize, TaskError> {
+        let target = self
+            .task
+            .task_by_pid(pid)
+            .filter(|task| !task.is_zombie())
+            .ok_or(TaskError::ESRCH)?;
+        if target.process_id == self.task.process_id || target.pcb.lock().ptrace.is_some() {
+            return Err(TaskError::EPERM);
+        }
+        if !self.task.cred.read().owns(&target.cred.read()) {
+            return Err(TaskError::EPERM);
+        }
+        target.attach_tracer(&self.task);
+        target.signal_process(SigInfo::new(SIGSTOP, SI_USER));
+        Ok(0)
+    }
+
+    pub async fn sys_ptrace(&self, request: usize, pid: usize, addr: usize, data: usize) -> Result<usize, TaskError> {
+        debug!(
+            "[task {:?}] sys_ptrace @ request: {:#x}, pid: {}, addr: {:#x}, data: {:#x}",
+            self.tid, request, pid, addr, data
+        );
+        match request {
+            PTRACE_TRACEME => {
+                let parent = self.task.parent.read().upgrade().ok_or(TaskError::EPERM)?;
+                if self.task.pcb.lock().ptrace.is_some() {
+                    return Err(TaskError::EPERM);
+                }
+                self.task.attach_tracer(&parent);
+                return Ok(0);
+            }
+            PTRACE_ATTACH => return self.ptrace_attach(pid),
+            _ => {}
+        }
+
+        let tracee = self.tracee(pid)?;
+        match request {
+            // The raw call stores the word at `data`, libc returns it.
+            PTRACE_PEEKTEXT | PTRACE_PEEKDATA => {
+                let mut word = [0u8; size_of::<usize>()];
+                if !tracee.peek_user(addr, &mut word) {
+                    return Err(TaskError::EIO);
+                }
+                let out = UserBuf::new(data as *mut usize);
+                if !out.is_valid() {
+                    return Err(TaskError::EFAULT);
+                }
+                out.write(usize::from_le_bytes(word));
+                Ok(0)
+            }
+            PTRACE_POKETEXT | PTRACE_POKEDATA => match tracee.poke_user(addr, &data.to_le_bytes()) {
+                true => Ok(0),
+                false => Err(TaskError::EIO),
+            },
+            PTRACE_GETREGS => {
+                let out = UserBuf::new(data as *mut UserRegs);
+                if !out.is_valid() {
+                    return Err(TaskError::EFAULT);
+                }
+                out.write(user_regs(&tracee.tcb.read().cx));
+                Ok(0)
+            }
+            PTRACE_SETREGS => {
+                let regs = UserBuf::new(data as *mut UserRegs);
+                if !regs.is_valid() {
+                    return Err(TaskError::EFAULT);
+                }
+                set_user_regs(&mut tracee.tcb.write().cx, &regs.read());
+                Ok(0)
+            }
+            PTRACE_GETREGSET | PTRACE_SETREGSET => {
+                let iov = UserBuf::new(data as *mut IoVec);
+                if addr != NT_PRSTATUS {
+                    return Err(TaskError::EINVAL);
+                }
+                if !iov.is_valid() {
+                    return Err(TaskError::EFAULT);
+                }
+                let mut vec = iov.read();
+                let regs = UserBuf::new(vec.base as *mut UserRegs);
+                if !regs.is_valid() || vec.len < size_of::<UserRegs>() {
+                    return Err(TaskError::EINVAL);
+                }
+                match request {
+                    PTRACE_GETREGSET => regs.write(user_regs(&tracee.tcb.read().cx)),
+                    _ => set_user_regs(&mut tracee.tcb.write().cx, &regs.read()),
+                }
+                vec.len = size_of::<UserRegs>();
+                iov.write(vec);
+                Ok(0)
+            }
+            PTRACE_GETSIGINFO => {
+                let out = UserBuf::new(data as *mut SigInfo);
+                if !out.is_valid() {
+                    return Err(TaskError::EFAULT);
+                }
+                let stop = tracee.tcb.read().trace.stop.ok_or(TaskError::ESRCH)?;
+                out.write(stop.info);
+                Ok(0)
+            }
+            PTRACE_SETOPTIONS => {
+                if let Some(tracing) = tracee.pcb.lock().ptrace.as_mut() {
+                    tracing.options = data;
+                }
+                Ok(0)
+            }
+            PTRACE_CONT | PTRACE_SYSCALL | PTRACE_SINGLESTEP => {
+                let mode = match request {
+                    PTRACE_SYSCALL => TraceMode::Syscall,
+                    PTRACE_SINGLESTEP => TraceMode::Step,
+                    _ => TraceMode::Cont,
+                };
+                tracee.trace_resume(mode, resume_signal(data)?);
+                Ok(0)
+            }
+            PTRACE_DETACH => {
+                tracee.trace_resume(TraceMode::Cont, resume_signal(data)?);
+                tracee.detach_tracer();
+                Ok(0)
+            }
+            _ => Err(TaskError::EIO),
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::executor::error::TaskError;
+use crate::executor::ops::yield_now;
+use crate::executor::sched::{MAX_NICE, MAX_RT_PRIO, MIN_NICE, MIN_RT_PRIO, SchedPolicy};
+use crate::executor::thread::UserTask;
+use crate::user_handler::handler::UserHandler;
+use crate::user_handler::userbuf::UserBuf;
+use alloc::sync::Arc;
+use arch::{get_cpu_num, get_cur_cpu_id};
+use core::mem::size_of;
+use log::debug;
+use struct_define::sched::SchedParam;
+
+/// `which` of `setpriority`/`getpriority`.
+const PRIO_PROCESS: usize = 0;
+
+/// Flag or-ed into the policy, accepted but ignored.
+const SCHED_RESET_ON_FORK: usize = 0x4000_0000;
+
+/// Check that `priority` is valid for `policy`.
+fn check_priority(policy: SchedPolicy, priority: i32) -> Result<u32, TaskError> {
+    let valid = match policy.is_realtime() {
+        true => (MIN_RT_PRIO as i32..=MAX_RT_PRIO as i32).contains(&priority),
+        false => priority == 0,
+    };
+    match valid {
+        true => Ok(priority as u32),
+        false => Err(TaskError::EINVAL),
+    }
+}
+
+/// Mask of the CPUs that exist.
+fn online_cpus() -> usize {
+    match get_cpu_num() {
+        num if num >= usize::BITS as usize => usize::MAX,
+        num => (1 << num) - 1,
+    }
+}
+
+impl UserHandler {
+    /// Find the task a sched_* call refers to, 0 is the caller.
+    fn sched_target(&self, pid: isize) -> Result<Arc<UserTask>, TaskError> {
+        match pid {
+            0 => Ok(self.task.clone()),
+            pid if pid < 0 => Err(TaskError::EINVAL),
+            pid => self.task.task_by_pid(pid as usize).ok_or(TaskError::ESRCH),
+        }
+    }
+
+    pub async fn sys_sched_setscheduler(
+        &self,
+        pid: isize,
+        policy: usize,
+        param: UserBuf<SchedParam>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_sched_setscheduler @ pid: {}, policy: {}, param: {}", pid, policy, param);
+        let policy = SchedPolicy::from_raw(policy & !SCHED_RESET_ON_FORK).ok_or(TaskError::EINVAL)?;
+        if !param.is_valid() {
+            return Err(TaskError::EINVAL);
+        }
+        let priority = check_priority(policy, param.read().sched_priority)?;
+        let task = self.sched_target(pid)?;
+        let mut entity = task.sched.lock();
+        entity.policy = policy;
+        entity.rt_priority = priority;
+        Ok(0)
+    }
+
+    pub async fn sys_sched_getscheduler(&self, pid: isize) -> Result<usize, TaskError> {
+        debug!("sys_sched_getscheduler @ pid: {}", pid);
+        let task = self.sched_target(pid)?;
+        let policy = task.sched.lock().policy;
+        Ok(policy as usize)
+    }
+
+    pub async fn sys_sched_setparam(
+        &self,
+        pid: isize,
+        param: UserBuf<SchedParam>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_sched_setparam @ pid: {}, param: {}", pid, param);
+        if !param.is_valid() {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.sched_target(pid)?;
+        let mut entity = task.sched.lock();
+        entity.rt_priority = check_priority(entity.policy, param.read().sched_priority)?;
+        Ok(0)
+    }
+
+    pub async fn sys_sched_getparam(
+        &self,
+        pid: isize,
+        param: UserBuf<SchedParam>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_sched_getparam @ pid: {}, param: {}", pid, param);
+        if !param.is_valid() {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.sched_target(pid)?;
+        let sched_priority = task.sched.lock().rt_priority as i32;
+        param.write(SchedParam { sched_priority });
+        Ok(0)
+    }
+
+    pub async fn sys_sched_get_priority_max(&self, policy: usize) -> Result<usize, TaskError> {
+        let policy = SchedPolicy::from_raw(policy).ok_or(TaskError::EINVAL)?;
+        match policy.is_realtime() {
+            true => Ok(MAX_RT_PRIO as usize),
+            false => Ok(0),
+        }
+    }
+
+    pub async fn sys_sched_get_priority_min(&self, policy: usize) -> Result<usize, TaskError> {
+        let policy = SchedPolicy::from_raw(policy).ok_or(TaskError::EINVAL)?;
+        match policy.is_realtime() {
+            true => Ok(MIN_RT_PRIO as usize),
+            false => Ok(0),
+        }
+    }
+
+    pub async fn sys_setpriority(
+        &self,
+        which: usize,
+        who: isize,
+        prio: isize,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_setpriority @ which: {}, who: {}, prio: {}", which, who, prio);
+        if which != PRIO_PROCESS {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.sched_target(who)?;
+        task.sched.lock().nice = (prio as i32).clamp(MIN_NICE, MAX_NICE);
+        Ok(0)
+    }
+
+    /// Returns `20 - nice` like the raw Linux syscall, libc converts it back.
+    pub async fn sys_getpriority(&self, which: usize, who: isize) -> Result<usize, TaskError> {
+        debug!("sys_getpriority @ which: {}, who: {}", which, who);
+        if which != PRIO_PROCESS {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.sched_target(who)?;
+        let nice = task.sched.lock().nice;
+        Ok((20 - nice) as usize)
+    }
+
+    pub async fn sys_sched_setaffinity(
+        &self,
+        pid: isize,
+        len: usize,
+        mask: UserBuf<u8>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_sched_setaffinity @ pid: {}, len: {}, mask: {}", pid, len, mask);
+        if !mask.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let mut bytes = [0u8; size_of::<usize>()];
+        let len = len.min(bytes.len());
+        bytes[..len].copy_from_slice(mask.slice_mut_with_len(len));
+        let affinity = usize::from_le_bytes(bytes) & online_cpus();
+        if affinity == 0 {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.sched_target(pid)?;
+        task.sched.lock().affinity = affinity;
+        // Leave this CPU if it isn't allowed anymore, the run queue picks a
+        // new one.
+        let allowed = task.sched.lock().allows_cpu(get_cur_cpu_id());
+        if Arc::ptr_eq(&task, &self.task) && !allowed {
+            yield_now().await;
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_sched_getaffinity(
+        &self,
+        pid: isize,
+        len: usize,
+        mask: UserBuf<u8>,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_sched_getaffinity @ pid: {}, len: {}, mask: {}", pid, len, mask);
+        if len < size_of::<usize>() {
+            return Err(TaskError::EINVAL);
+        }
+        if !mask.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let task = self.sched_target(pid)?;
+        let affinity = task.sched.lock().affinity & online_cpus();
+        mask.write_slice(&affinity.to_le_bytes());
+        Ok(size_of::<usize>())
+    }
+}
@@ -0,0 +1,231 @@
+use crate::executor::error::TaskError;
+use crate::executor::jobctl::process_group;
+use crate::executor::signal::{DefaultAction, UNBLOCKABLE, user_processes, valid_signal};
+use crate::executor::thread::UserTask;
+use crate::user_handler::handler::UserHandler;
+use crate::user_handler::userbuf::UserBuf;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::future::poll_fn;
+use core::mem::size_of;
+use core::task::Poll;
+use log::debug;
+use struct_define::signal::*;
+use struct_define::timespec::TimeSpec;
+use timer::timeout;
+use trap::trapframe::TrapFrameArgs;
+
+impl UserHandler {
+    /// Find the thread `tid` of our PID namespace.
+    fn find_task(&self, tid: usize) -> Result<Arc<UserTask>, TaskError> {
+        self.task.task_by_pid(tid).ok_or(TaskError::ESRCH)
+    }
+
+    fn check_sigset_size(sigsetsize: usize) -> Result<(), TaskError> {
+        match sigsetsize == size_of::<SigSet>() {
+            true => Ok(()),
+            false => Err(TaskError::EINVAL),
+        }
+    }
+
+    pub async fn sys_rt_sigaction(
+        &self,
+        signo: usize,
+        act: UserBuf<SigAction>,
+        oldact: UserBuf<SigAction>,
+        sigsetsize: usize,
+    ) -> Result<usize, TaskError> {
+        debug!(
+            "sys_rt_sigaction @ signo: {}, act: {}, oldact: {}",
+            signo, act, oldact
+        );
+        Self::check_sigset_size(sigsetsize)?;
+        if !valid_signal(signo) || (act.is_valid() && UNBLOCKABLE.contains(signo)) {
+            return Err(TaskError::EINVAL);
+        }
+        let mut pcb = self.task.pcb.lock();
+        if oldact.is_valid() {
+            oldact.write(pcb.sig_actions[signo]);
+        }
+        if act.is_valid() {
+            let mut action = act.read();
+            action.mask = action.mask.difference(UNBLOCKABLE);
+            pcb.sig_actions[signo] = action;
+            // Setting a signal to be ignored drops it if it is pending.
+            let ignored = match action.handler {
+                SIG_IGN => true,
+                SIG_DFL => DefaultAction::of(signo) == DefaultAction::Ignore,
+                _ => false,
+            };
+            if ignored {
+                pcb.sig_pending.discard(signo);
+                let threads: Vec<_> = pcb.threads.iter().filter_map(|x| x.upgrade()).collect();
+                drop(pcb);
+                threads
+                    .iter()
+                    .for_each(|thread| thread.tcb.write().sig_pending.discard(signo));
+            }
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_rt_sigprocmask(
+        &self,
+        how: usize,
+        set: UserBuf<SigSet>,
+        oldset: UserBuf<SigSet>,
+        sigsetsize: usize,
+    ) -> Result<usize, TaskError> {
+        debug!("sys_rt_sigprocmask @ how: {}, set: {}, oldset: {}", how, set, oldset);
+        Self::check_sigset_size(sigsetsize)?;
+        let mut tcb = self.task.tcb.write();
+        let old = tcb.sig_mask;
+        if set.is_valid() {
+            let set = set.read();
+            let mask = match how {
+                SIG_BLOCK => old.union(set),
+                SIG_UNBLOCK => old.difference(set),
+                SIG_SETMASK => set,
+                _ => return Err(TaskError::EINVAL),
+            };
+            tcb.sig_mask = mask.difference(UNBLOCKABLE);
+        }
+        if oldset.is_valid() {
+            oldset.write(old);
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_kill(&self, pid: isize, signo: usize) -> Result<usize, TaskError> {
+        debug!("sys_kill @ pid: {}, signo: {}", pid, signo);
+        if signo != 0 && !valid_signal(signo) {
+            return Err(TaskError::EINVAL);
+        }
+        let targets: Vec<Arc<UserTask>> = match pid {
+            1.. => vec![self.find_task(pid as usize)?],
+            0 => process_group(self.task.pgid()),
+            // Everyone in our PID namespace but the caller and the processes
+            // the kernel started.
+            -1 => user_processes()
+                .into_iter()
+                .filter(|task| task.process_id != self.task.process_id)
+                .filter(|task| task.parent.read().upgrade().is_some())
+                .filter(|task| self.task.pid_of(task.process_id) != 0)
+                .collect(),
+            _ => match self.task.pid_ns().task_id(pid.unsigned_abs()) {
+                Some(pgid) => process_group(pgid.0),
+                None => vec![],
+            },
+        };
+        if targets.is_empty() {
+            return Err(TaskError::ESRCH);
+        }
+        // SIGCONT may be sent to anyone in the caller's session.
+        let cred = self.task.cred.read().clone();
+        let targets: Vec<Arc<UserTask>> = targets
+            .into_iter()
+            .filter(|task| {
+                cred.may_signal(&task.cred.read()) || (signo == SIGCONT && task.sid() == self.task.sid())
+            })
+            .collect();
+        if targets.is_empty() {
+            return Err(TaskError::EPERM);
+        }
+        // Signal 0 only checks that the target exists.
+        // The target sees our pid in its PID namespace.
+        if signo != 0 {
+            for task in targets.iter() {
+                let pid = task.pid_of(self.task.process_id);
+                task.signal_process(SigInfo::user(signo, SI_USER, pid, cred.uid));
+            }
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_tkill(&self, tid: usize, signo: usize) -> Result<usize, TaskError> {
+        debug!("sys_tkill @ tid: {}, signo: {}", tid, signo);
+        self.send_thread_signal(None, tid, signo)
+    }
+
+    pub async fn sys_tgkill(&self, tgid: usize, tid: usize, signo: usize) -> Result<usize, TaskError> {
+        debug!("sys_tgkill @ tgid: {}, tid: {}, signo: {}", tgid, tid, signo);
+        self.send_thread_signal(Some(tgid), tid, signo)
+    }
+
+    /// Signal thread `tid`, which has to belong to process `tgid` if given.
+    fn send_thread_signal(&self, tgid: Option<usize>, tid: usize, signo: usize) -> Result<usize, TaskError> {
+        if (tid as isize) <= 0 || tgid.is_some_and(|x| (x as isize) <= 0) {
+            return Err(TaskError::EINVAL);
+        }
+        if signo != 0 && !valid_signal(signo) {
+            return Err(TaskError::EINVAL);
+        }
+        let task = self.find_task(tid)?;
+        if tgid.is_some_and(|tgid| self.task.pid_of(task.process_id) != tgid) {
+            return Err(TaskError::ESRCH);
+        }
+        if !self.task.cred.read().may_signal(&task.cred.read()) {
+            return Err(TaskError::EPERM);
+        }
+        if signo != 0 {
+            let pid = task.pid_of(self.task.process_id);
+            task.signal_thread(SigInfo::user(signo, SI_TKILL, pid, self.task.cred.read().uid));
+        }
+        Ok(0)
+    }
+
+    pub async fn sys_rt_sigtimedwait(
+        &self,
+        set: UserBuf<SigSet>,
+        info: UserBuf<SigInfo>,
+        timeout_ptr: UserBuf<TimeSpec>,
+        sigsetsize: usize,
+    ) -> Result<usize, TaskError> {
+        debug!(
+            "sys_rt_sigtimedwait @ set: {}, info: {}, timeout: {}",
+            set, info, timeout_ptr
+        );
+        Self::check_sigset_size(sigsetsize)?;
+        if !set.is_valid() {
+            return Err(TaskError::EFAULT);
+        }
+        let set = set.read().difference(UNBLOCKABLE);
+        // Senders wake every thread that may take the signal.
+        let wait = self.interruptible(poll_fn(|_| match self.task.dequeue_signal(set) {
+            Some(info) => Poll::Ready(info),
+            None => Poll::Pending,
+        }));
+        let siginfo = match timeout_ptr.is_valid() {
+            true => {
+                let duration = timeout_ptr.read().to_duration().ok_or(TaskError::EINVAL)?;
+                timeout(wait, duration).await.map_err(|_| TaskError::EAGAIN)??
+            }
+            false => wait.await?,
+        };
+        if info.is_valid() {
+            info.write(siginfo);
+        }
+        Ok(siginfo.signo as usize)
+    }
+
+    /// Return from a signal handler to the context saved by
+    /// [`UserHandler::handle_signals`], the frame sits right above the stack
+    /// pointer the handler was entered with.
+    pub async fn sys_rt_sigreturn(&self) -> Result<usize, TaskError> {
+        let cx = self.task.force_cx_ref();
+        let uc_ptr = cx[TrapFrameArgs::SP].wrapping_add(size_of::<SigInfo>());
+        debug!("sys_rt_sigreturn @ ucontext: {:#x}", uc_ptr);
+        if !self.user_range_mapped(uc_ptr, uc_ptr.wrapping_add(size_of::<UContext>())) {
+            self.task.exit_by_signal(SIGSEGV);
+            return Ok(0);
+        }
+        let uc = UserBuf::new(uc_ptr as *mut UContext).read();
+        let gregs = uc.mcontext.gregs;
+        cx.x[1..].copy_from_slice(&gregs[1..]);
+        cx.sepc = gregs[0];
+        self.task.tcb.write().sig_mask = uc.sigmask.difference(UNBLOCKABLE);
+        // The return value goes to a0, hand back the saved one.
+        Ok(gregs[10])
+    }
+}
@@ -40,16 +40,33 @@ pub const SYS_FSYNC: usize = 82;
 pub const SYS_UTIMENSAT: usize = 88;
 pub const SYS_EXIT: usize = 93;
 pub const SYS_EXIT_GROUP: usize = 94;
+pub const SYS_WAITID: usize = 95;
 pub const SYS_SET_TID_ADDRESS: usize = 96;
+pub const SYS_UNSHARE: usize = 97;
 pub const SYS_FUTEX: usize = 98;
 pub const SYS_SET_ROBUST_LIST: usize = 99;
 pub const SYS_GET_ROBUST_LIST: usize = 100;
 pub const SYS_NANOSLEEP: usize = 101;
 pub const SYS_GETITIMER: usize = 102;
 pub const SYS_SETITIMER: usize = 103;
+pub const SYS_TIMER_CREATE: usize = 107;
+pub const SYS_TIMER_GETTIME: usize = 108;
+pub const SYS_TIMER_GETOVERRUN: usize = 109;
+pub const SYS_TIMER_SETTIME: usize = 110;
+pub const SYS_TIMER_DELETE: usize = 111;
 pub const SYS_CLOCK_GETTIME: usize = 113;
+pub const SYS_CLOCK_NANOSLEEP: usize = 115;
+pub const SYS_PTRACE: usize = 117;
+pub const SYS_SCHED_SETPARAM: usize = 118;
+pub const SYS_SCHED_SETSCHEDULER: usize = 119;
+pub const SYS_SCHED_GETSCHEDULER: usize = 120;
+pub const SYS_SCHED_GETPARAM: usize = 121;
+pub const SYS_SCHED_SETAFFINITY: usize = 122;
+pub const SYS_SCHED_GETAFFINITY: usize = 123;
 pub const SYS_YIELD: usize = 124;
 pub const SYS_SCHED_YIELD: usize = 124;
+pub const SYS_SCHED_GET_PRIORITY_MAX: usize = 125;
+pub const SYS_SCHED_GET_PRIORITY_MIN: usize = 126;
 pub const SYS_KILL: usize = 129;
 pub const SYS_TKILL: usize = 130;
 pub const SYS_TGKILL: usize = 131;
@@ -57,9 +74,30 @@ pub const SYS_SIGACTION: usize = 134;
 pub const SYS_SIGPROCMASK: usize = 135;
 pub const SYS_SIGTIMEDWAIT: usize = 137;
 pub const SYS_SIGRETURN: usize = 139;
+pub const SYS_SETPRIORITY: usize = 140;
+pub const SYS_GETPRIORITY: usize = 141;
+pub const SYS_SETREGID: usize = 143;
+pub const SYS_SETGID: usize = 144;
+pub const SYS_SETREUID: usize = 145;
+pub const SYS_SETUID: usize = 146;
+pub const SYS_SETRESUID: usize = 147;
+pub const SYS_GETRESUID: usize = 148;
+pub const SYS_SETRESGID: usize = 149;
+pub const SYS_GETRESGID: usize = 150;
 pub const SYS_TIMES: usize = 153;
+pub const SYS_SETPGID: usize = 154;
+pub const SYS_GETPGID: usize = 155;
+pub const SYS_GETSID: usize = 156;
+pub const SYS_SETSID: usize = 157;
+pub const SYS_GETGROUPS: usize = 158;
+pub const SYS_SETGROUPS: usize = 159;
 pub const SYS_UNAME: usize = 160;
+pub const SYS_SETHOSTNAME: usize = 161;
+pub const SYS_SETDOMAINNAME: usize = 162;
+pub const SYS_GETRLIMIT: usize = 163;
+pub const SYS_SETRLIMIT: usize = 164;
 pub const SYS_GETRUSAGE: usize = 165;
+pub const SYS_UMASK: usize = 166;
 pub const SYS_GETTIMEOFDAY: usize = 169;
 pub const SYS_GETPID: usize = 172;
 pub const SYS_GETPPID: usize = 173;
@@ -79,7 +117,9 @@ pub const SYS_MPROTECT: usize = 226;
 pub const SYS_MSYNC: usize = 227;
 pub const SYS_WAIT4: usize = 260;
 pub const SYS_PRLIMIT64: usize = 261;
+pub const SYS_SETNS: usize = 268;
 pub const SYS_RENAMEAT2: usize = 276;
+pub const SYS_PIDFD_OPEN: usize = 434;
 
 
 
@@ -88,6 +128,23 @@ pub fn sysnum_to_string(sys_num: usize) -> String {
         SYS_IOCTL  => "SYS_IOCTL".into(),
         SYS_GETCWD => "SYS_GETCWD".into(),
         SYS_GETUID => "SYS_GETUID".into(),
+        SYS_GETRLIMIT => "SYS_GETRLIMIT".into(),
+        SYS_SETRLIMIT => "SYS_SETRLIMIT".into(),
+        SYS_SETREGID => "SYS_SETREGID".into(),
+        SYS_SETGID => "SYS_SETGID".into(),
+        SYS_SETREUID => "SYS_SETREUID".into(),
+        SYS_SETUID => "SYS_SETUID".into(),
+        SYS_SETRESUID => "SYS_SETRESUID".into(),
+        SYS_GETRESUID => "SYS_GETRESUID".into(),
+        SYS_SETRESGID => "SYS_SETRESGID".into(),
+        SYS_GETRESGID => "SYS_GETRESGID".into(),
+        SYS_GETGROUPS => "SYS_GETGROUPS".into(),
+        SYS_SETGROUPS => "SYS_SETGROUPS".into(),
+        SYS_SETHOSTNAME => "SYS_SETHOSTNAME".into(),
+        SYS_SETDOMAINNAME => "SYS_SETDOMAINNAME".into(),
+        SYS_GETEUID => "SYS_GETEUID".into(),
+        SYS_GETGID => "SYS_GETGID".into(),
+        SYS_GETEGID => "SYS_GETEGID".into(),
         SYS_DUP => "SYS_DUP".into(),
         SYS_DUP3 => "SYS_DUP3".into(),
         SYS_MKDIRAT => "SYS_MKDIRAT".into(),
@@ -120,15 +177,40 @@ pub fn sysnum_to_string(sys_num: usize) -> String {
         SYS_UTIMENSAT => "SYS_UTIMENSAT".into(),
         SYS_EXIT => "SYS_EXIT".into(),
         SYS_EXIT_GROUP => "SYS_EXIT_GROUP".into(),
+        SYS_WAITID => "SYS_WAITID".into(),
         SYS_SET_TID_ADDRESS => "SYS_SET_TID_ADDRESS".into(),
+        SYS_UNSHARE => "SYS_UNSHARE".into(),
         SYS_FUTEX => "SYS_FUTEX".into(),
         SYS_SET_ROBUST_LIST => "SYS_SET_ROBUST_LIST".into(),
         SYS_GET_ROBUST_LIST => "SYS_GET_ROBUST_LIST".into(),
         SYS_NANOSLEEP => "SYS_NANOSLEEP".into(),
         SYS_GETITIMER => "SYS_GETITIMER".into(),
         SYS_SETITIMER => "SYS_SETITIMER".into(),
+        SYS_TIMER_CREATE => "SYS_TIMER_CREATE".into(),
+        SYS_TIMER_GETTIME => "SYS_TIMER_GETTIME".into(),
+        SYS_TIMER_GETOVERRUN => "SYS_TIMER_GETOVERRUN".into(),
+        SYS_TIMER_SETTIME => "SYS_TIMER_SETTIME".into(),
+        SYS_TIMER_DELETE => "SYS_TIMER_DELETE".into(),
         SYS_CLOCK_GETTIME => "SYS_CLOCK_GETTIME".into(),
+        SYS_CLOCK_NANOSLEEP => "SYS_CLOCK_NANOSLEEP".into(),
+        SYS_PTRACE => "SYS_PTRACE".into(),
+        SYS_SCHED_SETPARAM => "SYS_SCHED_SETPARAM".into(),
+        SYS_SCHED_SETSCHEDULER => "SYS_SCHED_SETSCHEDULER".into(),
+        SYS_SCHED_GETSCHEDULER => "SYS_SCHED_GETSCHEDULER".into(),
+        SYS_SCHED_GETPARAM => "SYS_SCHED_GETPARAM".into(),
+        SYS_SCHED_SETAFFINITY => "SYS_SCHED_SETAFFINITY".into(),
+        SYS_SCHED_GETAFFINITY => "SYS_SCHED_GETAFFINITY".into(),
         SYS_YIELD => "SYS_SCHED_YIELD".into(),
+        SYS_SCHED_GET_PRIORITY_MAX => "SYS_SCHED_GET_PRIORITY_MAX".into(),
+        SYS_SCHED_GET_PRIORITY_MIN => "SYS_SCHED_GET_PRIORITY_MIN".into(),
+        SYS_SETPRIORITY => "SYS_SETPRIORITY".into(),
+        SYS_GETPRIORITY => "SYS_GETPRIORITY".into(),
+        SYS_GETRUSAGE => "SYS_GETRUSAGE".into(),
+        SYS_UMASK => "SYS_UMASK".into(),
+        SYS_SETPGID => "SYS_SETPGID".into(),
+        SYS_GETPGID => "SYS_GETPGID".into(),
+        SYS_GETSID => "SYS_GETSID".into(),
+        SYS_SETSID => "SYS_SETSID".into(),
         SYS_GETPID => "SYS_GETPID".into(),
         SYS_GETTID => "SYS_GETTID".into(),
         SYS_GETPPID => "SYS_GETPPID".into(),
@@ -141,7 +223,9 @@ pub fn sysnum_to_string(sys_num: usize) -> String {
         SYS_MSYNC => "SYS_MSYNC".into(),
         SYS_WAIT4 => "SYS_WAIT4".into(),
         SYS_PRLIMIT64 => "SYS_PRLIMIT64".into(),
+        SYS_SETNS => "SYS_SETNS".into(),
         SYS_RENAMEAT2 => "SYS_RENAMEAT2".into(),
+        SYS_PIDFD_OPEN => "SYS_PIDFD_OPEN".into(),
         _ => "UNKNOWN".into(),
     }
 }
\ No newline at end of file